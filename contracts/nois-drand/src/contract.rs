@@ -7,6 +7,9 @@ use cw2::set_contract_version;
 use cw_storage_plus::Bound;
 use drand_common::DRAND_MAINNET2_PUBKEY;
 use drand_verify::{derive_randomness, G2PubkeyFastnet, Pubkey};
+use manager_transfer::{
+    accept_manager, cancel_manager_transfer, propose_manager, query_pending_manager,
+};
 
 use crate::attributes::{
     ATTR_BOT, ATTR_RANDOMNESS, ATTR_REWARD_PAYOUT, ATTR_REWARD_POINTS, ATTR_ROUND,
@@ -89,7 +92,6 @@ pub fn execute(
             execute_update_allowlist_bots(deps, info, add, remove)
         }
         ExecuteMsg::SetConfig {
-            manager,
             gateway,
             min_round,
             incentive_point_price,
@@ -97,12 +99,14 @@ pub fn execute(
         } => execute_set_config(
             deps,
             info,
-            manager,
             gateway,
             min_round,
             incentive_point_price,
             incentive_denom,
         ),
+        ExecuteMsg::ProposeManager { manager } => execute_propose_manager(deps, info, manager),
+        ExecuteMsg::AcceptManager {} => execute_accept_manager(deps, info),
+        ExecuteMsg::CancelManagerTransfer {} => execute_cancel_manager_transfer(deps, info),
     }
 }
 
@@ -125,6 +129,7 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
         QueryMsg::Bots {} => to_json_binary(&query_bots(deps)?)?,
         QueryMsg::Allowlist {} => to_json_binary(&query_allowlist(deps)?)?,
         QueryMsg::IsAllowlisted { bot } => to_json_binary(&query_is_allowlisted(deps, bot)?)?,
+        QueryMsg::PendingManager {} => to_json_binary(&query_pending_manager(deps.storage)?)?,
     };
     Ok(response)
 }
@@ -495,7 +500,6 @@ fn execute_add_round(
 fn execute_set_config(
     deps: DepsMut,
     info: MessageInfo,
-    manager: Option<String>,
     gateway: Option<String>,
     min_round: Option<u64>,
     incentive_point_price: Option<Uint128>,
@@ -510,10 +514,8 @@ fn execute_set_config(
         Some(gateway) => Some(deps.api.addr_validate(&gateway)?),
         None => config.gateway,
     };
-    let manager = match manager {
-        Some(ma) => deps.api.addr_validate(&ma)?,
-        None => config.manager,
-    };
+    // Use ProposeManager/AcceptManager for this
+    let manager = config.manager;
     let min_round = min_round.unwrap_or(config.min_round);
     let incentive_point_price = incentive_point_price.unwrap_or(config.incentive_point_price);
     let incentive_denom = incentive_denom.unwrap_or(config.incentive_denom);
@@ -531,6 +533,52 @@ fn execute_set_config(
     Ok(Response::default())
 }
 
+fn execute_propose_manager(
+    deps: DepsMut,
+    info: MessageInfo,
+    manager: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check the calling address is the authorised multisig
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let manager = deps.api.addr_validate(&manager)?;
+    propose_manager(deps.storage, &manager)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_manager")
+        .add_attribute("pending_manager", manager))
+}
+
+fn execute_accept_manager(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    let manager = accept_manager(deps.storage, &info.sender)?;
+    config.manager = manager.clone();
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_manager")
+        .add_attribute("manager", manager))
+}
+
+fn execute_cancel_manager_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check the calling address is the authorised multisig
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let cancelled = cancel_manager_transfer(deps.storage)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_manager_transfer")
+        .add_attribute("pending_manager", cancelled))
+}
+
 /// Returns true if this round is incentivized for the given `sender`.
 /// Being incentivized for the bot is a basic property of a round a bot
 /// should check. However, it does not guarantee an incentive. Further checks
@@ -554,6 +602,7 @@ mod tests {
 
     use super::*;
     use crate::msg::ExecuteMsg;
+    use manager_transfer::{ManagerTransferError, PendingManagerResponse};
 
     use cosmwasm_std::testing::{
         message_info, mock_dependencies, mock_dependencies_with_balance, mock_env, MockApi,
@@ -727,7 +776,6 @@ mod tests {
 
         // Set gateway
         let msg = ExecuteMsg::SetConfig {
-            manager: None,
             gateway: Some(gateway.to_string()),
             min_round: None,
             incentive_point_price: None,
@@ -869,7 +917,6 @@ mod tests {
 
         // Set gateway
         let msg = ExecuteMsg::SetConfig {
-            manager: None,
             gateway: Some(gateway.to_string()),
             min_round: None,
             incentive_point_price: None,
@@ -2254,7 +2301,7 @@ mod tests {
     }
 
     #[test]
-    fn only_manager_can_set_manager() {
+    fn only_manager_can_propose_manager() {
         let mut deps = mock_dependencies();
 
         let creator = deps.api.addr_make("creator");
@@ -2272,41 +2319,55 @@ mod tests {
         };
         instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // A random addr cannot set a new manager
+        // A random addr cannot propose a new manager
         let info = message_info(&guest, &[]);
-        let msg = ExecuteMsg::SetConfig {
-            manager: Some(new_manager.to_string()),
-            gateway: None,
-            incentive_denom: None,
-            incentive_point_price: None,
-            min_round: None,
+        let msg = ExecuteMsg::ProposeManager {
+            manager: new_manager.to_string(),
         };
         let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
         assert!(matches!(err, ContractError::Unauthorized));
 
-        // Creator cannot set a new manager
+        // Creator cannot propose a new manager
         let info = message_info(&creator, &[]);
-        let msg = ExecuteMsg::SetConfig {
-            manager: Some(new_manager.to_string()),
-            gateway: None,
-            incentive_denom: None,
-            incentive_point_price: None,
-            min_round: None,
+        let msg = ExecuteMsg::ProposeManager {
+            manager: new_manager.to_string(),
         };
-
         let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
         assert!(matches!(err, ContractError::Unauthorized));
 
-        // Manager can set a new manager
+        // Manager can propose a new manager
         let info = message_info(&manager, &[]);
-        let msg = ExecuteMsg::SetConfig {
-            manager: Some(new_manager.to_string()),
-            gateway: None,
-            incentive_denom: None,
-            incentive_point_price: None,
-            min_round: None,
+        let msg = ExecuteMsg::ProposeManager {
+            manager: new_manager.to_string(),
         };
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let pending: PendingManagerResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending.pending_manager, Some(new_manager.clone()));
+
+        // Only the pending manager can accept
+        let info = message_info(&guest, &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ManagerTransfer(ManagerTransferError::NotPendingManager)
+        ));
+
+        let info = message_info(&new_manager, &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap();
         let config: ConfigResponse =
             from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
         assert_eq!(
@@ -2319,5 +2380,13 @@ mod tests {
                 incentive_denom: "unois".to_string(),
             }
         );
+
+        // The old manager lost its privileges
+        let info = message_info(&manager, &[]);
+        let msg = ExecuteMsg::ProposeManager {
+            manager: manager.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
     }
 }
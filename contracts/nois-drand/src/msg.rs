@@ -1,5 +1,6 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
 use cosmwasm_std::{Addr, HexBinary, Timestamp, Uint128};
+use manager_transfer::PendingManagerResponse;
 
 use crate::state::{Config, QueriedBeacon, QueriedBot, StoredSubmission};
 
@@ -35,12 +36,17 @@ pub enum ExecuteMsg {
         remove: Vec<String>,
     },
     SetConfig {
-        manager: Option<String>,
         gateway: Option<String>,
         min_round: Option<u64>,
         incentive_point_price: Option<Uint128>,
         incentive_denom: Option<String>,
     },
+    /// Proposes a new manager. The new manager has to accept the role using `AcceptManager`.
+    ProposeManager { manager: String },
+    /// Accepts the manager role. Must be sent by the pending manager.
+    AcceptManager {},
+    /// Cancels a pending manager transfer. Only the current manager can do this.
+    CancelManagerTransfer {},
 }
 
 #[cw_serde]
@@ -93,6 +99,9 @@ pub enum QueryMsg {
         /// The address of the bot
         bot: String,
     },
+    /// Gets the manager that was proposed but did not accept yet
+    #[returns(PendingManagerResponse)]
+    PendingManager {},
 }
 
 // We define a custom struct for each query response
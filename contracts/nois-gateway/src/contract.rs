@@ -1,17 +1,22 @@
 use cosmwasm_std::{
     attr, ensure, ensure_eq, entry_point, from_json, instantiate2_address, to_json_binary, Addr,
-    Attribute, Binary, CodeInfoResponse, Coin, Deps, DepsMut, Empty, Env, Event, HexBinary,
-    Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannelCloseMsg, IbcChannelConnectMsg,
+    Attribute, Binary, CodeInfoResponse, Coin, CosmosMsg, Deps, DepsMut, Empty, Env, Event,
+    HexBinary, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannelCloseMsg, IbcChannelConnectMsg,
     IbcChannelOpenMsg, IbcChannelOpenResponse, IbcMsg, IbcPacketAckMsg, IbcPacketReceiveMsg,
     IbcPacketTimeoutMsg, IbcReceiveResponse, MessageInfo, Never, Order, QueryRequest,
     QueryResponse, Response, StdAck, StdError, StdResult, SystemError, SystemResult, Timestamp,
-    WasmMsg, WasmQuery,
+    Uint128, WasmMsg, WasmQuery,
 };
 use cw2::set_contract_version;
 use cw_storage_plus::Bound;
+use drand_common::round_after;
+use manager_transfer::{
+    accept_manager, cancel_manager_transfer, propose_manager, query_pending_manager,
+};
 use nois_protocol::{
-    check_order, check_version, InPacket, InPacketAck, OutPacket, OutPacketAck,
-    BEACON_PRICE_PACKET_LIFETIME, IBC_APP_VERSION, WELCOME_PACKET_LIFETIME,
+    check_order, is_v8_or_later, negotiate_version, InPacket, InPacketAck, OutPacket, OutPacketAck,
+    PacketError, RequestBeaconStatus, BEACON_PRICE_PACKET_LIFETIME, MAX_BEACON_REQUESTS_PER_PACKET,
+    WELCOME_PACKET_LIFETIME,
 };
 use sha2::{Digest, Sha256};
 
@@ -19,16 +24,21 @@ use crate::attributes::ATTR_ACTION;
 use crate::error::ContractError;
 use crate::job_id::validate_origin;
 use crate::msg::{
-    ConfigResponse, CustomerResponse, CustomersResponse, DrandJobStatsResponse, ExecuteMsg,
-    InstantiateMsg, JobsResponse, QueriedCustomer, QueryMsg, RequestsLogResponse,
+    ConfigResponse, CustomerResponse, CustomersResponse, DrandJobStatsRangeResponse,
+    DrandJobStatsResponse, ExecuteMsg, InstantiateMsg, JobsResponse, QueriedCustomer, QueryMsg,
+    RequestsLogResponse,
 };
 use crate::request_router::{NewDrand, RequestRouter, RoutingReceipt};
 use crate::state::{
-    all_unprocessed_drand_jobs, get_processed_drand_jobs, requests_log_add, requests_log_asc,
-    requests_log_desc, unprocessed_drand_jobs_len, Config, Customer, RequestLogEntry, CONFIG,
-    CUSTOMERS,
+    all_unprocessed_drand_jobs, check_and_count_requests, get_processed_drand_jobs,
+    recount_unprocessed_drand_jobs, requests_log_add, requests_log_asc, requests_log_desc,
+    set_queued_jobs, unprocessed_drand_jobs_len, unprocessed_drand_jobs_rounds, Config, Customer,
+    CustomerLimits, RequestLogEntry, CONFIG, CUSTOMERS,
 };
 
+/// How far in the future a requested drand round can be (10 years in seconds)
+const MAX_ROUND_DISTANCE_S: u64 = 10 * 3600 * 24 * 365;
+
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
@@ -57,6 +67,7 @@ pub fn instantiate(
         payment_code_id,
         payment_initial_funds,
         sink,
+        paused: None,
     };
     CONFIG.save(deps.storage, &config)?;
 
@@ -90,6 +101,15 @@ pub fn migrate(deps: DepsMut, _env: Env, _msg: Empty) -> StdResult<Response> {
 
     CONFIG.save(deps.storage, &config)?;
 
+    // Previous versions did not maintain the unprocessed jobs counters and the number of
+    // queued jobs per customer. Once the counters exist, they are maintained by this
+    // version, so the scan over all jobs is only needed for the first migration.
+    if unprocessed_drand_jobs_rounds(deps.storage, None, 1)?.is_empty() {
+        for (channel_id, queued_jobs) in recount_unprocessed_drand_jobs(deps.storage)? {
+            set_queued_jobs(deps.storage, &channel_id, queued_jobs)?;
+        }
+    }
+
     set_contract_version(
         deps.storage,
         env!("CARGO_PKG_NAME"),
@@ -113,21 +133,41 @@ pub fn execute(
             is_verifying_tx,
         } => execute_add_verified_round(deps, env, info, round, randomness, is_verifying_tx),
         ExecuteMsg::SetConfig {
-            manager,
             price,
             drand_addr,
             payment_initial_funds,
             trusted_sources,
+            sink,
         } => execute_set_config(
             deps,
             info,
             env,
-            manager,
             price,
             drand_addr,
             trusted_sources,
             payment_initial_funds,
+            sink,
         ),
+        ExecuteMsg::SetPaymentCodeId { code_id } => {
+            execute_set_payment_code_id(deps, info, code_id)
+        }
+        ExecuteMsg::MigratePayments { start_after, limit } => {
+            execute_migrate_payments(deps, info, start_after, limit)
+        }
+        ExecuteMsg::UpdatePaymentSinks { start_after, limit } => {
+            execute_update_payment_sinks(deps, info, start_after, limit)
+        }
+        ExecuteMsg::SetCustomerLimits { channel_id, limits } => {
+            execute_set_customer_limits(deps, info, channel_id, limits)
+        }
+        ExecuteMsg::SetCustomerSuspended {
+            channel_id,
+            suspended,
+        } => execute_set_customer_suspended(deps, info, channel_id, suspended),
+        ExecuteMsg::ProposeManager { manager } => execute_propose_manager(deps, info, manager),
+        ExecuteMsg::AcceptManager {} => execute_accept_manager(deps, info),
+        ExecuteMsg::CancelManagerTransfer {} => execute_cancel_manager_transfer(deps, info),
+        ExecuteMsg::SetPaused { paused } => execute_set_paused(deps, info, paused),
     }
 }
 
@@ -136,6 +176,9 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
     let response = match msg {
         QueryMsg::Config {} => to_json_binary(&query_config(deps)?)?,
         QueryMsg::DrandJobStats { round } => to_json_binary(&query_drand_job_stats(deps, round)?)?,
+        QueryMsg::DrandJobStatsRange { start_after, limit } => {
+            to_json_binary(&query_drand_job_stats_range(deps, start_after, limit)?)?
+        }
         QueryMsg::Customer { channel_id } => to_json_binary(&query_customer(deps, channel_id)?)?,
         QueryMsg::Customers { start_after, limit } => {
             to_json_binary(&query_customers(deps, start_after, limit)?)?
@@ -156,6 +199,7 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
             offset,
             limit,
         } => to_json_binary(&query_requests_desc(deps, channel_id, offset, limit)?)?,
+        QueryMsg::PendingManager {} => to_json_binary(&query_pending_manager(deps.storage)?)?,
     };
     Ok(response)
 }
@@ -176,6 +220,27 @@ fn query_drand_job_stats(deps: Deps, round: u64) -> StdResult<DrandJobStatsRespo
     })
 }
 
+/// Query drand job stats for all rounds with unprocessed jobs
+fn query_drand_job_stats_range(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<DrandJobStatsRangeResponse> {
+    let limit = limit.unwrap_or(50) as usize;
+    let rounds = unprocessed_drand_jobs_rounds(deps.storage, start_after, limit)?
+        .into_iter()
+        .map(|(round, unprocessed)| {
+            let processed = get_processed_drand_jobs(deps.storage, round)?;
+            Ok(DrandJobStatsResponse {
+                round,
+                unprocessed,
+                processed,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(DrandJobStatsRangeResponse { rounds })
+}
+
 fn query_customer(deps: Deps, channel_id: String) -> StdResult<CustomerResponse> {
     let customer = CUSTOMERS.may_load(deps.storage, &channel_id)?;
     Ok(CustomerResponse {
@@ -256,11 +321,11 @@ pub fn ibc_channel_open(
     check_order(&channel.order)?;
     // In ibcv3 we don't check the version string passed in the message
     // and only check the counterparty version.
-    check_version(&counterparty_version)?;
+    let version = negotiate_version(&counterparty_version)?;
 
-    // We return the version we need (which could be different than the counterparty version)
+    // We return the highest version both sides support (which could be different than the counterparty version)
     Ok(Some(Ibc3ChannelOpenResponse {
-        version: IBC_APP_VERSION.to_string(),
+        version: version.to_string(),
     }))
 }
 
@@ -298,6 +363,9 @@ pub fn ibc_channel_connect(
     let customer = Customer {
         payment: address,
         requested_beacons: 0,
+        limits: None,
+        version: Some(channel.version),
+        suspended: None,
     };
     CUSTOMERS.save(deps.storage, &chan_id, &customer)?;
 
@@ -379,8 +447,24 @@ pub fn ibc_packet_receive(
     (|| {
         let op: InPacket = from_json(&packet.data)?;
         match op {
-            InPacket::RequestBeacon { after, origin } => {
-                receive_request_beacon(deps, env, channel_id, relayer, after, origin)
+            InPacket::RequestBeacon { after, origin } => receive_request_beacon(
+                deps,
+                env,
+                channel_id,
+                relayer,
+                RequestedBeacon::After(after),
+                origin,
+            ),
+            InPacket::RequestBeaconAtRound { round, origin } => receive_request_beacon(
+                deps,
+                env,
+                channel_id,
+                relayer,
+                RequestedBeacon::Round(round),
+                origin,
+            ),
+            InPacket::RequestBeacons { requests } => {
+                receive_request_beacons(deps, env, channel_id, relayer, requests)
             }
             InPacket::PullBeaconPrice {} => receive_pull_beacon_price(deps, env),
             _ => Err(ContractError::UnsupportedPacketType),
@@ -389,21 +473,64 @@ pub fn ibc_packet_receive(
     .or_else(|e| {
         // we try to capture all app-level errors and convert them into
         // acknowledgement packets that contain an error code.
-        let acknowledgement = StdAck::error(format!("Error processing packet: {e}"));
+        let acknowledgement = PacketError::new(
+            e.packet_error_code(),
+            format!("Error processing packet: {e}"),
+        )
+        .to_ack();
         Ok(IbcReceiveResponse::new(acknowledgement)
             .add_event(Event::new("ibc").add_attribute("packet", "receive")))
     })
 }
 
+/// The beacon requested by a single request packet
+enum RequestedBeacon {
+    /// The first beacon published after this time (`InPacket::RequestBeacon`)
+    After(Timestamp),
+    /// A specific drand round (`InPacket::RequestBeaconAtRound`)
+    Round(u64),
+}
+
 fn receive_request_beacon(
     mut deps: DepsMut,
     env: Env,
     channel_id: String,
     relayer: Addr,
-    after: Timestamp,
+    requested: RequestedBeacon,
     origin: Binary,
 ) -> Result<IbcReceiveResponse, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure!(!config.paused.unwrap_or_default(), ContractError::Paused);
+
     validate_origin(&origin)?;
+    let action = match requested {
+        RequestedBeacon::After(_) => "receive_request_beacon",
+        RequestedBeacon::Round(_) => "receive_request_beacon_at_round",
+    };
+    // Rounds published until now are known to the requester and must not be used
+    if let RequestedBeacon::Round(round) = requested {
+        ensure!(
+            round >= round_after(env.block.time),
+            ContractError::RoundNotInFuture { round }
+        );
+        let max_round = round_after(env.block.time.plus_seconds(MAX_ROUND_DISTANCE_S));
+        ensure!(
+            round <= max_round,
+            ContractError::RoundTooHigh { round, max_round }
+        );
+    }
+
+    let mut customer = CUSTOMERS.load(deps.storage, &channel_id)?;
+    // Round requests were introduced in nois-v8 and must not be used on older channels
+    if let RequestedBeacon::Round(_) = requested {
+        ensure!(
+            is_v8_or_later(customer.version.as_deref()),
+            ContractError::UnsupportedPacketType
+        );
+    }
+    check_customer(deps.as_ref(), &channel_id, &customer, &config.price, 1)?;
+    let limits = customer.limits.clone().unwrap_or_default();
+    check_and_count_requests(deps.storage, &env, &channel_id, &limits, 1)?;
 
     let router = RequestRouter::new();
     let RoutingReceipt {
@@ -411,13 +538,22 @@ fn receive_request_beacon(
         source_id,
         acknowledgement,
         mut msgs,
-    } = router.route(
-        deps.branch(),
-        &env,
-        channel_id.clone(),
-        after,
-        origin.clone(),
-    )?;
+    } = match requested {
+        RequestedBeacon::After(after) => router.route(
+            deps.branch(),
+            &env,
+            channel_id.clone(),
+            after,
+            origin.clone(),
+        )?,
+        RequestedBeacon::Round(round) => router.route_drand_round(
+            deps.branch(),
+            &env,
+            channel_id.clone(),
+            round,
+            origin.clone(),
+        )?,
+    };
 
     // Store request
     requests_log_add(
@@ -432,31 +568,153 @@ fn receive_request_beacon(
     )?;
 
     // Pay time
-    let mut customer = CUSTOMERS.load(deps.storage, &channel_id)?;
     customer.requested_beacons += 1;
     CUSTOMERS.save(deps.storage, &channel_id, &customer)?;
 
+    let msg = create_pay_msg(config.price, 1, relayer, customer.payment)?;
+    msgs.push(msg.into());
+
+    Ok(IbcReceiveResponse::new(acknowledgement)
+        .add_messages(msgs)
+        .add_attribute("action", action))
+}
+
+fn receive_request_beacons(
+    mut deps: DepsMut,
+    env: Env,
+    channel_id: String,
+    relayer: Addr,
+    requests: Vec<(Timestamp, Binary)>,
+) -> Result<IbcReceiveResponse, ContractError> {
     let config = CONFIG.load(deps.storage)?;
+    ensure!(!config.paused.unwrap_or_default(), ContractError::Paused);
 
-    let Coin { amount, denom } = config.price;
+    ensure!(!requests.is_empty(), ContractError::NoBeaconRequests);
+    ensure!(
+        requests.len() <= MAX_BEACON_REQUESTS_PER_PACKET,
+        ContractError::TooManyBeaconRequests {
+            max: MAX_BEACON_REQUESTS_PER_PACKET
+        }
+    );
+    for (_, origin) in &requests {
+        validate_origin(origin)?;
+    }
+    let count = requests.len() as u32;
+
+    // All checks happen before the first request is routed such that the batch
+    // is either processed entirely or not at all.
+    let mut customer = CUSTOMERS.load(deps.storage, &channel_id)?;
+    // Batches were introduced in nois-v8 and must not be used on older channels
+    ensure!(
+        is_v8_or_later(customer.version.as_deref()),
+        ContractError::UnsupportedPacketType
+    );
+    check_customer(deps.as_ref(), &channel_id, &customer, &config.price, count)?;
+    let limits = customer.limits.clone().unwrap_or_default();
+    check_and_count_requests(deps.storage, &env, &channel_id, &limits, count)?;
+
+    let router = RequestRouter::new();
+    let tx = (
+        env.block.height,
+        env.transaction.as_ref().map(|ti| ti.index),
+    );
+    let mut statuses = Vec::with_capacity(requests.len());
+    let mut msgs = Vec::<CosmosMsg>::new();
+    for (after, origin) in requests {
+        let RoutingReceipt {
+            queued,
+            source_id,
+            msgs: routing_msgs,
+            ..
+        } = router.route(
+            deps.branch(),
+            &env,
+            channel_id.clone(),
+            after,
+            origin.clone(),
+        )?;
+
+        requests_log_add(
+            deps.storage,
+            &channel_id,
+            &RequestLogEntry {
+                origin,
+                tx,
+                source_id: source_id.clone(),
+                queued,
+            },
+        )?;
+
+        statuses.push(RequestBeaconStatus { source_id, queued });
+        for msg in routing_msgs {
+            // Multiple requests for the same round lead to equal messages to the drand contract
+            if !msgs.contains(&msg) {
+                msgs.push(msg);
+            }
+        }
+    }
+
+    // Pay time
+    customer.requested_beacons += count as u64;
+    CUSTOMERS.save(deps.storage, &channel_id, &customer)?;
+
+    let msg = create_pay_msg(config.price, count, relayer, customer.payment)?;
+    msgs.push(msg.into());
+
+    let acknowledgement =
+        StdAck::success(to_json_binary(&InPacketAck::RequestBeacons { statuses })?);
+    Ok(IbcReceiveResponse::new(acknowledgement)
+        .add_messages(msgs)
+        .add_attribute("action", "receive_request_beacons")
+        .add_attribute("count", count.to_string()))
+}
+
+/// Checks that the customer is not suspended and that its payment contract
+/// can pay for `count` beacons.
+fn check_customer(
+    deps: Deps,
+    channel_id: &str,
+    customer: &Customer,
+    price: &Coin,
+    count: u32,
+) -> Result<(), ContractError> {
+    ensure!(
+        !customer.suspended.unwrap_or_default(),
+        ContractError::CustomerSuspended {
+            channel_id: channel_id.to_string()
+        }
+    );
+    let required = Coin {
+        denom: price.denom.clone(),
+        amount: price.amount * Uint128::from(count),
+    };
+    let balance = deps
+        .querier
+        .query_balance(&customer.payment, &price.denom)?;
+    ensure!(
+        balance.amount >= required.amount,
+        ContractError::InsufficientPaymentBalance { balance, required }
+    );
+    Ok(())
+}
+
+/// Creates the message that makes the customer's payment contract pay for `count` beacons.
+fn create_pay_msg(price: Coin, count: u32, relayer: Addr, payment: Addr) -> StdResult<WasmMsg> {
+    let Coin { amount, denom } = price;
+    let amount = amount * Uint128::from(count);
     let amount_burn = amount.mul_floor((40u128, 100)); // 40%
     let amount_relayer = amount.mul_floor((20u128, 100)); // 20%
     let amount_rest = amount - amount_burn - amount_relayer; // 40%
 
-    let msg = WasmMsg::Execute {
-        contract_addr: customer.payment.into(),
+    Ok(WasmMsg::Execute {
+        contract_addr: payment.into(),
         msg: to_json_binary(&nois_payment::msg::ExecuteMsg::Pay {
             burn: Coin::new(amount_burn.u128(), &denom),
             relayer: (relayer.into(), Coin::new(amount_relayer.u128(), &denom)),
             community_pool: Coin::new(amount_rest.u128(), denom),
         })?,
         funds: vec![],
-    };
-    msgs.push(msg.into());
-
-    Ok(IbcReceiveResponse::new(acknowledgement)
-        .add_messages(msgs)
-        .add_attribute("action", "receive_request_beacon"))
+    })
 }
 
 fn receive_pull_beacon_price(deps: DepsMut, env: Env) -> Result<IbcReceiveResponse, ContractError> {
@@ -548,21 +806,17 @@ fn execute_set_config(
     deps: DepsMut,
     info: MessageInfo,
     _env: Env,
-    manager: Option<String>,
     price: Option<Coin>,
     drand: Option<String>,
     trusted_sources: Option<Vec<String>>,
     payment_initial_funds: Option<Coin>,
+    sink: Option<String>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
     // check the calling address is the authorised multisig
     ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
 
-    let manager = match manager {
-        Some(ma) => deps.api.addr_validate(&ma)?,
-        None => config.manager,
-    };
     let drand = match drand {
         Some(dr) => Some(deps.api.addr_validate(&dr)?),
         None => config.drand,
@@ -582,15 +836,20 @@ fn execute_set_config(
         Some(pif) => Some(pif),
         None => config.payment_initial_funds,
     };
+    let sink = match sink {
+        Some(sink) => deps.api.addr_validate(&sink)?,
+        None => config.sink,
+    };
 
     let new_config = Config {
-        manager,
+        manager: config.manager, // Use ProposeManager/AcceptManager for this
         drand,
         price,
-        payment_code_id: config.payment_code_id, // Use a separate message for this in order to migrate all existing contracts too
+        payment_code_id: config.payment_code_id, // Use SetPaymentCodeId for this
         payment_initial_funds,
-        sink: config.sink, // Make updatable?
+        sink, // Use UpdatePaymentSinks to update existing payment contracts
         trusted_sources: Some(trusted_sources),
+        paused: config.paused, // Use SetPaused for this
     };
 
     CONFIG.save(deps.storage, &new_config)?;
@@ -598,6 +857,216 @@ fn execute_set_config(
     Ok(Response::default())
 }
 
+fn execute_set_payment_code_id(
+    deps: DepsMut,
+    info: MessageInfo,
+    code_id: u64,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    ensure_code_id_exists(deps.as_ref(), code_id)?;
+    config.payment_code_id = code_id;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "set_payment_code_id")
+        .add_attribute("code_id", code_id.to_string()))
+}
+
+/// Sends a migrate message to the payment contracts of the customers in the given range.
+/// The gateway is the admin of all payment contracts it instantiated.
+fn execute_migrate_payments(
+    deps: DepsMut,
+    info: MessageInfo,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let (customers, done) = customers_page(deps.as_ref(), start_after, limit)?;
+
+    let mut msgs = Vec::<WasmMsg>::with_capacity(customers.len());
+    for (_channel_id, customer) in &customers {
+        msgs.push(WasmMsg::Migrate {
+            contract_addr: customer.payment.to_string(),
+            new_code_id: config.payment_code_id,
+            msg: to_json_binary(&Empty {})?,
+        });
+    }
+
+    let mut event = Event::new("migrate_payments")
+        .add_attribute("code_id", config.payment_code_id.to_string())
+        .add_attribute("migrated", customers.len().to_string())
+        .add_attribute("done", done.to_string());
+    if let Some((last_channel_id, _)) = customers.last() {
+        event = event.add_attribute("last_channel_id", last_channel_id);
+    }
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute(ATTR_ACTION, "migrate_payments")
+        .add_event(event))
+}
+
+/// Sends the sink from the gateway config to the payment contracts of the customers
+/// in the given range.
+fn execute_update_payment_sinks(
+    deps: DepsMut,
+    info: MessageInfo,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let (customers, done) = customers_page(deps.as_ref(), start_after, limit)?;
+
+    let mut msgs = Vec::<WasmMsg>::with_capacity(customers.len());
+    for (_channel_id, customer) in &customers {
+        msgs.push(WasmMsg::Execute {
+            contract_addr: customer.payment.to_string(),
+            msg: to_json_binary(&nois_payment::msg::ExecuteMsg::SetSink {
+                sink: config.sink.to_string(),
+            })?,
+            funds: vec![],
+        });
+    }
+
+    let mut event = Event::new("update_payment_sinks")
+        .add_attribute("sink", config.sink)
+        .add_attribute("updated", customers.len().to_string())
+        .add_attribute("done", done.to_string());
+    if let Some((last_channel_id, _)) = customers.last() {
+        event = event.add_attribute("last_channel_id", last_channel_id);
+    }
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute(ATTR_ACTION, "update_payment_sinks")
+        .add_event(event))
+}
+
+/// Loads a page of customers for batch operations on payment contracts.
+/// The returned bool is true if there are no more customers after this page.
+fn customers_page(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<(Vec<(String, Customer)>, bool)> {
+    let limit = limit.unwrap_or(10) as usize;
+    let low_bound = start_after.as_deref().map(Bound::exclusive);
+
+    // Load one more element to see if we are done
+    let mut customers = CUSTOMERS
+        .range(deps.storage, low_bound, None, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let done = customers.len() <= limit;
+    customers.truncate(limit);
+    Ok((customers, done))
+}
+
+fn execute_set_customer_limits(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    limits: CustomerLimits,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let Some(mut customer) = CUSTOMERS.may_load(deps.storage, &channel_id)? else {
+        return Err(ContractError::CustomerNotFound { channel_id });
+    };
+    customer.limits = Some(limits);
+    CUSTOMERS.save(deps.storage, &channel_id, &customer)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "set_customer_limits")
+        .add_attribute("channel_id", channel_id))
+}
+
+fn execute_set_customer_suspended(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    suspended: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let Some(mut customer) = CUSTOMERS.may_load(deps.storage, &channel_id)? else {
+        return Err(ContractError::CustomerNotFound { channel_id });
+    };
+    customer.suspended = Some(suspended);
+    CUSTOMERS.save(deps.storage, &channel_id, &customer)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "set_customer_suspended")
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("suspended", suspended.to_string()))
+}
+
+fn execute_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    config.paused = Some(paused);
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "set_paused")
+        .add_attribute("paused", paused.to_string()))
+}
+
+fn execute_propose_manager(
+    deps: DepsMut,
+    info: MessageInfo,
+    manager: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let manager = deps.api.addr_validate(&manager)?;
+    propose_manager(deps.storage, &manager)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "propose_manager")
+        .add_attribute("pending_manager", manager))
+}
+
+fn execute_accept_manager(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    let manager = accept_manager(deps.storage, &info.sender)?;
+    config.manager = manager.clone();
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "accept_manager")
+        .add_attribute("manager", manager))
+}
+
+fn execute_cancel_manager_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let cancelled = cancel_manager_transfer(deps.storage)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "cancel_manager_transfer")
+        .add_attribute("pending_manager", cancelled))
+}
+
 fn ensure_code_id_exists(deps: Deps, code_id: u64) -> Result<(), ContractError> {
     let query = to_json_binary(&QueryRequest::<Empty>::Wasm(WasmQuery::CodeInfo {
         code_id,
@@ -635,7 +1104,15 @@ mod tests {
         IbcAcknowledgement, IbcMsg, OwnedDeps, QuerierResult, SystemError, SystemResult, Timestamp,
         WasmQuery,
     };
-    use nois_protocol::{APP_ORDER, BAD_APP_ORDER};
+    use drand_common::DRAND_CHAIN_HASH;
+    use manager_transfer::{ManagerTransferError, PendingManagerResponse};
+    use nois_protocol::{
+        PacketErrorCode, APP_ORDER, BAD_APP_ORDER, IBC_APP_VERSION, IBC_APP_VERSION_V7,
+    };
+
+    use crate::drand_archive::archive_lookup;
+    use crate::state::RequestWindowLimit;
+    use cw_storage_plus::Map;
 
     const CREATOR: &str = "creator";
     const MANAGER: &str = "boss";
@@ -791,6 +1268,10 @@ mod tests {
         }
     }
 
+    fn error_ack(code: PacketErrorCode, message: &str) -> StdAck {
+        PacketError::new(code, message).to_ack()
+    }
+
     /// Gets the value of the first attribute with the given key
     fn first_attr(data: impl AsRef<[Attribute]>, search_key: &str) -> Option<String> {
         data.as_ref().iter().find_map(|a| {
@@ -817,14 +1298,17 @@ mod tests {
         env
     }
 
-    fn connect(mut deps: DepsMut, channel_id: &str) {
-        let handshake_open = mock_ibc_channel_open_try(channel_id, APP_ORDER, IBC_APP_VERSION);
+    fn connect(deps: DepsMut, channel_id: &str) {
+        connect_with_version(deps, channel_id, IBC_APP_VERSION)
+    }
+
+    fn connect_with_version(mut deps: DepsMut, channel_id: &str, version: &str) {
+        let handshake_open = mock_ibc_channel_open_try(channel_id, APP_ORDER, version);
         // first we try to open with a valid handshake
         ibc_channel_open(deps.branch(), mock_env(), handshake_open).unwrap();
 
         // then we connect (with counter-party version set)
-        let handshake_connect =
-            mock_ibc_channel_connect_confirm(channel_id, APP_ORDER, IBC_APP_VERSION);
+        let handshake_connect = mock_ibc_channel_connect_confirm(channel_id, APP_ORDER, version);
         let env = mock_env_addr();
         let res = ibc_channel_connect(deps.branch(), env, handshake_connect).unwrap();
         assert_eq!(res.messages.len(), 3);
@@ -846,6 +1330,10 @@ mod tests {
 
     fn mock_dependencies() -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
         let mut deps = testing::mock_dependencies();
+        // Payment contracts of customers can pay for plenty of beacons
+        deps.querier
+            .bank
+            .update_balance("some payment address", vec![coin(1_000_000_000, "unois")]);
         deps.querier
             .update_wasm(Box::from(|request: &WasmQuery| -> QuerierResult {
                 match request {
@@ -919,6 +1407,7 @@ mod tests {
                 payment_code_id: PAYMENT,
                 payment_initial_funds: None,
                 sink: sink.clone(),
+                paused: None,
             }
         );
 
@@ -946,6 +1435,7 @@ mod tests {
                 payment_code_id: PAYMENT,
                 payment_initial_funds: payment_initial(),
                 sink: sink.clone(),
+                paused: None,
             }
         );
     }
@@ -986,6 +1476,7 @@ mod tests {
         let manager = deps.api.addr_make(MANAGER);
         let manager2 = deps.api.addr_make(MANAGER2);
         let sink = deps.api.addr_make(SINK);
+        let sink2 = deps.api.addr_make("sink2");
         let somewhere = deps.api.addr_make("somewhere");
 
         let msg = InstantiateMsg {
@@ -1000,11 +1491,11 @@ mod tests {
         instantiate(deps.as_mut(), env, info, msg).unwrap();
 
         let msg = ExecuteMsg::SetConfig {
-            manager: Some(manager2.to_string()),
             price: Some(coin(123, "unois")),
             drand_addr: Some(somewhere.to_string()),
             trusted_sources: Some(vec![somewhere.to_string(), manager2.to_string()]),
             payment_initial_funds: Some(coin(500, "unois")),
+            sink: Some(sink2.to_string()),
         };
 
         // Fails for incorrect manager
@@ -1028,234 +1519,1412 @@ mod tests {
         assert_eq!(
             config,
             ConfigResponse {
-                manager: manager2.clone(),
+                manager: manager.clone(),
                 price: coin(123, "unois"),
                 drand: Some(somewhere.clone()),
                 trusted_sources: Some(vec![somewhere.clone(), manager2.clone()]),
                 payment_code_id: PAYMENT,
                 payment_initial_funds: Some(coin(500, "unois")),
-                sink,
+                sink: sink2,
+                paused: None,
             }
         )
     }
 
     #[test]
-    fn add_round_verified_must_only_be_called_by_drand() {
+    fn manager_transfer_works() {
         let mut deps = mock_dependencies();
 
         let creator = deps.api.addr_make(CREATOR);
         let manager = deps.api.addr_make(MANAGER);
         let manager2 = deps.api.addr_make(MANAGER2);
         let sink = deps.api.addr_make(SINK);
-        let anon = deps.api.addr_make("anon");
-        let drand = deps.api.addr_make("drand_verifier_7");
 
-        let info = message_info(&creator, &[]);
         let msg = InstantiateMsg {
             manager: manager.to_string(),
             price: coin(1, "unois"),
             payment_code_id: PAYMENT,
-            payment_initial_funds: payment_initial(),
+            payment_initial_funds: None,
             sink: sink.to_string(),
         };
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        instantiate(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
 
-        // drand contract unset, i.e. noone can submit
-        let msg = make_add_verified_round_msg(2183668, true);
-        let err = execute(deps.as_mut(), mock_env(), message_info(&anon, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::UnauthorizedAddVerifiedRound));
-        let msg = make_add_verified_round_msg(2183668, true);
-        let err = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::UnauthorizedAddVerifiedRound));
+        let pending: PendingManagerResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending.pending_manager, None);
 
-        // Set drand contract
-        let msg = ExecuteMsg::SetConfig {
-            manager: None,
-            price: None,
-            drand_addr: Some(drand.to_string()),
-            trusted_sources: None,
-            payment_initial_funds: None,
+        // Only the manager can propose
+        let msg = ExecuteMsg::ProposeManager {
+            manager: manager2.to_string(),
         };
-        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager2, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "propose_manager"
+        );
 
-        // drand cannot add
-        let msg = make_add_verified_round_msg(2183668, true);
-        let err = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::UnauthorizedAddVerifiedRound));
+        let pending: PendingManagerResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending.pending_manager, Some(manager2.clone()));
 
-        // Set trusted sources
+        // Only the pending manager can accept
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ManagerTransfer(ManagerTransferError::NotPendingManager)
+        ));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager2, &[]),
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap();
+
+        let config: ConfigResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.manager, manager2);
+        let pending: PendingManagerResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending.pending_manager, None);
+
+        // New manager can propose and cancel; the old one cannot
+        let msg = ExecuteMsg::ProposeManager {
+            manager: manager.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager2, &[]), msg).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            ExecuteMsg::CancelManagerTransfer {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager2, &[]),
+            ExecuteMsg::CancelManagerTransfer {},
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ManagerTransfer(ManagerTransferError::NoPendingManager)
+        ));
+    }
+
+    #[test]
+    fn add_round_verified_must_only_be_called_by_drand() {
+        let mut deps = mock_dependencies();
+
+        let creator = deps.api.addr_make(CREATOR);
+        let manager = deps.api.addr_make(MANAGER);
+        let manager2 = deps.api.addr_make(MANAGER2);
+        let sink = deps.api.addr_make(SINK);
+        let anon = deps.api.addr_make("anon");
+        let drand = deps.api.addr_make("drand_verifier_7");
+
+        let info = message_info(&creator, &[]);
+        let msg = InstantiateMsg {
+            manager: manager.to_string(),
+            price: coin(1, "unois"),
+            payment_code_id: PAYMENT,
+            payment_initial_funds: payment_initial(),
+            sink: sink.to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // drand contract unset, i.e. noone can submit
+        let msg = make_add_verified_round_msg(2183668, true);
+        let err = execute(deps.as_mut(), mock_env(), message_info(&anon, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedAddVerifiedRound));
+        let msg = make_add_verified_round_msg(2183668, true);
+        let err = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedAddVerifiedRound));
+
+        // Set drand contract
+        let msg = ExecuteMsg::SetConfig {
+            price: None,
+            drand_addr: Some(drand.to_string()),
+            trusted_sources: None,
+            payment_initial_funds: None,
+            sink: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        // drand cannot add
+        let msg = make_add_verified_round_msg(2183668, true);
+        let err = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedAddVerifiedRound));
+
+        // Set trusted sources
         let msg = ExecuteMsg::SetConfig {
-            manager: None,
             price: None,
             drand_addr: None,
             trusted_sources: Some(vec![drand.to_string(), manager2.to_string()]),
             payment_initial_funds: None,
+            sink: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        // Anon still cannot add round
+        let msg = make_add_verified_round_msg(2183668, true);
+        let err = execute(deps.as_mut(), mock_env(), message_info(&anon, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedAddVerifiedRound));
+
+        // But drand and manager2 can
+        let msg = make_add_verified_round_msg(2183668, true);
+        let _res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        let msg = make_add_verified_round_msg(2183668, true);
+        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager2, &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn add_round_verified_processes_jobs() {
+        let mut deps = mock_dependencies();
+
+        let creator = deps.api.addr_make(CREATOR);
+        let manager = deps.api.addr_make(MANAGER);
+        let sink = deps.api.addr_make(SINK);
+        let drand = deps.api.addr_make("drand_verifier_7");
+
+        let info = message_info(&creator, &[]);
+        let msg = InstantiateMsg {
+            manager: manager.to_string(),
+            price: coin(1, "unois"),
+            payment_code_id: PAYMENT,
+            payment_initial_funds: payment_initial(),
+            sink: sink.to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Set drand contract
+        let msg = ExecuteMsg::SetConfig {
+            price: None,
+            drand_addr: Some(drand.to_string()),
+            trusted_sources: Some(vec![drand.to_string()]),
+            payment_initial_funds: None,
+            sink: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        connect(deps.as_mut(), "foo");
+
+        // Create one job
+        let msg = mock_ibc_packet_recv(
+            "foo",
+            &InPacket::RequestBeacon {
+                after: AFTER2,
+                origin: origin(1),
+            },
+        )
+        .unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+
+        // Previous round processes no job
+        let msg = make_add_verified_round_msg(ROUND1, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 0);
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "0");
+
+        // Process one job
+        let msg = make_add_verified_round_msg(ROUND2, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].gas_limit, None);
+        assert!(matches!(
+            res.messages[0].msg,
+            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
+        ));
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "1");
+
+        // Create 2 job
+        for i in 0..2 {
+            let msg = mock_ibc_packet_recv(
+                "foo",
+                &InPacket::RequestBeacon {
+                    after: AFTER3,
+                    origin: origin(i),
+                },
+            )
+            .unwrap();
+            ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        }
+
+        // Process first jobs
+        let msg = make_add_verified_round_msg(ROUND3, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].gas_limit, None);
+        assert!(matches!(
+            res.messages[0].msg,
+            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
+        ));
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "1");
+
+        // Process second job
+        let msg = make_add_verified_round_msg(ROUND3, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].gas_limit, None);
+        assert!(matches!(
+            res.messages[0].msg,
+            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
+        ));
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "1");
+
+        // Create 21 job
+        for i in 0..21 {
+            let msg = mock_ibc_packet_recv(
+                "foo",
+                &InPacket::RequestBeacon {
+                    after: AFTER4,
+                    origin: origin(i),
+                },
+            )
+            .unwrap();
+            let rec = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+            dbg!(rec.acknowledgement);
+        }
+
+        // Process first job
+        let msg = make_add_verified_round_msg(ROUND4, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "1");
+
+        // Process next job
+        let msg = make_add_verified_round_msg(ROUND4, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "1");
+
+        // Process next job
+        let msg = make_add_verified_round_msg(ROUND4, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "1");
+
+        // Process next 10 jobs
+        let msg = make_add_verified_round_msg(ROUND4, false);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 10);
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "10");
+
+        // Process remaining jobs
+        let msg = make_add_verified_round_msg(ROUND4, false);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 8);
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "8");
+
+        // No jobs left for later submissions
+        let msg = make_add_verified_round_msg(ROUND4, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 0);
+        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
+        assert_eq!(jobs_processed, "0");
+    }
+
+    #[test]
+    fn execute_set_payment_code_id_works() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let manager2 = deps.api.addr_make(MANAGER2);
+
+        // Fails for incorrect manager
+        let msg = ExecuteMsg::SetPaymentCodeId { code_id: PAYMENT2 };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager2, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // Fails for non-existing code ID
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            ExecuteMsg::SetPaymentCodeId { code_id: 654321 },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CodeIdDoesNotExist { code_id: 654321 }
+        ));
+
+        // Works for correct manager
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let config: ConfigResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.payment_code_id, PAYMENT2);
+    }
+
+    #[test]
+    fn execute_migrate_payments_works() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let manager2 = deps.api.addr_make(MANAGER2);
+
+        connect(deps.as_mut(), "channel-1");
+        connect(deps.as_mut(), "channel-2");
+        connect(deps.as_mut(), "channel-3");
+
+        let msg = ExecuteMsg::SetPaymentCodeId { code_id: PAYMENT2 };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        // Fails for incorrect manager
+        let msg = ExecuteMsg::MigratePayments {
+            start_after: None,
+            limit: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager2, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // All in one go
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Migrate {
+                contract_addr: "some payment address".to_string(),
+                new_code_id: PAYMENT2,
+                msg: to_json_binary(&Empty {}).unwrap(),
+            })
+        );
+        assert_eq!(
+            res.events,
+            [Event::new("migrate_payments")
+                .add_attribute("code_id", PAYMENT2.to_string())
+                .add_attribute("migrated", "3")
+                .add_attribute("done", "true")
+                .add_attribute("last_channel_id", "channel-3")]
+        );
+
+        // Paginated
+        let msg = ExecuteMsg::MigratePayments {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.events,
+            [Event::new("migrate_payments")
+                .add_attribute("code_id", PAYMENT2.to_string())
+                .add_attribute("migrated", "2")
+                .add_attribute("done", "false")
+                .add_attribute("last_channel_id", "channel-2")]
+        );
+        let msg = ExecuteMsg::MigratePayments {
+            start_after: Some("channel-2".to_string()),
+            limit: Some(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.events,
+            [Event::new("migrate_payments")
+                .add_attribute("code_id", PAYMENT2.to_string())
+                .add_attribute("migrated", "1")
+                .add_attribute("done", "true")
+                .add_attribute("last_channel_id", "channel-3")]
+        );
+        let msg = ExecuteMsg::MigratePayments {
+            start_after: Some("channel-3".to_string()),
+            limit: Some(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 0);
+        assert_eq!(
+            res.events,
+            [Event::new("migrate_payments")
+                .add_attribute("code_id", PAYMENT2.to_string())
+                .add_attribute("migrated", "0")
+                .add_attribute("done", "true")]
+        );
+    }
+
+    #[test]
+    fn execute_update_payment_sinks_works() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let manager2 = deps.api.addr_make(MANAGER2);
+        let sink2 = deps.api.addr_make("sink2");
+
+        connect(deps.as_mut(), "channel-1");
+        connect(deps.as_mut(), "channel-2");
+        connect(deps.as_mut(), "channel-3");
+
+        let msg = ExecuteMsg::SetConfig {
+            price: None,
+            drand_addr: None,
+            trusted_sources: None,
+            payment_initial_funds: None,
+            sink: Some(sink2.to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        // Fails for incorrect manager
+        let msg = ExecuteMsg::UpdatePaymentSinks {
+            start_after: None,
+            limit: Some(2),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager2, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "some payment address".to_string(),
+                msg: to_json_binary(&nois_payment::msg::ExecuteMsg::SetSink {
+                    sink: sink2.to_string()
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+        assert_eq!(
+            res.events,
+            [Event::new("update_payment_sinks")
+                .add_attribute("sink", sink2.as_str())
+                .add_attribute("updated", "2")
+                .add_attribute("done", "false")
+                .add_attribute("last_channel_id", "channel-2")]
+        );
+
+        let msg = ExecuteMsg::UpdatePaymentSinks {
+            start_after: Some("channel-2".to_string()),
+            limit: Some(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.events,
+            [Event::new("update_payment_sinks")
+                .add_attribute("sink", sink2.as_str())
+                .add_attribute("updated", "1")
+                .add_attribute("done", "true")
+                .add_attribute("last_channel_id", "channel-3")]
+        );
+    }
+
+    #[test]
+    fn execute_set_customer_limits_works() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let manager2 = deps.api.addr_make(MANAGER2);
+
+        const CHANNEL_ID: &str = "the-channel";
+
+        let limits = CustomerLimits {
+            max_requests_per_block: Some(3),
+            max_requests_per_window: Some(RequestWindowLimit {
+                seconds: 60,
+                max_requests: 10,
+            }),
+            max_queued_jobs: None,
+        };
+        let msg = ExecuteMsg::SetCustomerLimits {
+            channel_id: CHANNEL_ID.to_string(),
+            limits: limits.clone(),
+        };
+
+        // Fails for unknown customer
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::CustomerNotFound { .. }));
+
+        connect(deps.as_mut(), CHANNEL_ID);
+
+        // Fails for incorrect manager
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager2, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // Works for correct manager
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        let CustomerResponse { customer } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Customer {
+                    channel_id: CHANNEL_ID.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(customer.unwrap().limits, limits);
+    }
+
+    #[test]
+    fn customer_limits_are_enforced() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let drand = deps.api.addr_make("drand_verifier_7");
+
+        const CHANNEL_ID: &str = "the-channel";
+
+        connect(deps.as_mut(), CHANNEL_ID);
+
+        let msg = ExecuteMsg::SetConfig {
+            price: None,
+            drand_addr: Some(drand.to_string()),
+            trusted_sources: Some(vec![drand.to_string()]),
+            payment_initial_funds: None,
+            sink: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        fn set_limits(deps: DepsMut, manager: &Addr, limits: CustomerLimits) {
+            let msg = ExecuteMsg::SetCustomerLimits {
+                channel_id: CHANNEL_ID.to_string(),
+                limits,
+            };
+            execute(deps, mock_env(), message_info(manager, &[]), msg).unwrap();
+        }
+
+        fn request(deps: DepsMut, env: Env, after: Timestamp, job: u32) -> StdAck {
+            let msg = mock_ibc_packet_recv(
+                CHANNEL_ID,
+                &InPacket::RequestBeacon {
+                    after,
+                    origin: origin(job),
+                },
+            )
+            .unwrap();
+            let res = ibc_packet_receive(deps, env, msg).unwrap();
+            from_json(res.acknowledgement.unwrap()).unwrap()
+        }
+
+        // Requests per block
+        set_limits(
+            deps.as_mut(),
+            &manager,
+            CustomerLimits {
+                max_requests_per_block: Some(2),
+                ..Default::default()
+            },
+        );
+        let mut env = mock_env();
+        assert!(request(deps.as_mut(), env.clone(), AFTER1, 1).is_success());
+        assert!(request(deps.as_mut(), env.clone(), AFTER1, 2).is_success());
+        let ack = request(deps.as_mut(), env.clone(), AFTER1, 3);
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::LimitExceeded,
+                "Error processing packet: Limit of 2 beacon requests per block exceeded."
+            )
+        );
+        env.block.height += 1;
+        assert!(request(deps.as_mut(), env.clone(), AFTER1, 4).is_success());
+
+        // Requests per time window
+        set_limits(
+            deps.as_mut(),
+            &manager,
+            CustomerLimits {
+                max_requests_per_window: Some(RequestWindowLimit {
+                    seconds: 60,
+                    max_requests: 2,
+                }),
+                ..Default::default()
+            },
+        );
+        env.block.height += 1;
+        assert!(request(deps.as_mut(), env.clone(), AFTER1, 5).is_success());
+        env.block.height += 1;
+        env.block.time = env.block.time.plus_seconds(5);
+        assert!(request(deps.as_mut(), env.clone(), AFTER1, 6).is_success());
+        env.block.height += 1;
+        env.block.time = env.block.time.plus_seconds(5);
+        let ack = request(deps.as_mut(), env.clone(), AFTER1, 7);
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::LimitExceeded,
+                "Error processing packet: Limit of 2 beacon requests per 60 seconds exceeded."
+            )
+        );
+        env.block.height += 1;
+        env.block.time = env.block.time.plus_seconds(60);
+        assert!(request(deps.as_mut(), env.clone(), AFTER1, 8).is_success());
+
+        // Queued jobs
+        set_limits(
+            deps.as_mut(),
+            &manager,
+            CustomerLimits {
+                max_queued_jobs: Some(7),
+                ..Default::default()
+            },
+        );
+        // 6 jobs for ROUND1 are queued already
+        assert!(request(deps.as_mut(), env.clone(), AFTER2, 9).is_success());
+        let ack = request(deps.as_mut(), env.clone(), AFTER2, 10);
+        assert_eq!(
+            ack,
+            error_ack(PacketErrorCode::LimitExceeded, "Error processing packet: Limit of 7 queued jobs exceeded. Wait for queued jobs to be processed.")
+        );
+
+        // Processing a job frees up a slot
+        let msg = make_add_verified_round_msg(ROUND1, true);
+        execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert!(request(deps.as_mut(), env.clone(), AFTER2, 11).is_success());
+        let ack = request(deps.as_mut(), env.clone(), AFTER2, 12);
+        assert!(ack.is_error());
+
+        // Jobs processed right away do not count
+        let msg = make_add_verified_round_msg(ROUND1, false);
+        execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        for job in 13..20 {
+            assert!(request(deps.as_mut(), env.clone(), AFTER1, job).is_success());
+        }
+    }
+
+    #[test]
+    fn migrate_counts_jobs_queued_before_upgrade() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let drand = deps.api.addr_make("drand_verifier_7");
+
+        const CHANNEL_ID: &str = "the-channel";
+
+        connect(deps.as_mut(), CHANNEL_ID);
+
+        let msg = ExecuteMsg::SetConfig {
+            price: None,
+            drand_addr: Some(drand.to_string()),
+            trusted_sources: Some(vec![drand.to_string()]),
+            payment_initial_funds: None,
+            sink: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        fn request(deps: DepsMut, job: u32) -> StdAck {
+            let msg = mock_ibc_packet_recv(
+                CHANNEL_ID,
+                &InPacket::RequestBeacon {
+                    after: AFTER1,
+                    origin: origin(job),
+                },
+            )
+            .unwrap();
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            from_json(res.acknowledgement.unwrap()).unwrap()
+        }
+
+        fn unprocessed(deps: Deps) -> u32 {
+            let stats: DrandJobStatsResponse = from_json(
+                query(deps, mock_env(), QueryMsg::DrandJobStats { round: ROUND1 }).unwrap(),
+            )
+            .unwrap();
+            stats.unprocessed
+        }
+
+        for job in 1..=3 {
+            assert!(request(deps.as_mut(), job).is_success());
+        }
+
+        // Simulate jobs queued by a version without counters
+        Map::<u32, u32>::new("djuc").clear(deps.as_mut().storage);
+        Map::<&str, Empty>::new("customer_usage").clear(deps.as_mut().storage);
+        assert_eq!(unprocessed(deps.as_ref()), 0);
+
+        migrate(deps.as_mut(), mock_env(), Empty {}).unwrap();
+        assert_eq!(unprocessed(deps.as_ref()), 3);
+
+        // Jobs queued before the upgrade count towards the limit
+        let msg = ExecuteMsg::SetCustomerLimits {
+            channel_id: CHANNEL_ID.to_string(),
+            limits: CustomerLimits {
+                max_queued_jobs: Some(4),
+                ..Default::default()
+            },
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert!(request(deps.as_mut(), 4).is_success());
+        let ack = request(deps.as_mut(), 5);
+        assert_eq!(
+            ack,
+            error_ack(PacketErrorCode::LimitExceeded, "Error processing packet: Limit of 4 queued jobs exceeded. Wait for queued jobs to be processed.")
+        );
+
+        // Migrating again keeps the maintained counters
+        migrate(deps.as_mut(), mock_env(), Empty {}).unwrap();
+        assert_eq!(unprocessed(deps.as_ref()), 4);
+        assert!(request(deps.as_mut(), 6).is_error());
+    }
+
+    #[test]
+    fn set_paused_works() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let drand = deps.api.addr_make("drand_verifier_7");
+        let guest = deps.api.addr_make("guest");
+
+        const CHANNEL_ID: &str = "the-channel";
+
+        connect(deps.as_mut(), CHANNEL_ID);
+
+        let msg = ExecuteMsg::SetConfig {
+            price: None,
+            drand_addr: Some(drand.to_string()),
+            trusted_sources: Some(vec![drand.to_string()]),
+            payment_initial_funds: None,
+            sink: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        fn request(deps: DepsMut, job: u32) -> StdAck {
+            let msg = mock_ibc_packet_recv(
+                CHANNEL_ID,
+                &InPacket::RequestBeacon {
+                    after: AFTER1,
+                    origin: origin(job),
+                },
+            )
+            .unwrap();
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            from_json(res.acknowledgement.unwrap()).unwrap()
+        }
+
+        // Queue a job before pausing
+        assert!(request(deps.as_mut(), 1).is_success());
+
+        // Only manager can pause
+        let msg = ExecuteMsg::SetPaused { paused: true };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&guest, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(first_attr(&res.attributes, "action").unwrap(), "set_paused");
+        assert_eq!(first_attr(&res.attributes, "paused").unwrap(), "true");
+
+        let config: ConfigResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.paused, Some(true));
+
+        // Requests are rejected
+        let ack = request(deps.as_mut(), 2);
+        assert_eq!(
+            ack,
+            error_ack(PacketErrorCode::Paused, "Error processing packet: The gateway is paused. No new beacon requests are accepted.")
+        );
+        let CustomerResponse { customer } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Customer {
+                    channel_id: CHANNEL_ID.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(customer.unwrap().requested_beacons, 1);
+
+        // Rounds are still stored and queued jobs delivered
+        let msg = make_add_verified_round_msg(ROUND1, true);
+        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(archive_lookup(&deps.storage, ROUND1).is_some());
+
+        // Unpause
+        let msg = ExecuteMsg::SetPaused { paused: false };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let config: ConfigResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.paused, Some(false));
+        assert!(request(deps.as_mut(), 3).is_success());
+    }
+
+    #[test]
+    fn packet_error_customer_suspended() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let guest = deps.api.addr_make("guest");
+
+        const CHANNEL_ID: &str = "the-channel";
+        connect(deps.as_mut(), CHANNEL_ID);
+
+        fn request(deps: DepsMut, job: u32) -> StdAck {
+            let msg = mock_ibc_packet_recv(
+                CHANNEL_ID,
+                &InPacket::RequestBeacon {
+                    after: AFTER1,
+                    origin: origin(job),
+                },
+            )
+            .unwrap();
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            from_json(res.acknowledgement.unwrap()).unwrap()
+        }
+
+        // Only manager can suspend
+        let suspend = |channel_id: &str, suspended: bool| ExecuteMsg::SetCustomerSuspended {
+            channel_id: channel_id.to_string(),
+            suspended,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&guest, &[]),
+            suspend(CHANNEL_ID, true),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            suspend("channel-unknown", true),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::CustomerNotFound { .. }));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            suspend(CHANNEL_ID, true),
+        )
+        .unwrap();
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "set_customer_suspended"
+        );
+
+        // Requests are rejected
+        assert_eq!(
+            request(deps.as_mut(), 1),
+            error_ack(
+                PacketErrorCode::CustomerSuspended,
+                "Error processing packet: Customer of channel the-channel is suspended."
+            )
+        );
+        let msg = mock_ibc_packet_recv(
+            CHANNEL_ID,
+            &InPacket::RequestBeacons {
+                requests: vec![(AFTER1, origin(2))],
+            },
+        )
+        .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::CustomerSuspended,
+                "Error processing packet: Customer of channel the-channel is suspended."
+            )
+        );
+        let CustomerResponse { customer } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Customer {
+                    channel_id: CHANNEL_ID.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let customer = customer.unwrap();
+        assert!(customer.suspended);
+        assert_eq!(customer.requested_beacons, 0);
+
+        // Unsuspend
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            suspend(CHANNEL_ID, false),
+        )
+        .unwrap();
+        assert!(request(deps.as_mut(), 3).is_success());
+    }
+
+    #[test]
+    fn packet_error_insufficient_payment_balance() {
+        let mut deps = setup();
+
+        const CHANNEL_ID: &str = "the-channel";
+        connect(deps.as_mut(), CHANNEL_ID);
+
+        // The payment contract can pay for 2 beacons only
+        deps.querier
+            .bank
+            .update_balance("some payment address", vec![coin(2, "unois")]);
+
+        let msg = mock_ibc_packet_recv(
+            CHANNEL_ID,
+            &InPacket::RequestBeacons {
+                requests: vec![
+                    (AFTER1, origin(1)),
+                    (AFTER1, origin(2)),
+                    (AFTER1, origin(3)),
+                ],
+            },
+        )
+        .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::InsufficientPaymentBalance,
+                "Error processing packet: Payment contract balance of 2unois is insufficient to pay 3unois."
+            )
+        );
+
+        let msg = mock_ibc_packet_recv(
+            CHANNEL_ID,
+            &InPacket::RequestBeacons {
+                requests: vec![(AFTER1, origin(1)), (AFTER1, origin(2))],
+            },
+        )
+        .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert!(ack.is_success());
+    }
+
+    #[test]
+    fn receive_request_beacons_requires_nois_v8_channel() {
+        let mut deps = setup();
+
+        const CHANNEL_ID: &str = "the-channel";
+        connect_with_version(deps.as_mut(), CHANNEL_ID, IBC_APP_VERSION_V7);
+
+        let CustomerResponse { customer } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Customer {
+                    channel_id: CHANNEL_ID.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(customer.unwrap().version, "nois-v7");
+
+        let msg = mock_ibc_packet_recv(
+            CHANNEL_ID,
+            &InPacket::RequestBeacons {
+                requests: vec![(AFTER1, origin(1))],
+            },
+        )
+        .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::UnsupportedPacket,
+                "Error processing packet: Unsupported packet type."
+            )
+        );
+
+        // Single requests keep working
+        let msg = mock_ibc_packet_recv(
+            CHANNEL_ID,
+            &InPacket::RequestBeacon {
+                after: AFTER1,
+                origin: origin(1),
+            },
+        )
+        .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert!(ack.is_success());
+    }
+
+    #[test]
+    fn receive_request_beacons_works() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let drand = deps.api.addr_make("drand_verifier_7");
+
+        const CHANNEL_ID: &str = "the-channel";
+
+        connect(deps.as_mut(), CHANNEL_ID);
+
+        let msg = ExecuteMsg::SetConfig {
+            price: Some(coin(100, "unois")),
+            drand_addr: Some(drand.to_string()),
+            trusted_sources: Some(vec![drand.to_string()]),
+            payment_initial_funds: None,
+            sink: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        // ROUND1 is available, ROUND2 is not
+        let msg = make_add_verified_round_msg(ROUND1, true);
+        execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+
+        fn requested_beacons(deps: Deps) -> u64 {
+            let CustomerResponse { customer } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::Customer {
+                        channel_id: CHANNEL_ID.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            customer.unwrap().requested_beacons
+        }
+
+        let msg = mock_ibc_packet_recv(
+            CHANNEL_ID,
+            &InPacket::RequestBeacons {
+                requests: vec![
+                    (AFTER1, origin(1)),
+                    (AFTER2, origin(2)),
+                    (AFTER2, origin(3)),
+                ],
+            },
+        )
+        .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        let InPacketAck::RequestBeacons { statuses } = from_json(ack.unwrap()).unwrap() else {
+            panic!("Unexpected ack type");
+        };
+        assert_eq!(
+            statuses,
+            [
+                RequestBeaconStatus {
+                    source_id: format!("drand:{DRAND_CHAIN_HASH}:{ROUND1}"),
+                    queued: false,
+                },
+                RequestBeaconStatus {
+                    source_id: format!("drand:{DRAND_CHAIN_HASH}:{ROUND2}"),
+                    queued: true,
+                },
+                RequestBeaconStatus {
+                    source_id: format!("drand:{DRAND_CHAIN_HASH}:{ROUND2}"),
+                    queued: true,
+                },
+            ]
+        );
+        assert_eq!(first_attr(&res.attributes, "count").unwrap(), "3");
+        // One delivery, one SetIncentivized for ROUND2 and one payment
+        assert_eq!(res.messages.len(), 3);
+        assert!(matches!(
+            res.messages[0].msg,
+            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
+        ));
+        assert!(matches!(
+            res.messages[1].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute { ref contract_addr, .. }) if *contract_addr == drand.to_string()
+        ));
+        let CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) = &res.messages[2].msg else {
+            panic!("Unexpected message type");
+        };
+        let pay: nois_payment::msg::ExecuteMsg = from_json(msg).unwrap();
+        assert_eq!(
+            pay,
+            nois_payment::msg::ExecuteMsg::Pay {
+                burn: coin(120, "unois"),
+                relayer: ("relayer".to_string(), coin(60, "unois")),
+                community_pool: coin(120, "unois"),
+            }
+        );
+        assert_eq!(requested_beacons(deps.as_ref()), 3);
+        assert_eq!(
+            unprocessed_drand_jobs_len(deps.as_ref().storage, ROUND2).unwrap(),
+            2
+        );
+
+        // Empty batch
+        let msg = mock_ibc_packet_recv(CHANNEL_ID, &InPacket::RequestBeacons { requests: vec![] })
+            .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::InvalidBatchSize,
+                "Error processing packet: Packet must contain at least one beacon request."
+            )
+        );
+
+        // Too large batch
+        let requests = (0..101).map(|job| (AFTER2, origin(job))).collect();
+        let msg = mock_ibc_packet_recv(CHANNEL_ID, &InPacket::RequestBeacons { requests }).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::InvalidBatchSize,
+                "Error processing packet: Packet must not contain more than 100 beacon requests."
+            )
+        );
+
+        // One invalid request fails the whole batch
+        let requests = vec![(AFTER2, origin(4)), (AFTER2, Binary::from(vec![0u8; 513]))];
+        let msg = mock_ibc_packet_recv(CHANNEL_ID, &InPacket::RequestBeacons { requests }).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::OriginTooLong,
+                "Error processing packet: Origin data exceeds length limit."
+            )
+        );
+        assert_eq!(requested_beacons(deps.as_ref()), 3);
+        assert_eq!(
+            unprocessed_drand_jobs_len(deps.as_ref().storage, ROUND2).unwrap(),
+            2
+        );
+
+        // Limits are applied to the whole batch
+        let msg = ExecuteMsg::SetCustomerLimits {
+            channel_id: CHANNEL_ID.to_string(),
+            limits: CustomerLimits {
+                max_queued_jobs: Some(3),
+                ..Default::default()
+            },
         };
-        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let requests = vec![(AFTER2, origin(5)), (AFTER2, origin(6))];
+        let msg = mock_ibc_packet_recv(CHANNEL_ID, &InPacket::RequestBeacons { requests }).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert!(ack.is_error());
+        assert_eq!(requested_beacons(deps.as_ref()), 3);
+        assert_eq!(
+            unprocessed_drand_jobs_len(deps.as_ref().storage, ROUND2).unwrap(),
+            2
+        );
+    }
 
-        // Anon still cannot add round
-        let msg = make_add_verified_round_msg(2183668, true);
-        let err = execute(deps.as_mut(), mock_env(), message_info(&anon, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::UnauthorizedAddVerifiedRound));
+    #[test]
+    fn receive_request_beacon_at_round_requires_nois_v8_channel() {
+        let mut deps = setup();
 
-        // But drand and manager2 can
-        let msg = make_add_verified_round_msg(2183668, true);
-        let _res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        let msg = make_add_verified_round_msg(2183668, true);
-        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager2, &[]), msg).unwrap();
+        const CHANNEL_ID: &str = "the-channel";
+        connect_with_version(deps.as_mut(), CHANNEL_ID, IBC_APP_VERSION_V7);
+
+        let mut env = mock_env();
+        env.block.time = AFTER1;
+
+        let msg = mock_ibc_packet_recv(
+            CHANNEL_ID,
+            &InPacket::RequestBeaconAtRound {
+                round: ROUND2,
+                origin: origin(1),
+            },
+        )
+        .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), env, msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert_eq!(
+            ack,
+            error_ack(
+                PacketErrorCode::UnsupportedPacket,
+                "Error processing packet: Unsupported packet type."
+            )
+        );
+        assert_eq!(
+            unprocessed_drand_jobs_len(deps.as_ref().storage, ROUND2).unwrap(),
+            0
+        );
     }
 
     #[test]
-    fn add_round_verified_processes_jobs() {
-        let mut deps = mock_dependencies();
-
-        let creator = deps.api.addr_make(CREATOR);
-        let manager = deps.api.addr_make(MANAGER);
-        let sink = deps.api.addr_make(SINK);
-        let drand = deps.api.addr_make("drand_verifier_7");
+    fn receive_request_beacon_at_round_works() {
+        let mut deps = setup();
 
-        let info = message_info(&creator, &[]);
-        let msg = InstantiateMsg {
-            manager: manager.to_string(),
-            price: coin(1, "unois"),
-            payment_code_id: PAYMENT,
-            payment_initial_funds: payment_initial(),
-            sink: sink.to_string(),
-        };
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        const CHANNEL_ID: &str = "the-channel";
+        connect(deps.as_mut(), CHANNEL_ID);
 
-        // Set drand contract
-        let msg = ExecuteMsg::SetConfig {
-            manager: None,
-            price: None,
-            drand_addr: Some(drand.to_string()),
-            trusted_sources: Some(vec![drand.to_string()]),
-            payment_initial_funds: None,
-        };
-        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        // Request time right before ROUND1 is published
+        let mut env = mock_env();
+        env.block.time = AFTER1;
 
-        // Create one job
         let msg = mock_ibc_packet_recv(
-            "foo",
-            &InPacket::RequestBeacon {
-                after: AFTER2,
+            CHANNEL_ID,
+            &InPacket::RequestBeaconAtRound {
+                round: ROUND2,
                 origin: origin(1),
             },
         )
         .unwrap();
-        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
-
-        // Previous round processes no job
-        let msg = make_add_verified_round_msg(ROUND1, true);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 0);
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "0");
+        let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert_eq!(
+            ack,
+            StdAck::success(
+                to_json_binary(&InPacketAck::RequestQueued {
+                    source_id: format!("drand:{DRAND_CHAIN_HASH}:{ROUND2}"),
+                })
+                .unwrap()
+            )
+        );
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "receive_request_beacon_at_round"
+        );
+        assert_eq!(
+            unprocessed_drand_jobs_len(deps.as_ref().storage, ROUND2).unwrap(),
+            1
+        );
 
-        // Process one job
-        let msg = make_add_verified_round_msg(ROUND2, true);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 1);
-        assert_eq!(res.messages[0].gas_limit, None);
-        assert!(matches!(
-            res.messages[0].msg,
-            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
-        ));
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "1");
+        // The next round is still in the future
+        let msg = mock_ibc_packet_recv(
+            CHANNEL_ID,
+            &InPacket::RequestBeaconAtRound {
+                round: ROUND1,
+                origin: origin(2),
+            },
+        )
+        .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+        let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+        assert!(ack.is_success());
+        assert_eq!(
+            unprocessed_drand_jobs_len(deps.as_ref().storage, ROUND1).unwrap(),
+            1
+        );
 
-        // Create 2 job
-        for i in 0..2 {
+        // Rounds in the past are rejected
+        for round in [ROUND1 - 1, 1, 0] {
             let msg = mock_ibc_packet_recv(
-                "foo",
-                &InPacket::RequestBeacon {
-                    after: AFTER3,
-                    origin: origin(i),
+                CHANNEL_ID,
+                &InPacket::RequestBeaconAtRound {
+                    round,
+                    origin: origin(3),
                 },
             )
             .unwrap();
-            ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+            let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+            let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+            assert_eq!(
+                ack,
+                error_ack(
+                    PacketErrorCode::InvalidRound,
+                    &format!("Error processing packet: Round {round} is not in the future.")
+                )
+            );
         }
 
-        // Process first jobs
-        let msg = make_add_verified_round_msg(ROUND3, true);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 1);
-        assert_eq!(res.messages[0].gas_limit, None);
-        assert!(matches!(
-            res.messages[0].msg,
-            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
-        ));
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "1");
-
-        // Process second job
-        let msg = make_add_verified_round_msg(ROUND3, true);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 1);
-        assert_eq!(res.messages[0].gas_limit, None);
-        assert!(matches!(
-            res.messages[0].msg,
-            CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
-        ));
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "1");
-
-        // Create 21 job
-        for i in 0..21 {
+        // Rounds too far in the future are rejected
+        let max_round = round_after(env.block.time.plus_seconds(MAX_ROUND_DISTANCE_S));
+        for round in [max_round + 1, u64::from(u32::MAX) + 1, u64::MAX] {
             let msg = mock_ibc_packet_recv(
-                "foo",
-                &InPacket::RequestBeacon {
-                    after: AFTER4,
-                    origin: origin(i),
+                CHANNEL_ID,
+                &InPacket::RequestBeaconAtRound {
+                    round,
+                    origin: origin(4),
                 },
             )
             .unwrap();
-            let rec = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
-            dbg!(rec.acknowledgement);
+            let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+            let ack: StdAck = from_json(res.acknowledgement.unwrap()).unwrap();
+            assert_eq!(
+                ack,
+                error_ack(
+                    PacketErrorCode::InvalidRound,
+                    &format!("Error processing packet: Round {round} is too far in the future (max round: {max_round}).")
+                )
+            );
         }
 
-        // Process first job
-        let msg = make_add_verified_round_msg(ROUND4, true);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 1);
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "1");
-
-        // Process next job
-        let msg = make_add_verified_round_msg(ROUND4, true);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 1);
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "1");
-
-        // Process next job
-        let msg = make_add_verified_round_msg(ROUND4, true);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 1);
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "1");
-
-        // Process next 10 jobs
-        let msg = make_add_verified_round_msg(ROUND4, false);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 10);
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "10");
-
-        // Process remaining jobs
-        let msg = make_add_verified_round_msg(ROUND4, false);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 8);
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "8");
-
-        // No jobs left for later submissions
-        let msg = make_add_verified_round_msg(ROUND4, true);
-        let res = execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 0);
-        let jobs_processed = first_attr(&res.attributes, "jobs_processed").unwrap();
-        assert_eq!(jobs_processed, "0");
+        let CustomerResponse { customer } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Customer {
+                    channel_id: CHANNEL_ID.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(customer.unwrap().requested_beacons, 2);
     }
 
     //
@@ -1283,13 +2952,14 @@ mod tests {
 
         // Set drand contract
         let msg = ExecuteMsg::SetConfig {
-            manager: None,
             price: None,
             drand_addr: Some(drand.to_string()),
             trusted_sources: Some(vec![drand.to_string()]),
             payment_initial_funds: None,
+            sink: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        connect(deps.as_mut(), "foo");
 
         fn job_stats(deps: Deps, round: u64) -> DrandJobStatsResponse {
             from_json(query(deps, mock_env(), QueryMsg::DrandJobStats { round }).unwrap()).unwrap()
@@ -1398,6 +3068,104 @@ mod tests {
         );
     }
 
+    #[test]
+    fn query_job_stats_range_works() {
+        let mut deps = setup();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let drand = deps.api.addr_make("drand_verifier_7");
+
+        // Set drand contract
+        let msg = ExecuteMsg::SetConfig {
+            price: None,
+            drand_addr: Some(drand.to_string()),
+            trusted_sources: Some(vec![drand.to_string()]),
+            payment_initial_funds: None,
+            sink: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        connect(deps.as_mut(), "foo");
+
+        fn job_stats_range(
+            deps: Deps,
+            start_after: Option<u64>,
+            limit: Option<u32>,
+        ) -> Vec<DrandJobStatsResponse> {
+            let res: DrandJobStatsRangeResponse = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::DrandJobStatsRange { start_after, limit },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res.rounds
+        }
+
+        // No jobs by default
+        assert_eq!(job_stats_range(deps.as_ref(), None, None), vec![]);
+
+        // Create 1 job in ROUND1 and 3 jobs in ROUND3
+        for (after, job) in [(AFTER1, 1), (AFTER3, 2), (AFTER3, 3), (AFTER3, 4)] {
+            let msg = mock_ibc_packet_recv(
+                "foo",
+                &InPacket::RequestBeacon {
+                    after,
+                    origin: origin(job),
+                },
+            )
+            .unwrap();
+            ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        }
+
+        assert_eq!(
+            job_stats_range(deps.as_ref(), None, None),
+            vec![
+                DrandJobStatsResponse {
+                    round: ROUND1,
+                    processed: 0,
+                    unprocessed: 1,
+                },
+                DrandJobStatsResponse {
+                    round: ROUND3,
+                    processed: 0,
+                    unprocessed: 3,
+                }
+            ]
+        );
+        assert_eq!(
+            job_stats_range(deps.as_ref(), None, Some(1)),
+            vec![DrandJobStatsResponse {
+                round: ROUND1,
+                processed: 0,
+                unprocessed: 1,
+            }]
+        );
+        assert_eq!(
+            job_stats_range(deps.as_ref(), Some(ROUND1), None),
+            vec![DrandJobStatsResponse {
+                round: ROUND3,
+                processed: 0,
+                unprocessed: 3,
+            }]
+        );
+
+        // Processing all jobs of a round removes it from the list
+        let msg = make_add_verified_round_msg(ROUND1, true);
+        execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        let msg = make_add_verified_round_msg(ROUND3, true);
+        execute(deps.as_mut(), mock_env(), message_info(&drand, &[]), msg).unwrap();
+        assert_eq!(
+            job_stats_range(deps.as_ref(), None, None),
+            vec![DrandJobStatsResponse {
+                round: ROUND3,
+                processed: 1,
+                unprocessed: 2,
+            }]
+        );
+    }
+
     #[test]
     fn query_requests_works() {
         let mut deps = mock_dependencies();
@@ -1422,13 +3190,14 @@ mod tests {
 
         // Set drand contract
         let msg = ExecuteMsg::SetConfig {
-            manager: None,
             price: None,
             drand_addr: Some(drand.to_string()),
             trusted_sources: Some(vec![drand.to_string()]),
             payment_initial_funds: None,
+            sink: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        connect(deps.as_mut(), CHANNEL);
 
         fn requests_asc(deps: Deps, channel_id: &str) -> RequestsLogResponse {
             from_json(
@@ -1586,7 +3355,18 @@ mod tests {
 
         // All good
         let valid_handshake = mock_ibc_channel_open_try("channel-12", APP_ORDER, IBC_APP_VERSION);
-        ibc_channel_open(deps.as_mut(), mock_env(), valid_handshake).unwrap();
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), valid_handshake).unwrap();
+        assert_eq!(res.unwrap().version, "nois-v8");
+
+        // Older proxy keeps its version
+        let old_handshake = mock_ibc_channel_open_try("channel-12", APP_ORDER, IBC_APP_VERSION_V7);
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), old_handshake).unwrap();
+        assert_eq!(res.unwrap().version, "nois-v7");
+
+        // Newer proxy gets the highest version the gateway supports
+        let new_handshake = mock_ibc_channel_open_try("channel-12", APP_ORDER, "nois-v9");
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), new_handshake).unwrap();
+        assert_eq!(res.unwrap().version, "nois-v8");
 
         // Wrong order
         let wrong_order = mock_ibc_channel_open_try("channel-12", BAD_APP_ORDER, IBC_APP_VERSION);
@@ -1704,6 +3484,9 @@ mod tests {
                 channel_id: channel_id.to_string(),
                 payment: Addr::unchecked("some payment address"),
                 requested_beacons: 0,
+                limits: CustomerLimits::default(),
+                version: IBC_APP_VERSION.to_string(),
+                suspended: false,
             })
         );
 
@@ -1736,11 +3519,11 @@ mod tests {
 
         // Set drand contract
         let msg = ExecuteMsg::SetConfig {
-            manager: None,
             price: None,
             drand_addr: Some(drand.to_string()),
             trusted_sources: Some(vec![drand.to_string()]),
             payment_initial_funds: None,
+            sink: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
 
@@ -1853,6 +3636,9 @@ mod tests {
                 channel_id: channel_id.to_string(),
                 payment: Addr::unchecked("some payment address"),
                 requested_beacons: 0,
+                limits: CustomerLimits::default(),
+                version: IBC_APP_VERSION.to_string(),
+                suspended: false,
             }]
         );
 
@@ -1,8 +1,10 @@
 use thiserror::Error;
 
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, StdError};
 
-use nois_protocol::ChannelError;
+use manager_transfer::ManagerTransferError;
+
+use nois_protocol::{ChannelError, PacketErrorCode};
 
 #[derive(Error, Debug, PartialEq)]
 #[non_exhaustive]
@@ -13,17 +15,55 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized,
 
+    #[error("{0}")]
+    ManagerTransfer(#[from] ManagerTransferError),
+
     // Payment
     #[error("Code ID does not exist: {code_id}")]
     CodeIdDoesNotExist { code_id: u64 },
 
     // Jobs
+    #[error("The gateway is paused. No new beacon requests are accepted.")]
+    Paused,
+
     #[error("Origin data exceeds length limit.")]
     OriginTooLong,
 
+    #[error("Packet must contain at least one beacon request.")]
+    NoBeaconRequests,
+
+    #[error("Packet must not contain more than {max} beacon requests.")]
+    TooManyBeaconRequests { max: usize },
+
+    #[error("Round {round} is not in the future.")]
+    RoundNotInFuture { round: u64 },
+
+    #[error("Round {round} is too far in the future (max round: {max_round}).")]
+    RoundTooHigh { round: u64, max_round: u64 },
+
     #[error("Sender is unauthorized to add verified round.")]
     UnauthorizedAddVerifiedRound,
 
+    // Rate limits
+    #[error("Limit of {limit} beacon requests per block exceeded.")]
+    RequestsPerBlockLimitExceeded { limit: u32 },
+
+    #[error("Limit of {limit} beacon requests per {seconds} seconds exceeded.")]
+    RequestsPerWindowLimitExceeded { limit: u32, seconds: u64 },
+
+    #[error("Limit of {limit} queued jobs exceeded. Wait for queued jobs to be processed.")]
+    QueuedJobsLimitExceeded { limit: u32 },
+
+    // Customers
+    #[error("Customer not found for channel: {channel_id}")]
+    CustomerNotFound { channel_id: String },
+
+    #[error("Customer of channel {channel_id} is suspended.")]
+    CustomerSuspended { channel_id: String },
+
+    #[error("Payment contract balance of {balance} is insufficient to pay {required}.")]
+    InsufficientPaymentBalance { balance: Coin, required: Coin },
+
     // IBC
     #[error("The nois-gateway contract must be on chain B of the connection. Try swapping A and B in the channel creation.")]
     MustBeChainB,
@@ -37,3 +77,29 @@ pub enum ContractError {
     #[error("Cannot register over an existing channel")]
     ChannelAlreadyRegistered,
 }
+
+impl ContractError {
+    /// The code that is sent to the proxy when this error occurs while processing a packet
+    pub fn packet_error_code(&self) -> PacketErrorCode {
+        match self {
+            ContractError::Std(StdError::ParseErr { .. }) => PacketErrorCode::InvalidPacket,
+            ContractError::OriginTooLong => PacketErrorCode::OriginTooLong,
+            ContractError::CustomerSuspended { .. } => PacketErrorCode::CustomerSuspended,
+            ContractError::InsufficientPaymentBalance { .. } => {
+                PacketErrorCode::InsufficientPaymentBalance
+            }
+            ContractError::Paused => PacketErrorCode::Paused,
+            ContractError::UnsupportedPacketType => PacketErrorCode::UnsupportedPacket,
+            ContractError::NoBeaconRequests | ContractError::TooManyBeaconRequests { .. } => {
+                PacketErrorCode::InvalidBatchSize
+            }
+            ContractError::RoundNotInFuture { .. } | ContractError::RoundTooHigh { .. } => {
+                PacketErrorCode::InvalidRound
+            }
+            ContractError::RequestsPerBlockLimitExceeded { .. }
+            | ContractError::RequestsPerWindowLimitExceeded { .. }
+            | ContractError::QueuedJobsLimitExceeded { .. } => PacketErrorCode::LimitExceeded,
+            _ => PacketErrorCode::Other,
+        }
+    }
+}
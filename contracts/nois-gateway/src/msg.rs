@@ -1,7 +1,9 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
 use cosmwasm_std::{Addr, Coin, HexBinary};
+use manager_transfer::PendingManagerResponse;
+use nois_protocol::IBC_APP_VERSION_V7;
 
-use crate::state::{Config, Customer, Job, RequestLogEntry};
+use crate::state::{Config, Customer, CustomerLimits, Job, RequestLogEntry};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -26,7 +28,6 @@ pub enum ExecuteMsg {
     },
     /// Set the config
     SetConfig {
-        manager: Option<String>,
         price: Option<Coin>,
         drand_addr: Option<String>,
         /// Updates the `payment_initial_funds`. When this value is set, the config will be updated.
@@ -35,7 +36,58 @@ pub enum ExecuteMsg {
         /// To deactivate it later on, send Some(Coin::new(0, "unois")) here.
         payment_initial_funds: Option<Coin>,
         trusted_sources: Option<Vec<String>>,
+        /// Updates the address of the Nois sink used for newly instantiated payment contracts.
+        /// Use `UpdatePaymentSinks` to push the new address to existing payment contracts.
+        sink: Option<String>,
     },
+    /// Sets the code ID of the payment contract. This is used for newly instantiated
+    /// payment contracts and as the target of `MigratePayments`.
+    /// Only the manager can do this.
+    SetPaymentCodeId { code_id: u64 },
+    /// Migrates existing payment contracts to the current payment code ID.
+    /// Customers are processed in ascending channel ID order. Use the `last_channel_id`
+    /// event attribute as `start_after` for the next call until `done` is true.
+    /// Only the manager can do this.
+    MigratePayments {
+        /// The channel ID after which to start
+        start_after: Option<String>,
+        /// The max number of payment contracts migrated.
+        /// When unset, an implementation defined default will be used.
+        limit: Option<u32>,
+    },
+    /// Sets the sink of existing payment contracts to the sink of the gateway config.
+    /// Customers are processed in ascending channel ID order. Use the `last_channel_id`
+    /// event attribute as `start_after` for the next call until `done` is true.
+    /// Only the manager can do this.
+    UpdatePaymentSinks {
+        /// The channel ID after which to start
+        start_after: Option<String>,
+        /// The max number of payment contracts updated.
+        /// When unset, an implementation defined default will be used.
+        limit: Option<u32>,
+    },
+    /// Sets the request limits of a customer. Only the manager can do this.
+    /// Unset limits mean unlimited.
+    SetCustomerLimits {
+        channel_id: String,
+        limits: CustomerLimits,
+    },
+    /// Suspends or unsuspends a customer. Beacon requests of a suspended customer are
+    /// rejected with an error acknowledgement. Already queued jobs are still delivered.
+    /// Only the manager can do this.
+    SetCustomerSuspended { channel_id: String, suspended: bool },
+    /// Proposes a new manager. The new manager has to accept the role using `AcceptManager`.
+    /// Only the manager can do this.
+    ProposeManager { manager: String },
+    /// Accepts the manager role. Must be sent by the pending manager.
+    AcceptManager {},
+    /// Cancels a pending manager transfer. Only the manager can do this.
+    CancelManagerTransfer {},
+    /// Pauses or unpauses the gateway. While paused, beacon requests are rejected
+    /// with an error acknowledgement. Verified rounds are still stored and already
+    /// queued jobs are still delivered.
+    /// Only the manager can do this.
+    SetPaused { paused: bool },
 }
 
 #[cw_serde]
@@ -47,6 +99,16 @@ pub enum QueryMsg {
     /// Gets basic statistics about jobs in this drand round.
     #[returns(DrandJobStatsResponse)]
     DrandJobStats { round: u64 },
+    /// Gets basic statistics about jobs for all drand rounds with unprocessed jobs.
+    /// Rounds are sorted in ascending order.
+    #[returns(DrandJobStatsRangeResponse)]
+    DrandJobStatsRange {
+        /// If set only rounds greater than this value are returned
+        start_after: Option<u64>,
+        /// The max number of entries returned. If you set this too high, your query runs out of gas.
+        /// When unset, an implementation defined default will be used.
+        limit: Option<u32>,
+    },
     #[returns(CustomerResponse)]
     Customer { channel_id: String },
     #[returns(CustomersResponse)]
@@ -81,6 +143,9 @@ pub enum QueryMsg {
         offset: Option<u32>,
         limit: Option<u32>,
     },
+    /// Gets the manager that was proposed but did not accept yet
+    #[returns(PendingManagerResponse)]
+    PendingManager {},
 }
 
 // We define a custom struct for each query response
@@ -95,6 +160,12 @@ pub struct DrandJobStatsResponse {
     pub processed: u32,
 }
 
+#[cw_serde]
+pub struct DrandJobStatsRangeResponse {
+    /// Stats of rounds with unprocessed jobs
+    pub rounds: Vec<DrandJobStatsResponse>,
+}
+
 #[cw_serde]
 pub struct QueriedCustomer {
     pub channel_id: String,
@@ -102,6 +173,12 @@ pub struct QueriedCustomer {
     pub payment: Addr,
     /// Number of beacons requested in total
     pub requested_beacons: u64,
+    /// Request limits of this customer
+    pub limits: CustomerLimits,
+    /// The IBC app version negotiated for the channel
+    pub version: String,
+    /// Suspended customers cannot request beacons
+    pub suspended: bool,
 }
 
 impl QueriedCustomer {
@@ -110,6 +187,11 @@ impl QueriedCustomer {
             channel_id,
             payment: customer.payment,
             requested_beacons: customer.requested_beacons,
+            limits: customer.limits.unwrap_or_default(),
+            version: customer
+                .version
+                .unwrap_or_else(|| IBC_APP_VERSION_V7.to_string()),
+            suspended: customer.suspended.unwrap_or_default(),
         }
     }
 }
@@ -10,8 +10,8 @@ use nois_protocol::{InPacketAck, OutPacket, DELIVER_BEACON_PACKET_LIFETIME};
 use crate::{
     drand_archive::{archive_lookup, archive_store},
     state::{
-        increment_processed_drand_jobs, unprocessed_drand_jobs_dequeue,
-        unprocessed_drand_jobs_enqueue, Job, CONFIG,
+        decrement_queued_jobs, increment_processed_drand_jobs, increment_queued_jobs,
+        unprocessed_drand_jobs_dequeue, unprocessed_drand_jobs_enqueue, Job, CONFIG,
     },
 };
 
@@ -51,24 +51,38 @@ impl RequestRouter {
         origin: Binary,
     ) -> StdResult<RoutingReceipt> {
         // Here we currently only have one backend
-        self.handle_drand_request(deps, env, channel, after, origin)
+        let (round, source_id) = commit_to_drand_round(after);
+        self.handle_drand_request(deps, env, channel, round, source_id, origin)
     }
 
-    fn handle_drand_request(
+    /// Routes a request for a specific drand round. The caller is responsible for
+    /// checking that the round is in the future.
+    pub fn route_drand_round(
         &self,
         deps: DepsMut,
         env: &Env,
         channel: String,
-        after: Timestamp,
+        round: u64,
         origin: Binary,
     ) -> StdResult<RoutingReceipt> {
-        let (round, source_id) = commit_to_drand_round(after);
+        let source_id = drand_source_id(round);
+        self.handle_drand_request(deps, env, channel, round, source_id, origin)
+    }
 
+    fn handle_drand_request(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        channel: String,
+        round: u64,
+        source_id: String,
+        origin: Binary,
+    ) -> StdResult<RoutingReceipt> {
         let existing_randomness = archive_lookup(deps.storage, round);
 
         let job = Job {
             source_id: source_id.clone(),
-            channel,
+            channel: channel.clone(),
             origin,
         };
 
@@ -84,6 +98,7 @@ impl RequestRouter {
             false
         } else {
             unprocessed_drand_jobs_enqueue(deps.storage, round, &job)?;
+            increment_queued_jobs(deps.storage, &channel)?;
             let config = CONFIG.load(deps.storage)?;
             if let Some(drand_addr) = config.drand {
                 msgs.push(
@@ -139,6 +154,7 @@ impl RequestRouter {
         // let max_jobs_per_submission
         while let Some(job) = unprocessed_drand_jobs_dequeue(deps.storage, round)? {
             increment_processed_drand_jobs(deps.storage, round)?;
+            decrement_queued_jobs(deps.storage, &job.channel)?;
             let published = time_of_round(round);
             // Use IbcMsg::SendPacket to send packages to the proxies.
             let msg = create_deliver_beacon_ibc_message(
@@ -186,8 +202,11 @@ fn create_deliver_beacon_ibc_message(
 /// Calculates the next round in the future, i.e. publish time > base time.
 fn commit_to_drand_round(after: Timestamp) -> (u64, String) {
     let round = round_after(after);
-    let source_id = format!("drand:{}:{}", DRAND_CHAIN_HASH, round);
-    (round, source_id)
+    (round, drand_source_id(round))
+}
+
+fn drand_source_id(round: u64) -> String {
+    format!("drand:{}:{}", DRAND_CHAIN_HASH, round)
 }
 
 #[cfg(test)]
@@ -22,6 +22,11 @@ pub struct Config {
     pub payment_initial_funds: Option<Coin>,
     /// Address of the Nois sink
     pub sink: Addr,
+    /// When paused, new beacon requests are rejected. Verified rounds are still
+    /// stored and already queued jobs are still delivered.
+    /// None means not paused. This is just an Option for compatibility
+    /// with previous versions of the contract.
+    pub paused: Option<bool>,
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
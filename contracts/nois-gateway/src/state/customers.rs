@@ -2,12 +2,23 @@ use cosmwasm_schema::cw_serde;
 use cosmwasm_std::Addr;
 use cw_storage_plus::Map;
 
+use super::CustomerLimits;
+
 #[cw_serde]
 pub struct Customer {
     /// The payment contract address
     pub payment: Addr,
     /// Number of beacons requested in total
     pub requested_beacons: u64,
+    /// Request limits of this customer. None means unlimited.
+    /// This is just an Option for compatibility with previous versions of the contract.
+    pub limits: Option<CustomerLimits>,
+    /// The IBC app version negotiated for the channel. None means `nois-v7`.
+    /// This is just an Option for compatibility with previous versions of the contract.
+    pub version: Option<String>,
+    /// A suspended customer cannot request beacons. None means not suspended.
+    /// This is just an Option for compatibility with previous versions of the contract.
+    pub suspended: Option<bool>,
 }
 
 /// A map from channel ID to customer information
@@ -1,6 +1,8 @@
 use cosmwasm_schema::cw_serde;
+use std::collections::BTreeMap;
+
 use cosmwasm_std::{Binary, Order, StdResult, Storage};
-use cw_storage_plus::Map;
+use cw_storage_plus::{Bound, Map};
 
 #[cw_serde]
 pub struct Job {
@@ -14,6 +16,9 @@ pub struct Job {
 /// A map from (round, job ID) here job ID is a round specific auto incrementing ID
 const JOBS: Map<(u32, u16), Job> = Map::new("djobs");
 const LAST_JOB_ID: Map<u32, u16> = Map::new("djids");
+/// A map from round to the number of unprocessed jobs in this round.
+/// Rounds without unprocessed jobs have no entry.
+const UNPROCESSED_COUNT: Map<u32, u32> = Map::new("djuc");
 
 /// Add an element to the unprocessed drand jobs queue of this round
 pub fn unprocessed_drand_jobs_enqueue(
@@ -25,6 +30,11 @@ pub fn unprocessed_drand_jobs_enqueue(
     let new_id = LAST_JOB_ID.may_load(storage, round)?.unwrap_or_default() + 1;
     JOBS.save(storage, (round, new_id), value)?;
     LAST_JOB_ID.save(storage, round, &new_id)?;
+    let count = UNPROCESSED_COUNT
+        .may_load(storage, round)?
+        .unwrap_or_default()
+        + 1;
+    UNPROCESSED_COUNT.save(storage, round, &count)?;
     Ok(())
 }
 
@@ -43,18 +53,67 @@ pub fn unprocessed_drand_jobs_dequeue(
     };
     let (id, job) = found?;
     JOBS.remove(storage, (round, id));
+    let count = UNPROCESSED_COUNT
+        .may_load(storage, round)?
+        .unwrap_or_default();
+    if count > 1 {
+        UNPROCESSED_COUNT.save(storage, round, &(count - 1))?;
+    } else {
+        UNPROCESSED_COUNT.remove(storage, round);
+    }
     Ok(Some(job))
 }
 
 /// Gets the number of unprocessed drand jobs queue of this round.
-/// This is inefficient for many jobs in a single round.
 pub fn unprocessed_drand_jobs_len(storage: &dyn Storage, round: u64) -> StdResult<u32> {
     let round: u32 = round.try_into().expect("round must not exceed u32 range");
-    let count = JOBS
-        .prefix(round)
-        .keys_raw(storage, None, None, Order::Ascending)
-        .count();
-    Ok(count as u32)
+    let count = UNPROCESSED_COUNT
+        .may_load(storage, round)?
+        .unwrap_or_default();
+    Ok(count)
+}
+
+/// Gets rounds with unprocessed jobs in ascending order together with
+/// the number of unprocessed jobs in each of them.
+pub fn unprocessed_drand_jobs_rounds(
+    storage: &dyn Storage,
+    start_after: Option<u64>,
+    limit: usize,
+) -> StdResult<Vec<(u64, u32)>> {
+    // Rounds beyond the u32 range cannot have jobs, so we saturate here
+    let start_after = start_after.map(|r| u32::try_from(r).unwrap_or(u32::MAX));
+    UNPROCESSED_COUNT
+        .range(
+            storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|res| res.map(|(round, count)| (round.into(), count)))
+        .collect()
+}
+
+/// Recreates the unprocessed jobs counters from the jobs queue and returns the number of
+/// unprocessed jobs per channel.
+///
+/// This is needed when migrating from a version that did not maintain the counters
+/// and is inefficient for many jobs.
+pub fn recount_unprocessed_drand_jobs(
+    storage: &mut dyn Storage,
+) -> StdResult<BTreeMap<String, u32>> {
+    let mut counts = BTreeMap::<u32, u32>::new();
+    let mut channel_counts = BTreeMap::<String, u32>::new();
+    for item in JOBS.range(storage, None, None, Order::Ascending) {
+        let ((round, _id), job) = item?;
+        *counts.entry(round).or_default() += 1;
+        *channel_counts.entry(job.channel).or_default() += 1;
+    }
+    UNPROCESSED_COUNT.clear(storage);
+    for (round, count) in counts {
+        UNPROCESSED_COUNT.save(storage, round, &count)?;
+    }
+    Ok(channel_counts)
 }
 
 pub fn all_unprocessed_drand_jobs(
@@ -148,6 +207,72 @@ mod tests {
         assert_eq!(unprocessed_drand_jobs_len(&storage, round).unwrap(), 0);
     }
 
+    #[test]
+    fn unprocessed_drand_jobs_rounds_works() {
+        let mut storage = MockStorage::default();
+
+        let rounds = unprocessed_drand_jobs_rounds(&storage, None, 100).unwrap();
+        assert_eq!(rounds, []);
+
+        unprocessed_drand_jobs_enqueue(&mut storage, 5, &make_job(1)).unwrap();
+        unprocessed_drand_jobs_enqueue(&mut storage, 3, &make_job(2)).unwrap();
+        unprocessed_drand_jobs_enqueue(&mut storage, 5, &make_job(3)).unwrap();
+        unprocessed_drand_jobs_enqueue(&mut storage, 8, &make_job(4)).unwrap();
+
+        let rounds = unprocessed_drand_jobs_rounds(&storage, None, 100).unwrap();
+        assert_eq!(rounds, [(3, 1), (5, 2), (8, 1)]);
+        let rounds = unprocessed_drand_jobs_rounds(&storage, None, 2).unwrap();
+        assert_eq!(rounds, [(3, 1), (5, 2)]);
+        let rounds = unprocessed_drand_jobs_rounds(&storage, Some(3), 100).unwrap();
+        assert_eq!(rounds, [(5, 2), (8, 1)]);
+        let rounds = unprocessed_drand_jobs_rounds(&storage, Some(4), 1).unwrap();
+        assert_eq!(rounds, [(5, 2)]);
+        let rounds = unprocessed_drand_jobs_rounds(&storage, Some(8), 100).unwrap();
+        assert_eq!(rounds, []);
+        let rounds = unprocessed_drand_jobs_rounds(&storage, Some(u64::MAX), 100).unwrap();
+        assert_eq!(rounds, []);
+
+        // Rounds disappear once all their jobs are processed
+        let _ = unprocessed_drand_jobs_dequeue(&mut storage, 3).unwrap();
+        let _ = unprocessed_drand_jobs_dequeue(&mut storage, 5).unwrap();
+        let rounds = unprocessed_drand_jobs_rounds(&storage, None, 100).unwrap();
+        assert_eq!(rounds, [(5, 1), (8, 1)]);
+        let _ = unprocessed_drand_jobs_dequeue(&mut storage, 5).unwrap();
+        let _ = unprocessed_drand_jobs_dequeue(&mut storage, 5).unwrap();
+        let rounds = unprocessed_drand_jobs_rounds(&storage, None, 100).unwrap();
+        assert_eq!(rounds, [(8, 1)]);
+    }
+
+    #[test]
+    fn recount_unprocessed_drand_jobs_works() {
+        let mut storage = MockStorage::default();
+
+        // Jobs stored by a version without counters
+        let other_channel_job = Job {
+            channel: "chan-456".to_string(),
+            ..make_job(1)
+        };
+        JOBS.save(&mut storage, (3, 1), &other_channel_job).unwrap();
+        JOBS.save(&mut storage, (5, 1), &make_job(2)).unwrap();
+        JOBS.save(&mut storage, (5, 2), &make_job(3)).unwrap();
+        assert_eq!(unprocessed_drand_jobs_len(&storage, 5).unwrap(), 0);
+
+        let channel_counts = recount_unprocessed_drand_jobs(&mut storage).unwrap();
+        assert_eq!(
+            channel_counts,
+            BTreeMap::from([("chan-123".to_string(), 2), ("chan-456".to_string(), 1)])
+        );
+        assert_eq!(unprocessed_drand_jobs_len(&storage, 3).unwrap(), 1);
+        assert_eq!(unprocessed_drand_jobs_len(&storage, 5).unwrap(), 2);
+        let rounds = unprocessed_drand_jobs_rounds(&storage, None, 100).unwrap();
+        assert_eq!(rounds, [(3, 1), (5, 2)]);
+
+        // Running it again does not change anything
+        recount_unprocessed_drand_jobs(&mut storage).unwrap();
+        let rounds = unprocessed_drand_jobs_rounds(&storage, None, 100).unwrap();
+        assert_eq!(rounds, [(3, 1), (5, 2)]);
+    }
+
     #[test]
     fn all_unprocessed_drand_jobs_works() {
         let mut storage = MockStorage::default();
@@ -1,14 +1,19 @@
 mod config;
 mod customers;
 mod drand_jobs;
+mod rate_limits;
 mod requests_log;
 mod stats;
 
 pub use config::{Config, CONFIG};
 pub use customers::{Customer, CUSTOMERS};
 pub use drand_jobs::{
-    all_unprocessed_drand_jobs, unprocessed_drand_jobs_dequeue, unprocessed_drand_jobs_enqueue,
-    unprocessed_drand_jobs_len, Job,
+    all_unprocessed_drand_jobs, recount_unprocessed_drand_jobs, unprocessed_drand_jobs_dequeue,
+    unprocessed_drand_jobs_enqueue, unprocessed_drand_jobs_len, unprocessed_drand_jobs_rounds, Job,
+};
+pub use rate_limits::{
+    check_and_count_requests, decrement_queued_jobs, increment_queued_jobs, set_queued_jobs,
+    CustomerLimits, RequestWindowLimit,
 };
 pub use requests_log::{requests_log_add, requests_log_asc, requests_log_desc, RequestLogEntry};
 pub use stats::{get_processed_drand_jobs, increment_processed_drand_jobs};
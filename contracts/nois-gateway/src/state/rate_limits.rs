@@ -0,0 +1,133 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Env, StdResult, Storage, Timestamp};
+use cw_storage_plus::Map;
+
+use crate::error::ContractError;
+
+/// Limits for the beacon requests of a customer.
+/// All limits are optional and `None` means unlimited.
+#[cw_serde]
+#[derive(Default)]
+pub struct CustomerLimits {
+    /// The max number of beacon requests per block
+    pub max_requests_per_block: Option<u32>,
+    /// The max number of beacon requests per time window
+    pub max_requests_per_window: Option<RequestWindowLimit>,
+    /// The max number of outstanding queued jobs. Once this is reached,
+    /// new requests are rejected until queued jobs are processed.
+    pub max_queued_jobs: Option<u32>,
+}
+
+#[cw_serde]
+pub struct RequestWindowLimit {
+    /// Length of the time window in seconds
+    pub seconds: u64,
+    /// The max number of beacon requests within one time window
+    pub max_requests: u32,
+}
+
+/// Usage data of a customer used for enforcing the limits
+#[cw_serde]
+#[derive(Default)]
+struct CustomerUsage {
+    /// The block height in which the last request was made
+    block_height: u64,
+    /// Number of requests made in `block_height`
+    block_requests: u32,
+    /// Start of the current time window
+    window_start: Timestamp,
+    /// Number of requests made in the current time window
+    window_requests: u32,
+    /// Number of jobs of this customer that are queued
+    queued_jobs: u32,
+}
+
+/// A map from channel ID to customer usage
+const CUSTOMER_USAGE: Map<&str, CustomerUsage> = Map::new("customer_usage");
+
+/// Checks the limits for `count` new beacon requests of this customer and counts the
+/// requests if all limits are respected. Either all or none of the requests are counted.
+pub fn check_and_count_requests(
+    storage: &mut dyn Storage,
+    env: &Env,
+    channel_id: &str,
+    limits: &CustomerLimits,
+    count: u32,
+) -> Result<(), ContractError> {
+    let mut usage = CUSTOMER_USAGE
+        .may_load(storage, channel_id)?
+        .unwrap_or_default();
+
+    if usage.block_height != env.block.height {
+        usage.block_height = env.block.height;
+        usage.block_requests = 0;
+    }
+    if let Some(limit) = limits.max_requests_per_block {
+        if usage.block_requests.saturating_add(count) > limit {
+            return Err(ContractError::RequestsPerBlockLimitExceeded { limit });
+        }
+    }
+
+    if let Some(RequestWindowLimit {
+        seconds,
+        max_requests,
+    }) = limits.max_requests_per_window
+    {
+        if env.block.time >= usage.window_start.plus_seconds(seconds) {
+            usage.window_start = env.block.time;
+            usage.window_requests = 0;
+        }
+        if usage.window_requests.saturating_add(count) > max_requests {
+            return Err(ContractError::RequestsPerWindowLimitExceeded {
+                limit: max_requests,
+                seconds,
+            });
+        }
+    }
+
+    if let Some(limit) = limits.max_queued_jobs {
+        if usage.queued_jobs.saturating_add(count) > limit {
+            return Err(ContractError::QueuedJobsLimitExceeded { limit });
+        }
+    }
+
+    usage.block_requests += count;
+    usage.window_requests += count;
+    CUSTOMER_USAGE.save(storage, channel_id, &usage)?;
+    Ok(())
+}
+
+/// Increments the number of queued jobs of this customer.
+pub fn increment_queued_jobs(storage: &mut dyn Storage, channel_id: &str) -> StdResult<()> {
+    let mut usage = CUSTOMER_USAGE
+        .may_load(storage, channel_id)?
+        .unwrap_or_default();
+    usage.queued_jobs += 1;
+    CUSTOMER_USAGE.save(storage, channel_id, &usage)?;
+    Ok(())
+}
+
+/// Sets the number of queued jobs of this customer. This is used to count the jobs
+/// queued before this counter existed during migration.
+pub fn set_queued_jobs(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    queued_jobs: u32,
+) -> StdResult<()> {
+    let mut usage = CUSTOMER_USAGE
+        .may_load(storage, channel_id)?
+        .unwrap_or_default();
+    usage.queued_jobs = queued_jobs;
+    CUSTOMER_USAGE.save(storage, channel_id, &usage)?;
+    Ok(())
+}
+
+/// Decrements the number of queued jobs of this customer. This saturates at 0.
+pub fn decrement_queued_jobs(storage: &mut dyn Storage, channel_id: &str) -> StdResult<()> {
+    let mut usage = CUSTOMER_USAGE
+        .may_load(storage, channel_id)?
+        .unwrap_or_default();
+    usage.queued_jobs = usage.queued_jobs.saturating_sub(1);
+    CUSTOMER_USAGE.save(storage, channel_id, &usage)?;
+    Ok(())
+}
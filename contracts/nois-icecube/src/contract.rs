@@ -3,6 +3,9 @@ use cosmwasm_std::{
     Env, MessageInfo, QueryResponse, Response, StakingMsg, StdResult, Uint128,
 };
 use cw2::set_contract_version;
+use manager_transfer::{
+    accept_manager, cancel_manager_transfer, propose_manager, query_pending_manager,
+};
 
 use crate::error::ContractError;
 use crate::msg::{ConfigResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
@@ -60,9 +63,9 @@ pub fn execute(
         } => execute_redelegate(deps, info, src_addr, dest_addr, amount),
         ExecuteMsg::ClaimRewards { addr } => execute_claim_rewards(addr),
         ExecuteMsg::SetDrandAddr { addr } => execute_set_drand_addr(deps, info, env, addr),
-        ExecuteMsg::SetManagerAddr { manager } => {
-            execute_set_manager_addr(deps, info, env, manager)
-        }
+        ExecuteMsg::ProposeManager { manager } => execute_propose_manager(deps, info, manager),
+        ExecuteMsg::AcceptManager {} => execute_accept_manager(deps, info),
+        ExecuteMsg::CancelManagerTransfer {} => execute_cancel_manager_transfer(deps, info),
     }
 }
 
@@ -70,6 +73,7 @@ pub fn execute(
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
     let response = match msg {
         QueryMsg::Config {} => to_json_binary(&query_config(deps)?)?,
+        QueryMsg::PendingManager {} => to_json_binary(&query_pending_manager(deps.storage)?)?,
     };
     Ok(response)
 }
@@ -201,23 +205,51 @@ fn execute_set_drand_addr(
     Ok(Response::new().add_attribute("nois-drand-address", nois_drand_address))
 }
 
-fn execute_set_manager_addr(
+fn execute_propose_manager(
     deps: DepsMut,
     info: MessageInfo,
-    _env: Env,
     manager: String,
 ) -> Result<Response, ContractError> {
-    let mut config = CONFIG.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
 
     // check the calling address is the authorised address
     ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
 
     let manager_addr = deps.api.addr_validate(&manager)?;
+    propose_manager(deps.storage, &manager_addr)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_manager")
+        .add_attribute("pending_manager", manager_addr))
+}
+
+fn execute_accept_manager(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    let manager_addr = accept_manager(deps.storage, &info.sender)?;
     config.manager = manager_addr.clone();
 
     CONFIG.save(deps.storage, &config)?;
 
-    Ok(Response::new().add_attribute("manager", manager_addr))
+    Ok(Response::new()
+        .add_attribute("action", "accept_manager")
+        .add_attribute("manager", manager_addr))
+}
+
+fn execute_cancel_manager_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // check the calling address is the authorised address
+    ensure_eq!(info.sender, config.manager, ContractError::Unauthorized);
+
+    let cancelled = cancel_manager_transfer(deps.storage)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_manager_transfer")
+        .add_attribute("pending_manager", cancelled))
 }
 
 fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
@@ -234,6 +266,7 @@ mod tests {
         testing::{message_info, mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage},
         CosmosMsg, Empty, OwnedDeps, Uint128,
     };
+    use manager_transfer::{ManagerTransferError, PendingManagerResponse};
 
     const CREATOR: &str = "creator";
     const MANAGER: &str = "the_manager_addr";
@@ -416,7 +449,7 @@ mod tests {
     }
 
     #[test]
-    fn only_manager_can_set_manager() {
+    fn only_manager_can_propose_manager() {
         let mut deps = mock_dependencies();
 
         let creator = deps.api.addr_make(CREATOR);
@@ -430,28 +463,60 @@ mod tests {
         let info = message_info(&creator, &[]);
         instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // A random addr cannot set a new manager
+        // A random addr cannot propose a new manager
         let info = message_info(&random, &[]);
-        let msg = ExecuteMsg::SetManagerAddr {
+        let msg = ExecuteMsg::ProposeManager {
             manager: new_manager.to_string(),
         };
         let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
         assert!(matches!(err, ContractError::Unauthorized));
 
-        // Creator cannot set a new manager
+        // Creator cannot propose a new manager
         let info = message_info(&creator, &[]);
-        let msg = ExecuteMsg::SetManagerAddr {
+        let msg = ExecuteMsg::ProposeManager {
             manager: new_manager.to_string(),
         };
         let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
         assert!(matches!(err, ContractError::Unauthorized));
 
-        // Manager can set a new manager
+        // Manager can propose a new manager
         let info = message_info(&manager, &[]);
-        let msg = ExecuteMsg::SetManagerAddr {
+        let msg = ExecuteMsg::ProposeManager {
             manager: new_manager.to_string(),
         };
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let pending: PendingManagerResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending.pending_manager, Some(new_manager.clone()));
+
+        // Manager does not change before the transfer is accepted
+        let config: ConfigResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.manager, manager);
+
+        // Only the pending manager can accept
+        let info = message_info(&random, &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ManagerTransfer(ManagerTransferError::NotPendingManager)
+        ));
+
+        let info = message_info(&new_manager, &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap();
         let config: ConfigResponse =
             from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
         assert_eq!(
@@ -461,5 +526,63 @@ mod tests {
                 drand: None,
             }
         );
+        let pending: PendingManagerResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending.pending_manager, None);
+    }
+
+    #[test]
+    fn cancel_manager_transfer_works() {
+        let mut deps = mock_dependencies();
+
+        let manager = deps.api.addr_make(MANAGER);
+        let new_manager = deps.api.addr_make("new manager");
+
+        let msg = InstantiateMsg {
+            manager: manager.to_string(),
+        };
+        let info = message_info(&manager, &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = message_info(&manager, &[]);
+        let msg = ExecuteMsg::ProposeManager {
+            manager: new_manager.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Only the manager can cancel
+        let info = message_info(&new_manager, &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::CancelManagerTransfer {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        let info = message_info(&manager, &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::CancelManagerTransfer {},
+        )
+        .unwrap();
+
+        // Accepting a cancelled transfer fails
+        let info = message_info(&new_manager, &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ManagerTransfer(ManagerTransferError::NoPendingManager)
+        ));
     }
 }
@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 use cosmwasm_std::StdError;
+use manager_transfer::ManagerTransferError;
 
 #[derive(Error, Debug, PartialEq)]
 #[non_exhaustive]
@@ -11,6 +12,9 @@ pub enum ContractError {
     #[error("Unauthorized.")]
     Unauthorized,
 
+    #[error("{0}")]
+    ManagerTransfer(#[from] ManagerTransferError),
+
     #[error("Unauthorized. Contract is already set")]
     ContractAlreadySet,
 
@@ -1,5 +1,6 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
 use cosmwasm_std::{Coin, Uint128};
+use manager_transfer::PendingManagerResponse;
 
 use crate::state::Config;
 
@@ -45,12 +46,13 @@ pub enum ExecuteMsg {
     },
     /// Sends funds from the icecube to the drand contract.
     /// This can be executed by any address in case the drand contract runs dry.
-    SendFundsToDrand {
-        funds: Coin,
-    },
-    SetManagerAddr {
-        manager: String,
-    },
+    SendFundsToDrand { funds: Coin },
+    /// Proposes a new manager. The new manager has to accept the role using `AcceptManager`.
+    ProposeManager { manager: String },
+    /// Accepts the manager role. Must be sent by the pending manager.
+    AcceptManager {},
+    /// Cancels a pending manager transfer. Only the current manager can do this.
+    CancelManagerTransfer {},
 }
 
 #[cw_serde]
@@ -59,6 +61,9 @@ pub enum QueryMsg {
     /// Get the config state
     #[returns(ConfigResponse)]
     Config {},
+    /// Gets the manager that was proposed but did not accept yet
+    #[returns(PendingManagerResponse)]
+    PendingManager {},
 }
 
 // We define a custom struct for each query response
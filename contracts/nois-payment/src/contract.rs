@@ -61,6 +61,7 @@ pub fn execute(
             community_pool,
             relayer,
         } => execute_pay(deps, info, env, burn, community_pool, relayer),
+        ExecuteMsg::SetSink { sink } => execute_set_sink(deps, info, sink),
     }
 }
 
@@ -142,6 +143,27 @@ fn execute_pay(
         .add_attribute("sent_to_community_pool", community_pool.to_string()))
 }
 
+fn execute_set_sink(
+    deps: DepsMut,
+    info: MessageInfo,
+    sink: String,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Only the gateway can change the sink for all payment contracts consistently
+    ensure_eq!(info.sender, config.gateway, ContractError::Unauthorized);
+
+    config.sink = deps
+        .api
+        .addr_validate(&sink)
+        .map_err(|_| ContractError::InvalidAddress)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_sink")
+        .add_attribute("nois_sink", sink))
+}
+
 fn encode_msg_fund_community_pool(amount: &Coin, depositor: &Addr) -> Vec<u8> {
     // Coin: https://github.com/cosmos/cosmos-sdk/blob/v0.45.15/proto/cosmos/base/v1beta1/coin.proto#L14-L19
     // MsgFundCommunityPool: https://github.com/cosmos/cosmos-sdk/blob/v0.45.15/proto/cosmos/distribution/v1beta1/tx.proto#L69-L76
@@ -354,6 +376,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn set_sink_works() {
+        let mut deps = mock_dependencies();
+        let sink = deps.api.addr_make(NOIS_SINK);
+        let sink2 = deps.api.addr_make("sink2");
+        let gateway = deps.api.addr_make(NOIS_GATEWAY);
+        let malicious = deps.api.addr_make("a-malicious-person");
+        let msg = InstantiateMsg {
+            sink: sink.to_string(),
+        };
+        let info = message_info(&gateway, &[]);
+        let _response = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Only gateway can set sink
+        let msg = ExecuteMsg::SetSink {
+            sink: sink2.to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&malicious, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // Invalid address
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&gateway, &[]),
+            ExecuteMsg::SetSink {
+                sink: "not a valid address".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAddress));
+
+        let response =
+            execute(deps.as_mut(), mock_env(), message_info(&gateway, &[]), msg).unwrap();
+        assert_eq!(
+            first_attr(&response.attributes, "nois_sink").unwrap(),
+            sink2.as_str()
+        );
+        let config: ConfigResponse =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(
+            config,
+            ConfigResponse {
+                sink: sink2,
+                gateway
+            }
+        );
+    }
+
     #[test]
     fn encode_msg_fund_community_pool_works() {
         // https://www.mintscan.io/stargaze/txs/0F52332EA355E306363FE321C218A3873730A6C20748425D2888063B36DCFAFB
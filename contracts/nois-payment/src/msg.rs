@@ -17,6 +17,8 @@ pub enum ExecuteMsg {
         community_pool: Coin,
         relayer: (String, Coin),
     },
+    /// Sets the address of the Nois sink. Only the gateway can do this.
+    SetSink { sink: String },
 }
 
 #[cw_serde]
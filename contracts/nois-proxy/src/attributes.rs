@@ -7,8 +7,39 @@ pub const ATTR_ACTION: &str = "action";
 
 pub const EVENT_TYPE_CALLBACK: &str = "nois-callback";
 
+/// Like `nois-callback` but for failure callbacks
+pub const EVENT_TYPE_FAILURE_CALLBACK: &str = "nois-failure-callback";
+
 /// "true"/"false" if the callback was successful or not
 pub const ATTR_CALLBACK_SUCCESS: &str = "success";
 
 /// The error message (used in case "success" is "false")
 pub const ATTR_CALLBACK_ERROR_MSG: &str = "error_msg";
+
+/// "false" if a failed callback was not stored for retries because the dapp has
+/// too many stored failed callbacks (used in case "success" is "false")
+pub const ATTR_CALLBACK_STORED: &str = "stored";
+
+/// Emitted for every job of a request packet that failed, i.e. got an error
+/// acknowledgement or timed out. The randomness for those jobs will never arrive.
+pub const EVENT_TYPE_FAILED_JOB: &str = "nois-failed-job";
+
+/// The funds refunded to the sender of a failed job
+pub const ATTR_REFUND: &str = "refund";
+
+/// Emitted when a refund could not be sent. Contains "success" and "error_msg".
+pub const EVENT_TYPE_REFUND: &str = "nois-refund";
+
+/// The address of the dapp that requested the job
+pub const ATTR_SENDER: &str = "sender";
+
+/// The job ID the dapp used in the request
+pub const ATTR_JOB_ID: &str = "job_id";
+
+/// Emitted when the next beacon of a subscription could not be requested.
+/// Contains "sender", "job_id_prefix" and "error_msg". The subscription is stalled
+/// and can be cancelled to get the remaining funds back.
+pub const EVENT_TYPE_SUBSCRIPTION_STALLED: &str = "nois-subscription-stalled";
+
+/// The job ID prefix of a subscription
+pub const ATTR_JOB_ID_PREFIX: &str = "job_id_prefix";
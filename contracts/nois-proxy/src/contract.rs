@@ -1,36 +1,83 @@
-use anybuf::Anybuf;
+use anybuf::{Anybuf, Bufany};
+use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{
     attr, ensure_eq, from_json, to_json_binary, Addr, Attribute, BankMsg, Binary, Coin, CosmosMsg,
-    Deps, DepsMut, Empty, Env, Event, HexBinary, Ibc3ChannelOpenResponse, IbcBasicResponse,
-    IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcMsg, IbcPacketAckMsg,
-    IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, MessageInfo, Never, Order,
-    QueryResponse, Reply, Response, StdAck, StdResult, Storage, SubMsg, SubMsgResult, Timestamp,
-    Uint128, WasmMsg,
+    Decimal, Deps, DepsMut, Empty, Env, Event, HexBinary, Ibc3ChannelOpenResponse,
+    IbcBasicResponse, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcMsg,
+    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, MessageInfo,
+    Never, Order, QueryResponse, Reply, Response, StdAck, StdError, StdResult, Storage, SubMsg,
+    SubMsgResponse, SubMsgResult, Timestamp, Uint128, WasmMsg,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::{Bound, Map};
+use drand_common::time_of_round;
+use manager_transfer::{
+    accept_manager, cancel_manager_transfer, propose_manager, query_pending_manager,
+};
 use nois::{NoisCallback, ReceiverExecuteMsg};
 use nois_protocol::{
-    check_order, check_version, InPacket, InPacketAck, OutPacket, OutPacketAck,
+    check_order, check_version, is_v8_or_later, InPacket, InPacketAck, OutPacket, OutPacketAck,
+    PacketError, IBC_APP_VERSION_V7, MAX_BEACON_REQUESTS_PER_PACKET,
     REQUEST_BEACON_PACKET_LIFETIME, TRANSFER_PACKET_LIFETIME,
 };
 
 use crate::attributes::{
-    ATTR_ACTION, ATTR_CALLBACK_ERROR_MSG, ATTR_CALLBACK_SUCCESS, EVENT_TYPE_CALLBACK,
+    ATTR_ACTION, ATTR_CALLBACK_ERROR_MSG, ATTR_CALLBACK_STORED, ATTR_CALLBACK_SUCCESS, ATTR_JOB_ID,
+    ATTR_JOB_ID_PREFIX, ATTR_REFUND, ATTR_SENDER, EVENT_TYPE_CALLBACK, EVENT_TYPE_FAILED_JOB,
+    EVENT_TYPE_FAILURE_CALLBACK, EVENT_TYPE_REFUND, EVENT_TYPE_SUBSCRIPTION_STALLED,
 };
 use crate::error::ContractError;
-use crate::jobs::{validate_job_id, validate_payment};
+use crate::jobs::{
+    add_coins, apply_gas_surcharge, apply_volume_tiers, multiply_prices, refund_amount,
+    split_funds, sub_coins, take_funds_share, validate_job_id, validate_payment,
+    validate_refund_policy, validate_volume_tiers,
+};
 use crate::msg::{
-    AllowlistResponse, ConfigResponse, ExecuteMsg, GatewayChannelResponse, InstantiateMsg,
-    IsAllowlistedResponse, PriceResponse, PricesResponse, QueryMsg, RequestBeaconOrigin, SudoMsg,
+    AllDappStatsResponse, AllowlistResponse, BatchRandomnessRequest, CallbackGasLimitResponse,
+    CallbackGasSurchargeResponse, ConfigResponse, CreditsResponse, DappStatsResponse,
+    DenylistResponse, ExecuteMsg, FailedCallbacksResponse, FailureCallbacksEnabledResponse,
+    FailureReceiverExecuteMsg, GatewayChannelResponse, GatewayChannelStatus,
+    GatewayChannelsResponse, InstantiateMsg, IsAllowlistedResponse, IsDenylistedResponse,
+    JobIdRetentionResponse, JobResponse, JobsResponse, NoisFailure, PausedResponse, PriceResponse,
+    PricesResponse, QueryMsg, RequestBeaconOrigin, SubscriptionsResponse, SudoMsg,
+    VolumeTiersResponse,
 };
 use crate::publish_time::{calculate_after, AfterMode};
-use crate::state::{Config, OperationalMode, ALLOWLIST, ALLOWLIST_MARKER, CONFIG, GATEWAY_CHANNEL};
+use crate::state::{
+    Config, DappCallbackGasLimit, DappStats, FailedCallback, GatewayChannelInfo, Job, JobStatus,
+    OperationalMode, PendingJob, RefundPolicy, Subscription, UsedJobId, VolumeTier, ALLOWLIST,
+    ALLOWLIST_MARKER, CALLBACK_GAS_LIMITS, CALLBACK_GAS_SURCHARGE, CALLBACK_IN_FLIGHT, CONFIG,
+    CREDITS, DAPP_STATS, DENYLIST, FAILED_CALLBACKS, FAILURE_CALLBACKS, GATEWAY_CHANNEL,
+    GATEWAY_CHANNELS, GATEWAY_CHANNEL_VERSION, JOBS, JOB_ID_RETENTION, PAUSED, PENDING_JOBS,
+    PRICE_OVERRIDES, RESERVED_FUNDS, SUBSCRIPTIONS, SUBSCRIPTION_JOBS, USED_JOB_IDS,
+    USED_JOB_IDS_QUEUE, VOLUME_TIERS,
+};
 
 pub const REPLAY_ID_CALLBACK: u64 = 456;
+pub const REPLAY_ID_REQUEST_BEACON: u64 = 457;
+pub const REPLAY_ID_FAILURE_CALLBACK: u64 = 458;
+pub const REPLAY_ID_REFUND: u64 = 459;
 
 /// 10 years in seconds
 const TEN_YEARS_S: u64 = 10 * 3600 * 24 * 365;
 
+/// The max number of beacons of a single subscription
+const MAX_SUBSCRIPTION_COUNT: u32 = 1000;
+
+/// The time a gateway channel with pending packets can go without acknowledgements
+/// or timeouts before it is considered unhealthy (1 hour)
+const GATEWAY_CHANNEL_STALL_TIMEOUT: u64 = 3600;
+
+/// The max number of expired job IDs removed per request
+const MAX_PRUNED_JOB_IDS: usize = 10;
+
+/// The max number of failed callbacks stored per dapp. Further failed callbacks
+/// are not stored until the dapp retries or prunes stored ones.
+const MAX_FAILED_CALLBACKS_PER_DAPP: usize = 100;
+
+/// The max callback gas limit a dapp can request
+const MAX_REQUESTED_CALLBACK_GAS_LIMIT: u64 = 10_000_000;
+
 /// If not set otherwise, min_after is the genesis time of Nois mainnet
 const MIN_AFTER_FALLBACK: Timestamp = Timestamp::from_seconds(1680015600);
 const MAX_AFTER_FALLBACK: Timestamp = MIN_AFTER_FALLBACK.plus_seconds(TEN_YEARS_S);
@@ -72,6 +119,9 @@ pub fn instantiate(
         allowlist_enabled: Some(allowlist_enabled),
         min_after: Some(env.block.time),
         max_after: Some(env.block.time.plus_seconds(TEN_YEARS_S)),
+        min_after_offset: None,
+        max_after_offset: None,
+        refund_policy: Some(RefundPolicy::None {}),
     };
 
     CONFIG.save(deps.storage, &config)?;
@@ -108,6 +158,28 @@ pub fn migrate(deps: DepsMut, env: Env, _msg: Empty) -> StdResult<Response> {
         config.allowlist_enabled = Some(false);
     }
 
+    // Move the channel connected before multiple gateway channels were supported
+    if let Some(channel_id) = GATEWAY_CHANNEL.may_load(deps.storage)? {
+        if !GATEWAY_CHANNELS.has(deps.storage, &channel_id) {
+            // Channels established before the version was stored are nois-v7 channels
+            let version = GATEWAY_CHANNEL_VERSION
+                .may_load(deps.storage)?
+                .unwrap_or_else(|| IBC_APP_VERSION_V7.to_string());
+            GATEWAY_CHANNELS.save(
+                deps.storage,
+                &channel_id,
+                &GatewayChannelInfo {
+                    version,
+                    enabled: true,
+                    pending_packets: 0,
+                    waiting_since: None,
+                    last_ack: None,
+                    payment: config.payment.take(),
+                },
+            )?;
+        }
+    }
+    GATEWAY_CHANNEL_VERSION.remove(deps.storage);
     CONFIG.save(deps.storage, &config)?;
 
     set_contract_version(
@@ -131,7 +203,6 @@ pub fn execute(
             execute_get_next_randomness(deps, env, info, job_id)
         }
         ExecuteMsg::SetConfig {
-            manager,
             prices,
             payment,
             nois_beacon_price,
@@ -140,11 +211,11 @@ pub fn execute(
             allowlist_enabled,
             min_after,
             max_after,
+            refund_policy,
         } => execute_set_config(
             deps,
             info,
             env,
-            manager,
             prices,
             payment,
             nois_beacon_price,
@@ -153,10 +224,61 @@ pub fn execute(
             allowlist_enabled,
             min_after,
             max_after,
+            refund_policy,
         ),
         ExecuteMsg::GetRandomnessAfter { after, job_id } => {
             execute_get_randomness_after(deps, env, info, after, job_id)
         }
+        ExecuteMsg::GetRandomnessAtRound { round, job_id } => {
+            execute_get_randomness_at_round(deps, env, info, round, job_id)
+        }
+        ExecuteMsg::GetRandomnessBatch { requests } => {
+            execute_get_randomness_batch(deps, env, info, requests)
+        }
+        ExecuteMsg::Subscribe {
+            interval,
+            count,
+            job_id_prefix,
+        } => execute_subscribe(deps, env, info, interval, count, job_id_prefix),
+        ExecuteMsg::CancelSubscription { job_id_prefix } => {
+            execute_cancel_subscription(deps, info, job_id_prefix)
+        }
+        ExecuteMsg::TopUp { beneficiary } => execute_top_up(deps, info, beneficiary),
+        ExecuteMsg::SetPriceOverride { address, prices } => {
+            execute_set_price_override(deps, info, address, prices)
+        }
+        ExecuteMsg::SetVolumeTiers { tiers } => execute_set_volume_tiers(deps, info, tiers),
+        ExecuteMsg::SetCallbackGasLimit { address, gas_limit } => {
+            execute_set_callback_gas_limit(deps, info, address, gas_limit)
+        }
+        ExecuteMsg::SetCallbackGasSurcharge { surcharge } => {
+            execute_set_callback_gas_surcharge(deps, info, surcharge)
+        }
+        ExecuteMsg::RequestCallbackGasLimit { gas_limit } => {
+            execute_request_callback_gas_limit(deps, info, gas_limit)
+        }
+        ExecuteMsg::SetJobIdRetention { retention } => {
+            execute_set_job_id_retention(deps, info, retention)
+        }
+        ExecuteMsg::SetAfterOffsets {
+            min_after_offset,
+            max_after_offset,
+        } => execute_set_after_offsets(deps, info, min_after_offset, max_after_offset),
+        ExecuteMsg::UpdateGatewayChannel {
+            channel_id,
+            enabled,
+        } => execute_update_gateway_channel(deps, info, channel_id, enabled),
+        ExecuteMsg::SetPrimaryGatewayChannel { channel_id } => {
+            execute_set_primary_gateway_channel(deps, info, channel_id)
+        }
+        ExecuteMsg::Pause { reason } => execute_set_paused(deps, info, Some(reason)),
+        ExecuteMsg::Unpause {} => execute_set_paused(deps, info, None),
+        ExecuteMsg::WithdrawCredits {
+            address,
+            denom,
+            amount,
+            recipient,
+        } => execute_withdraw_credits(deps, info, address, denom, amount, recipient),
         ExecuteMsg::Withdraw {
             denom,
             amount,
@@ -165,6 +287,23 @@ pub fn execute(
         ExecuteMsg::UpdateAllowlist { add, remove } => {
             execute_update_allowlist(deps, env, info, add, remove)
         }
+        ExecuteMsg::UpdateDenylist { add, remove } => {
+            execute_update_denylist(deps, info, add, remove)
+        }
+        ExecuteMsg::ProposeManager { manager } => execute_propose_manager(deps, info, manager),
+        ExecuteMsg::AcceptManager {} => execute_accept_manager(deps, info),
+        ExecuteMsg::CancelManagerTransfer {} => execute_cancel_manager_transfer(deps, info),
+        ExecuteMsg::SetFailureCallbacks { enabled } => {
+            execute_set_failure_callbacks(deps, info, enabled)
+        }
+        ExecuteMsg::RetryCallback {
+            sender,
+            job_id,
+            gas_limit,
+        } => execute_retry_callback(deps, info, sender, job_id, gas_limit),
+        ExecuteMsg::PruneFailedCallbacks { sender, job_ids } => {
+            execute_prune_failed_callbacks(deps, info, sender, job_ids)
+        }
     }
 }
 
@@ -189,7 +328,7 @@ fn execute_get_next_randomness(
         info,
         "execute_get_next_randomness",
         config,
-        after,
+        RequestedBeacon::Next(after),
         job_id,
     )
 }
@@ -209,253 +348,1098 @@ fn execute_get_randomness_after(
         info,
         "execute_get_randomness_after",
         config,
-        after,
+        RequestedBeacon::After(after),
+        job_id,
+    )
+}
+
+fn execute_get_randomness_at_round(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    round: u64,
+    job_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    execute_get_randomness_impl(
+        deps,
+        env,
+        info,
+        "execute_get_randomness_at_round",
+        config,
+        RequestedBeacon::Round(round),
         job_id,
     )
 }
 
+/// The beacon requested by a single randomness request
+pub enum RequestedBeacon {
+    /// The next beacon. The time is derived from the block time by the proxy.
+    Next(Timestamp),
+    /// The first beacon published after this time
+    After(Timestamp),
+    /// A specific drand round
+    Round(u64),
+}
+
 pub fn execute_get_randomness_impl(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     action: &str,
     config: Config,
-    after: Timestamp,
+    requested: RequestedBeacon,
     job_id: String,
 ) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage)?;
     validate_job_id(&job_id)?;
-    validate_payment(&config.prices, &info.funds)?;
+    use_job_id(deps.storage, &env, &info.sender, &job_id)?;
+    let prices = effective_prices(deps.storage, &config, &info.sender)?;
+    let (funds, paid_from_credit) =
+        collect_payment(deps.storage, &prices, &info.sender, info.funds)?;
+    check_sender_allowed(deps.storage, &config, &info.sender)?;
+
+    let (channel_id, channel) = get_gateway_channel(deps.storage, &env)?;
+    let origin = to_json_binary(&RequestBeaconOrigin {
+        sender: info.sender.to_string(),
+        job_id: job_id.clone(),
+    })?;
+    let packet = match requested {
+        RequestedBeacon::Next(after) => {
+            check_after(&config, after)?;
+            InPacket::RequestBeacon { after, origin }
+        }
+        RequestedBeacon::After(after) => {
+            check_requested_after(&config, &env, after)?;
+            InPacket::RequestBeacon { after, origin }
+        }
+        RequestedBeacon::Round(round) => {
+            // Whether the round is in the future is checked by the gateway
+            if !is_v8_or_later(Some(&channel.version)) {
+                return Err(ContractError::RoundRequestsNotSupported);
+            }
+            // Rounds beyond the u32 range are more than 400 years after drand genesis.
+            // Saturating them avoids overflows and still exceeds any reasonable max_after.
+            let published = time_of_round(round.clamp(1, u32::MAX.into()));
+            check_requested_after(&config, &env, published)?;
+            InPacket::RequestBeaconAtRound { round, origin }
+        }
+    };
+    let job = PendingJob {
+        sender: info.sender,
+        job_id,
+        funds,
+        paid_from_credit,
+    };
 
-    // only let whitelisted senders get randomness
-    let allowlist_enabled = config.allowlist_enabled.unwrap_or(false);
-    if allowlist_enabled && !ALLOWLIST.has(deps.storage, &info.sender) {
-        return Err(ContractError::SenderNotAllowed);
-    }
+    let mut msgs: Vec<SubMsg> = Vec::with_capacity(2);
 
-    let min_after = config.min_after.unwrap_or(MIN_AFTER_FALLBACK);
-    if after < min_after {
-        return Err(ContractError::AfterTooLow { min_after, after });
+    // Add payment frist such that (at least in integration tests) the funds arrive in time
+    if let Some(msg) = create_beacon_payment_msg(&env, config, &channel, 1) {
+        msgs.push(SubMsg::new(msg));
     }
 
-    let max_after = config.max_after.unwrap_or(MAX_AFTER_FALLBACK);
-    if after > max_after {
-        return Err(ContractError::AfterTooHigh { max_after, after });
+    msgs.push(create_request_packet_msg(
+        deps.storage,
+        &env,
+        channel_id,
+        &packet,
+        &[job],
+    )?);
+
+    let res = Response::new()
+        .add_submessages(msgs)
+        .add_attribute(ATTR_ACTION, action);
+    Ok(res)
+}
+
+fn execute_get_randomness_batch(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    requests: Vec<BatchRandomnessRequest>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    check_not_paused(deps.storage)?;
+
+    if requests.is_empty() {
+        return Err(ContractError::EmptyBatch);
+    }
+    if requests.len() > MAX_BEACON_REQUESTS_PER_PACKET {
+        return Err(ContractError::BatchTooLarge {
+            max: MAX_BEACON_REQUESTS_PER_PACKET,
+        });
     }
+    let count = requests.len() as u32;
 
-    let packet = InPacket::RequestBeacon {
-        after,
-        origin: to_json_binary(&RequestBeaconOrigin {
-            sender: info.sender.into(),
+    // The price applies to every request of the batch
+    let prices = multiply_prices(
+        &effective_prices(deps.storage, &config, &info.sender)?,
+        count,
+    );
+    let (funds, paid_from_credit) =
+        collect_payment(deps.storage, &prices, &info.sender, info.funds)?;
+    check_sender_allowed(deps.storage, &config, &info.sender)?;
+
+    let mode = if config.test_mode {
+        AfterMode::Test
+    } else {
+        AfterMode::BlockTime(env.block.time)
+    };
+    let mut packet_requests = Vec::with_capacity(requests.len());
+    let mut jobs = Vec::with_capacity(requests.len());
+    let funds = split_funds(&funds, count);
+    for (BatchRandomnessRequest { job_id, after }, funds) in requests.into_iter().zip(funds) {
+        validate_job_id(&job_id)?;
+        use_job_id(deps.storage, &env, &info.sender, &job_id)?;
+        if jobs.iter().any(|job: &PendingJob| job.job_id == job_id) {
+            return Err(ContractError::JobIdInFlight);
+        }
+        let after = match after {
+            Some(after) => {
+                check_requested_after(&config, &env, after)?;
+                after
+            }
+            None => {
+                let after = calculate_after(deps.storage, mode)?;
+                check_after(&config, after)?;
+                after
+            }
+        };
+        let origin = to_json_binary(&RequestBeaconOrigin {
+            sender: info.sender.to_string(),
+            job_id: job_id.clone(),
+        })?;
+        packet_requests.push((after, origin));
+        jobs.push(PendingJob {
+            sender: info.sender.clone(),
             job_id,
-        })?,
+            funds,
+            paid_from_credit,
+        });
+    }
+
+    let (channel_id, channel) = get_gateway_channel(deps.storage, &env)?;
+    // Batch packets need a nois-v8 channel. On older channels every request
+    // is sent in its own packet.
+    let packets = if is_v8_or_later(Some(&channel.version)) {
+        vec![(
+            InPacket::RequestBeacons {
+                requests: packet_requests,
+            },
+            jobs,
+        )]
+    } else {
+        packet_requests
+            .into_iter()
+            .zip(jobs)
+            .map(|((after, origin), job)| (InPacket::RequestBeacon { after, origin }, vec![job]))
+            .collect()
     };
-    let channel_id = get_gateway_channel(deps.storage)?;
 
-    let mut msgs: Vec<CosmosMsg> = Vec::with_capacity(2);
+    let mut msgs: Vec<SubMsg> = Vec::with_capacity(packets.len() + 1);
 
     // Add payment frist such that (at least in integration tests) the funds arrive in time
-    if let OperationalMode::IbcPay { unois_denom } = config.mode {
-        if let Some(payment_contract) = config.payment {
-            if !config.nois_beacon_price.is_zero() {
-                msgs.push(
-                    IbcMsg::Transfer {
-                        channel_id: unois_denom.ics20_channel,
-                        to_address: payment_contract,
-                        amount: Coin {
-                            amount: config.nois_beacon_price,
-                            denom: unois_denom.denom,
-                        },
-                        timeout: env.block.time.plus_seconds(TRANSFER_PACKET_LIFETIME).into(),
-                        memo: None,
-                    }
-                    .into(),
-                );
-            }
-        }
+    if let Some(msg) = create_beacon_payment_msg(&env, config, &channel, count) {
+        msgs.push(SubMsg::new(msg));
     }
 
-    msgs.push(
-        IbcMsg::SendPacket {
-            channel_id,
-            data: to_json_binary(&packet)?,
-            timeout: env
-                .block
-                .time
-                .plus_seconds(REQUEST_BEACON_PACKET_LIFETIME)
-                .into(),
-        }
-        .into(),
-    );
+    for (packet, jobs) in packets {
+        msgs.push(create_request_packet_msg(
+            deps.storage,
+            &env,
+            channel_id.clone(),
+            &packet,
+            &jobs,
+        )?);
+    }
 
     let res = Response::new()
-        .add_messages(msgs)
-        .add_attribute(ATTR_ACTION, action);
+        .add_submessages(msgs)
+        .add_attribute(ATTR_ACTION, "execute_get_randomness_batch")
+        .add_attribute("count", count.to_string());
     Ok(res)
 }
 
-#[allow(clippy::too_many_arguments)]
-fn execute_set_config(
+fn execute_subscribe(
     deps: DepsMut,
-    info: MessageInfo,
     env: Env,
-    manager: Option<String>,
-    prices: Option<Vec<Coin>>,
-    payment: Option<String>,
-    nois_beacon_price: Option<Uint128>,
-    callback_gas_limit: Option<u64>,
-    mode: Option<OperationalMode>,
-    allowlist_enabled: Option<bool>,
-    min_after: Option<Timestamp>,
-    max_after: Option<Timestamp>,
+    info: MessageInfo,
+    interval: u64,
+    count: u32,
+    job_id_prefix: String,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
+    check_not_paused(deps.storage)?;
 
-    // if manager set, check the calling address is the authorised multisig otherwise error unauthorised
-    let required_sender = config.manager.as_ref().ok_or(ContractError::Unauthorized)?;
-    ensure_eq!(info.sender, required_sender, ContractError::Unauthorized);
+    if interval == 0 {
+        return Err(ContractError::ZeroSubscriptionInterval);
+    }
+    if count == 0 || count > MAX_SUBSCRIPTION_COUNT {
+        return Err(ContractError::InvalidSubscriptionCount {
+            max: MAX_SUBSCRIPTION_COUNT,
+        });
+    }
+    // The longest job ID of the subscription
+    validate_job_id(&format!("{job_id_prefix}{count}"))?;
+    let prices = multiply_prices(
+        &effective_prices(deps.storage, &config, &info.sender)?,
+        count,
+    );
+    let (funds, paid_from_credit) =
+        collect_payment(deps.storage, &prices, &info.sender, info.funds)?;
+    check_sender_allowed(deps.storage, &config, &info.sender)?;
+
+    // Saturating math such that huge intervals fail the max_after check instead of panicking
+    let first_after = saturating_plus_seconds(env.block.time, interval);
+    let last_after = saturating_plus_seconds(env.block.time, interval.saturating_mul(count.into()));
+    check_after(&config, first_after)?;
+    check_after(&config, last_after)?;
+
+    if SUBSCRIPTIONS.has(deps.storage, (&info.sender, &job_id_prefix)) {
+        return Err(ContractError::SubscriptionExists);
+    }
 
-    set_config_unchecked(
-        deps,
+    let mut subscription = Subscription {
+        sender: info.sender,
+        job_id_prefix,
+        interval,
+        count,
+        requested: 0,
+        next_after: first_after,
+        funds,
+        paid_from_credit,
+        pending_job: None,
+    };
+    let msgs = request_subscription_beacon(deps.storage, &env, config, &mut subscription)?;
+    SUBSCRIPTIONS.save(
+        deps.storage,
+        (&subscription.sender, &subscription.job_id_prefix),
+        &subscription,
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(msgs)
+        .add_attribute(ATTR_ACTION, "execute_subscribe")
+        .add_attribute("count", count.to_string()))
+}
+
+fn execute_cancel_subscription(
+    deps: DepsMut,
+    info: MessageInfo,
+    job_id_prefix: String,
+) -> Result<Response, ContractError> {
+    let mut subscription = SUBSCRIPTIONS
+        .may_load(deps.storage, (&info.sender, &job_id_prefix))?
+        .ok_or(ContractError::SubscriptionNotFound)?;
+
+    let refund: Vec<Coin> = std::mem::take(&mut subscription.funds)
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    if subscription.pending_job.is_some() {
+        // Keep the subscription until the request in flight is processed
+        subscription.count = subscription.requested;
+        SUBSCRIPTIONS.save(deps.storage, (&info.sender, &job_id_prefix), &subscription)?;
+    } else {
+        SUBSCRIPTIONS.remove(deps.storage, (&info.sender, &job_id_prefix));
+    }
+
+    update_dapp_stats(deps.storage, &info.sender, |stats| {
+        sub_coins(&mut stats.fees, &refund)
+    })?;
+    release_funds(deps.storage, &refund)?;
+    let mut res = Response::new().add_attribute(ATTR_ACTION, "execute_cancel_subscription");
+    if subscription.paid_from_credit {
+        add_credit(deps.storage, &info.sender, &refund)?;
+    } else if !refund.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: refund,
+        });
+    }
+    Ok(res)
+}
+
+/// Requests the next beacon of the subscription and pays it from the prepaid funds.
+///
+/// All checks happen before storage is written such that nothing changes in case of
+/// an error, even if the error is not propagated.
+fn request_subscription_beacon(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: Config,
+    subscription: &mut Subscription,
+) -> Result<Vec<SubMsg>, ContractError> {
+    let job_id = format!(
+        "{}{}",
+        subscription.job_id_prefix,
+        subscription.requested + 1
+    );
+    let (channel_id, channel) = get_gateway_channel(storage, env)?;
+    use_job_id(storage, env, &subscription.sender, &job_id)?;
+
+    let funds = take_funds_share(
+        &mut subscription.funds,
+        subscription.count - subscription.requested,
+    );
+    subscription.requested += 1;
+
+    let packet = InPacket::RequestBeacon {
+        after: subscription.next_after,
+        origin: to_json_binary(&RequestBeaconOrigin {
+            sender: subscription.sender.to_string(),
+            job_id: job_id.clone(),
+        })?,
+    };
+    let job = PendingJob {
+        sender: subscription.sender.clone(),
+        job_id: job_id.clone(),
+        funds,
+        paid_from_credit: subscription.paid_from_credit,
+    };
+    subscription.next_after =
+        saturating_plus_seconds(subscription.next_after, subscription.interval);
+    SUBSCRIPTION_JOBS.save(
+        storage,
+        (&subscription.sender, &job_id),
+        &subscription.job_id_prefix,
+    )?;
+    subscription.pending_job = Some(job_id);
+
+    let mut msgs: Vec<SubMsg> = Vec::with_capacity(2);
+    if let Some(msg) = create_beacon_payment_msg(env, config, &channel, 1) {
+        msgs.push(SubMsg::new(msg));
+    }
+    msgs.push(create_request_packet_msg(
+        storage,
         env,
-        "execute_set_config",
-        manager,
-        prices,
-        payment,
-        nois_beacon_price,
-        callback_gas_limit,
-        mode,
-        allowlist_enabled,
-        min_after,
-        max_after,
-    )
+        channel_id,
+        &packet,
+        &[job],
+    )?);
+    Ok(msgs)
 }
 
-fn execute_withdraw(
+/// Marks the subscription request in flight as processed, i.e. delivered or failed.
+/// Completed subscriptions are removed.
+///
+/// Returns the subscription if it has beacons left to request. Returns None for jobs
+/// that are not part of a subscription.
+fn end_subscription_job(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+    job_id: &str,
+) -> StdResult<Option<Subscription>> {
+    let Some(job_id_prefix) = SUBSCRIPTION_JOBS.may_load(storage, (sender, job_id))? else {
+        return Ok(None);
+    };
+    SUBSCRIPTION_JOBS.remove(storage, (sender, job_id));
+
+    let key = (sender, job_id_prefix.as_str());
+    let Some(mut subscription) = SUBSCRIPTIONS.may_load(storage, key)? else {
+        return Ok(None);
+    };
+    if subscription.pending_job.as_deref() != Some(job_id) {
+        return Ok(None);
+    }
+    subscription.pending_job = None;
+
+    if subscription.requested >= subscription.count {
+        SUBSCRIPTIONS.remove(storage, key);
+        Ok(None)
+    } else {
+        SUBSCRIPTIONS.save(storage, key, &subscription)?;
+        Ok(Some(subscription))
+    }
+}
+
+/// The prices for the given dapp. This is the price override of the dapp if set.
+/// Otherwise the config prices with the discount of the dapp's volume tier.
+/// Both include the surcharge for a callback gas limit requested by the dapp.
+fn effective_prices(
+    storage: &dyn Storage,
+    config: &Config,
+    address: &Addr,
+) -> StdResult<Vec<Coin>> {
+    let prices = match PRICE_OVERRIDES.may_load(storage, address)? {
+        Some(prices) => prices,
+        None => {
+            let tiers = VOLUME_TIERS.may_load(storage)?.unwrap_or_default();
+            let requests = DAPP_STATS
+                .may_load(storage, address)?
+                .unwrap_or_default()
+                .requests;
+            apply_volume_tiers(config.prices.clone(), &tiers, requests)
+        }
+    };
+    match CALLBACK_GAS_LIMITS.may_load(storage, address)? {
+        Some(DappCallbackGasLimit {
+            gas_limit,
+            surcharge: true,
+        }) => {
+            let surcharge = CALLBACK_GAS_SURCHARGE
+                .may_load(storage)?
+                .unwrap_or_default();
+            Ok(apply_gas_surcharge(
+                prices,
+                surcharge,
+                gas_limit,
+                config.callback_gas_limit,
+            ))
+        }
+        _ => Ok(prices),
+    }
+}
+
+/// The callback gas limit for the given dapp. A gas limit requested by the dapp
+/// never falls below the configured callback gas limit.
+fn dapp_callback_gas_limit(
+    storage: &dyn Storage,
+    config: &Config,
+    address: &Addr,
+) -> StdResult<u64> {
+    let gas_limit = match CALLBACK_GAS_LIMITS.may_load(storage, address)? {
+        Some(DappCallbackGasLimit {
+            gas_limit,
+            surcharge: false,
+        }) => gas_limit,
+        Some(DappCallbackGasLimit {
+            gas_limit,
+            surcharge: true,
+        }) => gas_limit.max(config.callback_gas_limit),
+        None => config.callback_gas_limit,
+    };
+    Ok(gas_limit)
+}
+
+/// Pays the price from the sender's credit if it covers the price in one of the
+/// supported denoms. Otherwise the attached funds must cover the price.
+/// Funds attached to requests paid from credit are rejected such that dapps do not pay twice.
+///
+/// Returns the funds that pay for the request and whether they were paid from credit.
+/// The charged price is added to the fees in the sender's stats. The funds stay reserved
+/// until the request cannot be refunded anymore.
+fn collect_payment(
+    storage: &mut dyn Storage,
+    prices: &[Coin],
+    sender: &Addr,
+    funds: Vec<Coin>,
+) -> Result<(Vec<Coin>, bool), ContractError> {
+    let mut paid_from_credit = None;
+    for price in prices {
+        let key = (sender, price.denom.as_str());
+        let credit = CREDITS.may_load(storage, key)?.unwrap_or_default();
+        if !price.amount.is_zero() && credit >= price.amount {
+            if !funds.is_empty() {
+                return Err(ContractError::FundsWithCredit);
+            }
+            save_credit(storage, key, credit - price.amount)?;
+            paid_from_credit = Some(price.clone());
+            break;
+        }
+    }
+    let (payment, charged, paid_from_credit) = match paid_from_credit {
+        Some(price) => (vec![price.clone()], price, true),
+        None => {
+            let charged = validate_payment(prices, &funds)?;
+            reserve_funds(storage, &funds)?;
+            (funds, charged, false)
+        }
+    };
+    update_dapp_stats(storage, sender, |stats| {
+        add_coins(&mut stats.fees, &[charged])
+    })?;
+    Ok((payment, paid_from_credit))
+}
+
+fn update_dapp_stats(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    update: impl FnOnce(&mut DappStats),
+) -> StdResult<()> {
+    let mut stats = DAPP_STATS.may_load(storage, address)?.unwrap_or_default();
+    update(&mut stats);
+    DAPP_STATS.save(storage, address, &stats)
+}
+
+fn add_credit(storage: &mut dyn Storage, address: &Addr, amount: &[Coin]) -> StdResult<()> {
+    for coin in amount {
+        let key = (address, coin.denom.as_str());
+        let credit = CREDITS.may_load(storage, key)?.unwrap_or_default();
+        save_credit(storage, key, credit + coin.amount)?;
+    }
+    reserve_funds(storage, amount)
+}
+
+/// Adds funds held on behalf of dapps to the reserved funds
+fn reserve_funds(storage: &mut dyn Storage, funds: &[Coin]) -> StdResult<()> {
+    for coin in funds.iter().filter(|coin| !coin.amount.is_zero()) {
+        let reserved = RESERVED_FUNDS
+            .may_load(storage, &coin.denom)?
+            .unwrap_or_default();
+        RESERVED_FUNDS.save(storage, &coin.denom, &(reserved + coin.amount))?;
+    }
+    Ok(())
+}
+
+/// Removes funds that are not held on behalf of dapps anymore from the reserved funds
+fn release_funds(storage: &mut dyn Storage, funds: &[Coin]) -> StdResult<()> {
+    for coin in funds {
+        let reserved = RESERVED_FUNDS
+            .may_load(storage, &coin.denom)?
+            .unwrap_or_default()
+            .saturating_sub(coin.amount);
+        if reserved.is_zero() {
+            RESERVED_FUNDS.remove(storage, &coin.denom);
+        } else {
+            RESERVED_FUNDS.save(storage, &coin.denom, &reserved)?;
+        }
+    }
+    Ok(())
+}
+
+fn save_credit(storage: &mut dyn Storage, key: (&Addr, &str), credit: Uint128) -> StdResult<()> {
+    if credit.is_zero() {
+        CREDITS.remove(storage, key);
+        Ok(())
+    } else {
+        CREDITS.save(storage, key, &credit)
+    }
+}
+
+fn execute_set_price_override(
     deps: DepsMut,
-    env: Env,
     info: MessageInfo,
-    denom: String,
-    amount: Option<Uint128>,
     address: String,
+    prices: Option<Vec<Coin>>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
-    // if manager set, check the calling address is the authorised multisig otherwise error unauthorised
-    let required_sender = config.manager.as_ref().ok_or(ContractError::Unauthorized)?;
-    ensure_eq!(info.sender, required_sender, ContractError::Unauthorized);
+    ensure_manager(&config, &info)?;
 
-    withdraw_unchecked(deps, env, "execute_withdraw", denom, amount, address)
+    let address = deps.api.addr_validate(&address)?;
+    match prices {
+        Some(prices) => PRICE_OVERRIDES.save(deps.storage, &address, &prices)?,
+        None => PRICE_OVERRIDES.remove(deps.storage, &address),
+    }
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_set_price_override")
+        .add_attribute("address", address))
 }
 
-fn execute_update_allowlist(
+fn execute_set_volume_tiers(
     deps: DepsMut,
-    _env: Env,
     info: MessageInfo,
-    add_addresses: Vec<String>,
-    remove_addresses: Vec<String>,
+    tiers: Vec<VolumeTier>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
-    // if manager set, check the calling address is the authorised multisig otherwise error unauthorised
-    let required_sender = config.manager.as_ref().ok_or(ContractError::Unauthorized)?;
-    ensure_eq!(info.sender, required_sender, ContractError::Unauthorized);
+    ensure_manager(&config, &info)?;
 
-    update_allowlist_unchecked(deps, add_addresses, remove_addresses)?;
-    Ok(Response::new().add_attribute(ATTR_ACTION, "execute_update_allowlist"))
+    validate_volume_tiers(&tiers)?;
+    VOLUME_TIERS.save(deps.storage, &tiers)?;
+    Ok(Response::new().add_attribute(ATTR_ACTION, "execute_set_volume_tiers"))
 }
 
-/// Adds and remove entries from the allow list.
-fn update_allowlist_unchecked(
+fn execute_set_callback_gas_limit(
     deps: DepsMut,
-    add_addresses: Vec<String>,
-    remove_addresses: Vec<String>,
-) -> Result<(), ContractError> {
-    for addr in add_addresses {
-        let addr = deps.api.addr_validate(addr.as_str())?;
-        ALLOWLIST.save(deps.storage, &addr, &ALLOWLIST_MARKER)?;
-    }
+    info: MessageInfo,
+    address: String,
+    gas_limit: Option<u64>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
 
-    for addr in remove_addresses {
-        let addr = deps.api.addr_validate(addr.as_str())?;
-        ALLOWLIST.remove(deps.storage, &addr);
+    ensure_manager(&config, &info)?;
+
+    let address = deps.api.addr_validate(&address)?;
+    match gas_limit {
+        Some(gas_limit) => CALLBACK_GAS_LIMITS.save(
+            deps.storage,
+            &address,
+            &DappCallbackGasLimit {
+                gas_limit,
+                surcharge: false,
+            },
+        )?,
+        None => CALLBACK_GAS_LIMITS.remove(deps.storage, &address),
     }
-    Ok(())
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_set_callback_gas_limit")
+        .add_attribute("address", address))
 }
 
-#[cfg_attr(not(feature = "library"), ::cosmwasm_std::entry_point)]
-#[allow(unused)]
-pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
-    match msg {
-        #[cfg(feature = "governance_owned")]
-        SudoMsg::Withdraw {
-            denom,
-            amount,
-            address,
-        } => sudo_withdraw(deps, env, denom, amount, address),
-        #[cfg(feature = "governance_owned")]
-        SudoMsg::WithdrawToCommunityPool { denom, amount } => {
-            sudo_withdraw_to_community_pool(deps, env, denom, amount)
+fn execute_set_callback_gas_surcharge(
+    deps: DepsMut,
+    info: MessageInfo,
+    surcharge: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    match surcharge {
+        Some(surcharge) => CALLBACK_GAS_SURCHARGE.save(deps.storage, &surcharge)?,
+        None => CALLBACK_GAS_SURCHARGE.remove(deps.storage),
+    }
+    Ok(Response::new().add_attribute(ATTR_ACTION, "execute_set_callback_gas_surcharge"))
+}
+
+fn execute_request_callback_gas_limit(
+    deps: DepsMut,
+    info: MessageInfo,
+    gas_limit: Option<u64>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    match gas_limit {
+        Some(gas_limit) => {
+            if !CALLBACK_GAS_SURCHARGE.exists(deps.storage) {
+                return Err(ContractError::CallbackGasSurchargeNotSet);
+            }
+            if gas_limit < config.callback_gas_limit {
+                return Err(ContractError::CallbackGasLimitTooLow {
+                    min: config.callback_gas_limit,
+                });
+            }
+            if gas_limit > MAX_REQUESTED_CALLBACK_GAS_LIMIT {
+                return Err(ContractError::CallbackGasLimitTooHigh {
+                    max: MAX_REQUESTED_CALLBACK_GAS_LIMIT,
+                });
+            }
+            CALLBACK_GAS_LIMITS.save(
+                deps.storage,
+                &info.sender,
+                &DappCallbackGasLimit {
+                    gas_limit,
+                    surcharge: true,
+                },
+            )?;
         }
-        #[cfg(feature = "governance_owned")]
-        SudoMsg::SetConfig {
-            manager,
-            prices,
-            payment,
-            nois_beacon_price,
-            callback_gas_limit,
-            mode,
-            allowlist_enabled,
-            min_after,
-            max_after,
-        } => sudo_set_config(
-            deps,
-            env,
-            manager,
-            prices,
-            payment,
-            nois_beacon_price,
-            callback_gas_limit,
-            mode,
-            allowlist_enabled,
-            min_after,
-            max_after,
-        ),
+        None => CALLBACK_GAS_LIMITS.remove(deps.storage, &info.sender),
     }
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_request_callback_gas_limit")
+        .add_attribute("address", info.sender))
 }
 
-#[cfg(feature = "governance_owned")]
-fn sudo_withdraw(
+fn execute_set_job_id_retention(
     deps: DepsMut,
-    env: Env,
-    denom: String,
-    amount: Option<Uint128>,
-    address: String,
+    info: MessageInfo,
+    retention: Option<u64>,
 ) -> Result<Response, ContractError> {
-    withdraw_unchecked(deps, env, "sudo_withdraw", denom, amount, address)
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    match retention {
+        Some(retention) => JOB_ID_RETENTION.save(deps.storage, &retention)?,
+        None => JOB_ID_RETENTION.remove(deps.storage),
+    }
+    Ok(Response::new().add_attribute(ATTR_ACTION, "execute_set_job_id_retention"))
 }
 
-#[cfg(feature = "governance_owned")]
-fn sudo_withdraw_to_community_pool(
+fn execute_set_after_offsets(
     deps: DepsMut,
-    env: Env,
+    info: MessageInfo,
+    min_after_offset: Option<u64>,
+    max_after_offset: Option<u64>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    set_after_offsets_unchecked(
+        deps,
+        "execute_set_after_offsets",
+        min_after_offset,
+        max_after_offset,
+    )
+}
+
+fn set_after_offsets_unchecked(
+    deps: DepsMut,
+    action: &str,
+    min_after_offset: Option<u64>,
+    max_after_offset: Option<u64>,
+) -> Result<Response, ContractError> {
+    if let (Some(min), Some(max)) = (min_after_offset, max_after_offset) {
+        if min > max {
+            return Err(ContractError::InvalidAfterOffsets);
+        }
+    }
+    let mut config = CONFIG.load(deps.storage)?;
+    config.min_after_offset = min_after_offset;
+    config.max_after_offset = max_after_offset;
+    CONFIG.save(deps.storage, &config)?;
+    Ok(Response::new().add_attribute(ATTR_ACTION, action))
+}
+
+fn execute_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    reason: Option<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    let action = match reason {
+        Some(_) => "execute_pause",
+        None => "execute_unpause",
+    };
+    set_paused_unchecked(deps, action, reason)
+}
+
+/// Pauses the proxy if a reason is given and unpauses it otherwise
+fn set_paused_unchecked(
+    deps: DepsMut,
+    action: &str,
+    reason: Option<String>,
+) -> Result<Response, ContractError> {
+    match &reason {
+        Some(reason) => PAUSED.save(deps.storage, reason)?,
+        None => PAUSED.remove(deps.storage),
+    }
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, action)
+        .add_attribute("paused", reason.is_some().to_string()))
+}
+
+fn execute_update_gateway_channel(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    let mut channel = GATEWAY_CHANNELS
+        .may_load(deps.storage, &channel_id)?
+        .ok_or_else(|| ContractError::UnknownGatewayChannel {
+            channel_id: channel_id.clone(),
+        })?;
+    if !enabled && GATEWAY_CHANNEL.may_load(deps.storage)?.as_ref() == Some(&channel_id) {
+        return Err(ContractError::CannotDisablePrimaryChannel);
+    }
+    channel.enabled = enabled;
+    GATEWAY_CHANNELS.save(deps.storage, &channel_id, &channel)?;
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_update_gateway_channel")
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+fn execute_set_primary_gateway_channel(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    let channel = GATEWAY_CHANNELS
+        .may_load(deps.storage, &channel_id)?
+        .ok_or_else(|| ContractError::UnknownGatewayChannel {
+            channel_id: channel_id.clone(),
+        })?;
+    if !channel.enabled {
+        return Err(ContractError::GatewayChannelDisabled);
+    }
+    GATEWAY_CHANNEL.save(deps.storage, &channel_id)?;
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_set_primary_gateway_channel")
+        .add_attribute("channel_id", channel_id))
+}
+
+fn execute_top_up(
+    deps: DepsMut,
+    info: MessageInfo,
+    beneficiary: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let beneficiary = deps.api.addr_validate(&beneficiary)?;
+
+    if info.funds.is_empty() {
+        return Err(ContractError::InvalidTopUp);
+    }
+    let prices = effective_prices(deps.storage, &config, &beneficiary)?;
+    for coin in &info.funds {
+        if !prices.iter().any(|price| price.denom == coin.denom) {
+            return Err(ContractError::InvalidTopUp);
+        }
+    }
+    add_credit(deps.storage, &beneficiary, &info.funds)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_top_up")
+        .add_attribute("beneficiary", beneficiary))
+}
+
+fn execute_withdraw_credits(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
     denom: String,
     amount: Option<Uint128>,
+    recipient: String,
 ) -> Result<Response, ContractError> {
-    withdraw_to_community_pool_unchecked(
-        deps,
-        env,
-        "sudo_withdraw_to_community_pool",
-        denom,
-        amount,
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    let address = deps.api.addr_validate(&address)?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let key = (&address, denom.as_str());
+    let credit = CREDITS.may_load(deps.storage, key)?.unwrap_or_default();
+    let amount = amount.unwrap_or(credit);
+    if amount.is_zero() || amount > credit {
+        return Err(ContractError::InsufficientCredit);
+    }
+    save_credit(deps.storage, key, credit - amount)?;
+    release_funds(
+        deps.storage,
+        &[Coin {
+            denom: denom.clone(),
+            amount,
+        }],
+    )?;
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin { denom, amount }],
+        })
+        .add_attribute(ATTR_ACTION, "execute_withdraw_credits")
+        .add_attribute("address", address))
+}
+
+/// Checks that the sender is the manager. Fails if no manager is set.
+fn ensure_manager(config: &Config, info: &MessageInfo) -> Result<(), ContractError> {
+    let manager = config.manager.as_ref().ok_or(ContractError::Unauthorized)?;
+    ensure_eq!(info.sender, manager, ContractError::Unauthorized);
+    Ok(())
+}
+
+/// New requests are rejected while the proxy is paused
+fn check_not_paused(storage: &dyn Storage) -> Result<(), ContractError> {
+    match PAUSED.may_load(storage)? {
+        Some(reason) => Err(ContractError::Paused { reason }),
+        None => Ok(()),
+    }
+}
+
+/// Only let allowlisted senders get randomness if the allowlist is enabled.
+/// Denylisted senders never get randomness.
+fn check_sender_allowed(
+    storage: &dyn Storage,
+    config: &Config,
+    sender: &Addr,
+) -> Result<(), ContractError> {
+    if DENYLIST.has(storage, sender) {
+        return Err(ContractError::SenderDenylisted);
+    }
+    let allowlist_enabled = config.allowlist_enabled.unwrap_or(false);
+    if allowlist_enabled && !ALLOWLIST.has(storage, sender) {
+        return Err(ContractError::SenderNotAllowed);
+    }
+    Ok(())
+}
+
+/// Remembers the job ID if job ID retention is enabled. Errors if the sender used
+/// the job ID within the retention time. Expired entries are removed on the way.
+///
+/// Errors in any case if a job of the sender with this ID is in flight since
+/// its entry in `JOBS` would be overwritten.
+fn use_job_id(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    job_id: &str,
+) -> Result<(), ContractError> {
+    if JOBS.has(storage, (sender, job_id)) {
+        return Err(ContractError::JobIdInFlight);
+    }
+    let Some(retention) = JOB_ID_RETENTION.may_load(storage)? else {
+        return Ok(());
+    };
+    let now = env.block.time;
+
+    for _ in 0..MAX_PRUNED_JOB_IDS {
+        match USED_JOB_IDS_QUEUE.front(storage)? {
+            Some(entry) if entry.used.plus_seconds(retention) <= now => {
+                USED_JOB_IDS_QUEUE.pop_front(storage)?;
+                // The job ID might have been used again after the entry expired
+                let key = (&entry.sender, entry.job_id.as_str());
+                if USED_JOB_IDS.may_load(storage, key)? == Some(entry.used) {
+                    USED_JOB_IDS.remove(storage, key);
+                }
+            }
+            _ => break,
+        }
+    }
+
+    if let Some(used) = USED_JOB_IDS.may_load(storage, (sender, job_id))? {
+        if used.plus_seconds(retention) > now {
+            return Err(ContractError::JobIdAlreadyUsed);
+        }
+    }
+    USED_JOB_IDS.save(storage, (sender, job_id), &now)?;
+    USED_JOB_IDS_QUEUE.push_back(
+        storage,
+        &UsedJobId {
+            sender: sender.clone(),
+            job_id: job_id.to_string(),
+            used: now,
+        },
+    )?;
+    Ok(())
+}
+
+/// Adds seconds to a timestamp. Saturates at the max timestamp instead of panicking on overflow.
+fn saturating_plus_seconds(time: Timestamp, seconds: u64) -> Timestamp {
+    Timestamp::from_nanos(
+        time.nanos()
+            .saturating_add(seconds.saturating_mul(1_000_000_000)),
     )
 }
 
-#[cfg(feature = "governance_owned")]
+/// Checks `after` against the absolute bounds
+fn check_after(config: &Config, after: Timestamp) -> Result<(), ContractError> {
+    let min_after = config.min_after.unwrap_or(MIN_AFTER_FALLBACK);
+    if after < min_after {
+        return Err(ContractError::AfterTooLow { min_after, after });
+    }
+    let max_after = config.max_after.unwrap_or(MAX_AFTER_FALLBACK);
+    if after > max_after {
+        return Err(ContractError::AfterTooHigh { max_after, after });
+    }
+    Ok(())
+}
+
+/// Checks `after` against the absolute bounds and the bounds relative to the block time.
+/// The relative bounds only apply to `after` values provided by the caller.
+fn check_requested_after(
+    config: &Config,
+    env: &Env,
+    after: Timestamp,
+) -> Result<(), ContractError> {
+    check_after(config, after)?;
+    if let Some(offset) = config.min_after_offset {
+        let min_after = saturating_plus_seconds(env.block.time, offset);
+        if after < min_after {
+            return Err(ContractError::AfterTooLow { min_after, after });
+        }
+    }
+    if let Some(offset) = config.max_after_offset {
+        let max_after = saturating_plus_seconds(env.block.time, offset);
+        if after > max_after {
+            return Err(ContractError::AfterTooHigh { max_after, after });
+        }
+    }
+    Ok(())
+}
+
+/// In IBC pay mode, creates the transfer that pays the Nois chain for `count` beacons.
+/// The payment goes to the payment contract of the gateway channel the requests are sent through.
+fn create_beacon_payment_msg(
+    env: &Env,
+    config: Config,
+    channel: &GatewayChannelInfo,
+    count: u32,
+) -> Option<IbcMsg> {
+    let OperationalMode::IbcPay { unois_denom } = config.mode else {
+        return None;
+    };
+    let payment_contract = channel.payment.clone()?;
+    if config.nois_beacon_price.is_zero() {
+        return None;
+    }
+    Some(IbcMsg::Transfer {
+        channel_id: unois_denom.ics20_channel,
+        to_address: payment_contract,
+        amount: Coin {
+            amount: config.nois_beacon_price * Uint128::from(count),
+            denom: unois_denom.denom,
+        },
+        timeout: env.block.time.plus_seconds(TRANSFER_PACKET_LIFETIME).into(),
+        memo: None,
+    })
+}
+
+/// The payload of request packet submessages
+#[cw_serde]
+struct RequestPacketPayload {
+    channel_id: String,
+    jobs: Vec<PendingJob>,
+}
+
+/// Sends a request packet as a submessage. The jobs are attached as payload such that
+/// the reply can store them under the sequence of the packet.
+fn create_request_packet_msg(
+    storage: &mut dyn Storage,
+    env: &Env,
+    channel_id: String,
+    packet: &InPacket,
+    jobs: &[PendingJob],
+) -> StdResult<SubMsg> {
+    for job in jobs {
+        update_dapp_stats(storage, &job.sender, |stats| stats.requests += 1)?;
+    }
+
+    let requested: Vec<(Option<Timestamp>, Option<u64>)> = match packet {
+        InPacket::RequestBeacon { after, .. } => vec![(Some(*after), None)],
+        InPacket::RequestBeacons { requests } => requests
+            .iter()
+            .map(|(after, _)| (Some(*after), None))
+            .collect(),
+        InPacket::RequestBeaconAtRound { round, .. } => vec![(None, Some(*round))],
+        _ => vec![],
+    };
+    for (job, (after, round)) in jobs.iter().zip(requested) {
+        let entry = Job {
+            sender: job.sender.clone(),
+            job_id: job.job_id.clone(),
+            after,
+            round,
+            height: env.block.height,
+            status: JobStatus::Sent {},
+            source_id: None,
+        };
+        JOBS.save(storage, (&job.sender, &job.job_id), &entry)?;
+    }
+    let mut channel = GATEWAY_CHANNELS.load(storage, &channel_id)?;
+    if channel.pending_packets == 0 {
+        channel.waiting_since = Some(env.block.time);
+    }
+    channel.pending_packets += 1;
+    GATEWAY_CHANNELS.save(storage, &channel_id, &channel)?;
+
+    let payload = to_json_binary(&RequestPacketPayload {
+        channel_id: channel_id.clone(),
+        jobs: jobs.to_vec(),
+    })?;
+    let msg = IbcMsg::SendPacket {
+        channel_id,
+        data: to_json_binary(packet)?,
+        timeout: env
+            .block
+            .time
+            .plus_seconds(REQUEST_BEACON_PACKET_LIFETIME)
+            .into(),
+    };
+    Ok(SubMsg::reply_on_success(msg, REPLAY_ID_REQUEST_BEACON).with_payload(payload))
+}
+
 #[allow(clippy::too_many_arguments)]
-fn sudo_set_config(
+fn execute_set_config(
     deps: DepsMut,
+    info: MessageInfo,
     env: Env,
-    manager: Option<String>,
     prices: Option<Vec<Coin>>,
     payment: Option<String>,
     nois_beacon_price: Option<Uint128>,
@@ -464,12 +1448,17 @@ fn sudo_set_config(
     allowlist_enabled: Option<bool>,
     min_after: Option<Timestamp>,
     max_after: Option<Timestamp>,
+    refund_policy: Option<RefundPolicy>,
 ) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
     set_config_unchecked(
         deps,
         env,
-        "sudo_set_config",
-        manager,
+        "execute_set_config",
+        None, // Use ProposeManager/AcceptManager for this
         prices,
         payment,
         nois_beacon_price,
@@ -478,48 +1467,404 @@ fn sudo_set_config(
         allowlist_enabled,
         min_after,
         max_after,
+        refund_policy,
     )
 }
 
-fn withdraw_unchecked(
+fn execute_withdraw(
     deps: DepsMut,
     env: Env,
-    action: &str,
+    info: MessageInfo,
     denom: String,
     amount: Option<Uint128>,
     address: String,
 ) -> Result<Response, ContractError> {
-    let address = deps.api.addr_validate(&address)?;
-    let amount: Coin = match amount {
-        Some(amount) => Coin { denom, amount },
-        None => deps.querier.query_balance(env.contract.address, denom)?,
-    };
+    let config = CONFIG.load(deps.storage)?;
 
-    let msg = BankMsg::Send {
-        to_address: address.into(),
-        amount: vec![amount.clone()],
-    };
-    let res = Response::new()
-        .add_message(msg)
-        .add_attribute(ATTR_ACTION, action)
-        .add_attribute("amount", amount.to_string());
-    Ok(res)
+    ensure_manager(&config, &info)?;
+
+    withdraw_unchecked(deps, env, "execute_withdraw", denom, amount, address)
 }
 
-#[allow(unused)]
-fn withdraw_to_community_pool_unchecked(
+fn execute_update_allowlist(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    add_addresses: Vec<String>,
+    remove_addresses: Vec<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    update_allowlist_unchecked(deps, add_addresses, remove_addresses)?;
+    Ok(Response::new().add_attribute(ATTR_ACTION, "execute_update_allowlist"))
+}
+
+fn execute_update_denylist(
+    deps: DepsMut,
+    info: MessageInfo,
+    add_addresses: Vec<String>,
+    remove_addresses: Vec<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    for addr in add_addresses {
+        let addr = deps.api.addr_validate(addr.as_str())?;
+        DENYLIST.save(deps.storage, &addr, &ALLOWLIST_MARKER)?;
+    }
+
+    for addr in remove_addresses {
+        let addr = deps.api.addr_validate(addr.as_str())?;
+        DENYLIST.remove(deps.storage, &addr);
+    }
+    Ok(Response::new().add_attribute(ATTR_ACTION, "execute_update_denylist"))
+}
+
+fn execute_set_failure_callbacks(
+    deps: DepsMut,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    if enabled {
+        FAILURE_CALLBACKS.save(deps.storage, &info.sender, &true)?;
+    } else {
+        FAILURE_CALLBACKS.remove(deps.storage, &info.sender);
+    }
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_set_failure_callbacks")
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+fn execute_retry_callback(
+    deps: DepsMut,
+    info: MessageInfo,
+    sender: String,
+    job_id: String,
+    gas_limit: Option<u64>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let sender = deps.api.addr_validate(&sender)?;
+
+    // The dapp or the manager can retry
+    if info.sender != sender && config.manager.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized);
+    }
+
+    let min_gas_limit = dapp_callback_gas_limit(deps.storage, &config, &sender)?;
+    let gas_limit = gas_limit.unwrap_or(min_gas_limit);
+    if gas_limit < min_gas_limit {
+        return Err(ContractError::CallbackGasLimitTooLow { min: min_gas_limit });
+    }
+
+    let FailedCallback {
+        published,
+        randomness,
+        ..
+    } = FAILED_CALLBACKS
+        .may_load(deps.storage, (&sender, &job_id))?
+        .ok_or(ContractError::FailedCallbackNotFound)?;
+    FAILED_CALLBACKS.remove(deps.storage, (&sender, &job_id));
+    update_dapp_stats(deps.storage, &sender, |stats| {
+        stats.failed_callbacks = stats.failed_callbacks.saturating_sub(1);
+        stats.delivered_callbacks += 1;
+    })?;
+
+    // No reply here. If the callback fails again, the whole transaction fails
+    // and the failed callback remains stored.
+    let msg = SubMsg::new(create_callback_msg(
+        sender.to_string(),
+        job_id.clone(),
+        published,
+        randomness,
+    )?)
+    .with_gas_limit(gas_limit);
+
+    Ok(Response::new()
+        .add_submessage(msg)
+        .add_attribute(ATTR_ACTION, "execute_retry_callback")
+        .add_attribute(ATTR_JOB_ID, job_id))
+}
+
+fn execute_prune_failed_callbacks(
+    deps: DepsMut,
+    info: MessageInfo,
+    sender: String,
+    job_ids: Option<Vec<String>>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let sender = deps.api.addr_validate(&sender)?;
+
+    // The dapp or the manager can prune
+    if info.sender != sender && config.manager.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized);
+    }
+
+    let job_ids = match job_ids {
+        Some(job_ids) => job_ids,
+        None => FAILED_CALLBACKS
+            .prefix(&sender)
+            .keys(deps.storage, None, None, Order::Ascending)
+            .take(MAX_FAILED_CALLBACKS_PER_DAPP)
+            .collect::<StdResult<_>>()?,
+    };
+    let mut pruned = 0u32;
+    for job_id in job_ids {
+        let key = (&sender, job_id.as_str());
+        if FAILED_CALLBACKS.has(deps.storage, key) {
+            FAILED_CALLBACKS.remove(deps.storage, key);
+            pruned += 1;
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_prune_failed_callbacks")
+        .add_attribute(ATTR_SENDER, sender)
+        .add_attribute("pruned", pruned.to_string()))
+}
+
+fn execute_propose_manager(
+    deps: DepsMut,
+    info: MessageInfo,
+    manager: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    let manager = deps.api.addr_validate(&manager)?;
+    propose_manager(deps.storage, &manager)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_propose_manager")
+        .add_attribute("pending_manager", manager))
+}
+
+fn execute_accept_manager(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    let manager = accept_manager(deps.storage, &info.sender)?;
+    config.manager = Some(manager.clone());
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_accept_manager")
+        .add_attribute("manager", manager))
+}
+
+fn execute_cancel_manager_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    ensure_manager(&config, &info)?;
+
+    let cancelled = cancel_manager_transfer(deps.storage)?;
+
+    Ok(Response::new()
+        .add_attribute(ATTR_ACTION, "execute_cancel_manager_transfer")
+        .add_attribute("pending_manager", cancelled))
+}
+
+/// Adds and remove entries from the allow list.
+fn update_allowlist_unchecked(
+    deps: DepsMut,
+    add_addresses: Vec<String>,
+    remove_addresses: Vec<String>,
+) -> Result<(), ContractError> {
+    for addr in add_addresses {
+        let addr = deps.api.addr_validate(addr.as_str())?;
+        ALLOWLIST.save(deps.storage, &addr, &ALLOWLIST_MARKER)?;
+    }
+
+    for addr in remove_addresses {
+        let addr = deps.api.addr_validate(addr.as_str())?;
+        ALLOWLIST.remove(deps.storage, &addr);
+    }
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), ::cosmwasm_std::entry_point)]
+#[allow(unused)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        #[cfg(feature = "governance_owned")]
+        SudoMsg::Withdraw {
+            denom,
+            amount,
+            address,
+        } => sudo_withdraw(deps, env, denom, amount, address),
+        #[cfg(feature = "governance_owned")]
+        SudoMsg::WithdrawToCommunityPool { denom, amount } => {
+            sudo_withdraw_to_community_pool(deps, env, denom, amount)
+        }
+        #[cfg(feature = "governance_owned")]
+        SudoMsg::SetConfig {
+            manager,
+            prices,
+            payment,
+            nois_beacon_price,
+            callback_gas_limit,
+            mode,
+            allowlist_enabled,
+            min_after,
+            max_after,
+            refund_policy,
+        } => sudo_set_config(
+            deps,
+            env,
+            manager,
+            prices,
+            payment,
+            nois_beacon_price,
+            callback_gas_limit,
+            mode,
+            allowlist_enabled,
+            min_after,
+            max_after,
+            refund_policy,
+        ),
+        #[cfg(feature = "governance_owned")]
+        SudoMsg::SetAfterOffsets {
+            min_after_offset,
+            max_after_offset,
+        } => set_after_offsets_unchecked(
+            deps,
+            "sudo_set_after_offsets",
+            min_after_offset,
+            max_after_offset,
+        ),
+        #[cfg(feature = "governance_owned")]
+        SudoMsg::Pause { reason } => set_paused_unchecked(deps, "sudo_pause", Some(reason)),
+        #[cfg(feature = "governance_owned")]
+        SudoMsg::Unpause {} => set_paused_unchecked(deps, "sudo_unpause", None),
+    }
+}
+
+#[cfg(feature = "governance_owned")]
+fn sudo_withdraw(
+    deps: DepsMut,
+    env: Env,
+    denom: String,
+    amount: Option<Uint128>,
+    address: String,
+) -> Result<Response, ContractError> {
+    withdraw_unchecked(deps, env, "sudo_withdraw", denom, amount, address)
+}
+
+#[cfg(feature = "governance_owned")]
+fn sudo_withdraw_to_community_pool(
+    deps: DepsMut,
+    env: Env,
+    denom: String,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    withdraw_to_community_pool_unchecked(
+        deps,
+        env,
+        "sudo_withdraw_to_community_pool",
+        denom,
+        amount,
+    )
+}
+
+#[cfg(feature = "governance_owned")]
+#[allow(clippy::too_many_arguments)]
+fn sudo_set_config(
+    deps: DepsMut,
+    env: Env,
+    manager: Option<String>,
+    prices: Option<Vec<Coin>>,
+    payment: Option<String>,
+    nois_beacon_price: Option<Uint128>,
+    callback_gas_limit: Option<u64>,
+    mode: Option<OperationalMode>,
+    allowlist_enabled: Option<bool>,
+    min_after: Option<Timestamp>,
+    max_after: Option<Timestamp>,
+    refund_policy: Option<RefundPolicy>,
+) -> Result<Response, ContractError> {
+    set_config_unchecked(
+        deps,
+        env,
+        "sudo_set_config",
+        manager,
+        prices,
+        payment,
+        nois_beacon_price,
+        callback_gas_limit,
+        mode,
+        allowlist_enabled,
+        min_after,
+        max_after,
+        refund_policy,
+    )
+}
+
+/// The amount the manager can withdraw. Funds reserved for dapps are excluded.
+/// If `amount` is None, this is the full balance minus the reserved funds.
+fn withdrawable_amount(
+    deps: Deps,
+    env: &Env,
+    denom: String,
+    amount: Option<Uint128>,
+) -> Result<Coin, ContractError> {
+    let balance = deps
+        .querier
+        .query_balance(env.contract.address.clone(), denom.clone())?;
+    let reserved = RESERVED_FUNDS
+        .may_load(deps.storage, &denom)?
+        .unwrap_or_default();
+    let withdrawable = Coin {
+        denom,
+        amount: balance.amount.saturating_sub(reserved),
+    };
+    match amount {
+        Some(amount) if amount > withdrawable.amount => {
+            Err(ContractError::InsufficientWithdrawableBalance { withdrawable })
+        }
+        Some(amount) => Ok(Coin {
+            denom: withdrawable.denom,
+            amount,
+        }),
+        None => Ok(withdrawable),
+    }
+}
+
+fn withdraw_unchecked(
     deps: DepsMut,
     env: Env,
     action: &str,
     denom: String,
     amount: Option<Uint128>,
+    address: String,
 ) -> Result<Response, ContractError> {
-    let amount: Coin = match amount {
-        Some(amount) => Coin { denom, amount },
-        None => deps
-            .querier
-            .query_balance(env.contract.address.clone(), denom)?,
+    let address = deps.api.addr_validate(&address)?;
+    let amount = withdrawable_amount(deps.as_ref(), &env, denom, amount)?;
+
+    let msg = BankMsg::Send {
+        to_address: address.into(),
+        amount: vec![amount.clone()],
     };
+    let res = Response::new()
+        .add_message(msg)
+        .add_attribute(ATTR_ACTION, action)
+        .add_attribute("amount", amount.to_string());
+    Ok(res)
+}
+
+#[allow(unused)]
+fn withdraw_to_community_pool_unchecked(
+    deps: DepsMut,
+    env: Env,
+    action: &str,
+    denom: String,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let amount = withdrawable_amount(deps.as_ref(), &env, denom, amount)?;
 
     #[allow(deprecated)]
     let msg = CosmosMsg::Stargate {
@@ -548,6 +1893,7 @@ fn set_config_unchecked(
     allowlist_enabled: Option<bool>,
     min_after: Option<Timestamp>,
     max_after: Option<Timestamp>,
+    refund_policy: Option<RefundPolicy>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
@@ -558,10 +1904,15 @@ fn set_config_unchecked(
     let prices = prices.unwrap_or(config.prices);
     let test_mode = config.test_mode;
     let callback_gas_limit = callback_gas_limit.unwrap_or(config.callback_gas_limit);
-    let payment = match payment {
-        Some(pa) => Some(pa),
-        None => config.payment,
-    };
+    // The payment contract is stored per channel
+    if let Some(payment) = payment {
+        let primary = GATEWAY_CHANNEL
+            .may_load(deps.storage)?
+            .ok_or(ContractError::UnsetChannel)?;
+        let mut channel = GATEWAY_CHANNELS.load(deps.storage, &primary)?;
+        channel.payment = Some(payment);
+        GATEWAY_CHANNELS.save(deps.storage, &primary, &channel)?;
+    }
     let (nois_beacon_price, nois_beacon_price_updated) = match nois_beacon_price {
         Some(bp) => (bp, env.block.time),
         None => (config.nois_beacon_price, config.nois_beacon_price_updated),
@@ -581,19 +1932,29 @@ fn set_config_unchecked(
         Some(new_value) => Some(new_value),
         None => config.max_after,
     };
+    let refund_policy = match refund_policy {
+        Some(new_value) => {
+            validate_refund_policy(&new_value)?;
+            Some(new_value)
+        }
+        None => config.refund_policy,
+    };
 
     let new_config = Config {
         manager,
         prices,
         test_mode,
         callback_gas_limit,
-        payment,
+        payment: config.payment,
         nois_beacon_price,
         nois_beacon_price_updated,
         mode,
         allowlist_enabled: Some(allowlist_enabled),
         min_after,
         max_after,
+        min_after_offset: config.min_after_offset, // Use SetAfterOffsets for this
+        max_after_offset: config.max_after_offset, // Use SetAfterOffsets for this
+        refund_policy,
     };
 
     CONFIG.save(deps.storage, &new_config)?;
@@ -601,15 +1962,56 @@ fn set_config_unchecked(
     Ok(Response::default().add_attribute(ATTR_ACTION, action))
 }
 
-fn get_gateway_channel(storage: &dyn Storage) -> Result<String, ContractError> {
-    let data = GATEWAY_CHANNEL.may_load(storage)?;
-    match data {
-        Some(d) => Ok(d),
-        None => Err(ContractError::UnsetChannel),
+/// The channel for new requests. This is the primary channel if it is healthy.
+/// Otherwise it is the first healthy enabled channel. If no channel is healthy,
+/// the primary channel is used anyway.
+fn get_gateway_channel(
+    storage: &dyn Storage,
+    env: &Env,
+) -> Result<(String, GatewayChannelInfo), ContractError> {
+    let primary = GATEWAY_CHANNEL
+        .may_load(storage)?
+        .ok_or(ContractError::UnsetChannel)?;
+    let primary_info = GATEWAY_CHANNELS.load(storage, &primary)?;
+    if is_healthy(&primary_info, env) {
+        return Ok((primary, primary_info));
+    }
+    for item in GATEWAY_CHANNELS.range(storage, None, None, Order::Ascending) {
+        let (channel_id, info) = item?;
+        if info.enabled && is_healthy(&info, env) {
+            return Ok((channel_id, info));
+        }
     }
+    Ok((primary, primary_info))
 }
 
-#[allow(unused)]
+/// A channel is healthy unless it has pending packets and did not make progress for
+/// `GATEWAY_CHANNEL_STALL_TIMEOUT`, e.g. because the relayer or light client is broken.
+fn is_healthy(channel: &GatewayChannelInfo, env: &Env) -> bool {
+    channel
+        .waiting_since
+        .is_none_or(|since| since.plus_seconds(GATEWAY_CHANNEL_STALL_TIMEOUT) > env.block.time)
+}
+
+/// Updates the channel stats when a request packet is acknowledged or times out
+fn record_packet_progress(
+    storage: &mut dyn Storage,
+    env: &Env,
+    channel_id: &str,
+    acknowledged: bool,
+) -> StdResult<()> {
+    if let Some(mut channel) = GATEWAY_CHANNELS.may_load(storage, channel_id)? {
+        channel.pending_packets = channel.pending_packets.saturating_sub(1);
+        channel.waiting_since = (channel.pending_packets > 0).then_some(env.block.time);
+        if acknowledged {
+            channel.last_ack = Some(env.block.time);
+        }
+        GATEWAY_CHANNELS.save(storage, channel_id, &channel)?;
+    }
+    Ok(())
+}
+
+#[allow(unused)]
 fn encode_msg_fund_community_pool(amount: &Coin, depositor: &Addr) -> Vec<u8> {
     // Coin: https://github.com/cosmos/cosmos-sdk/blob/v0.45.15/proto/cosmos/base/v1beta1/coin.proto#L14-L19
     // MsgFundCommunityPool: https://github.com/cosmos/cosmos-sdk/blob/v0.45.15/proto/cosmos/distribution/v1beta1/tx.proto#L69-L76
@@ -623,37 +2025,153 @@ fn encode_msg_fund_community_pool(amount: &Coin, depositor: &Addr) -> Vec<u8> {
 }
 
 #[cfg_attr(not(feature = "library"), ::cosmwasm_std::entry_point)]
-pub fn reply(_deps: DepsMut, _env: Env, reply: Reply) -> Result<Response, ContractError> {
+pub fn reply(deps: DepsMut, _env: Env, reply: Reply) -> Result<Response, ContractError> {
     match reply.id {
-        REPLAY_ID_CALLBACK => {
+        REPLAY_ID_REQUEST_BEACON => {
+            let RequestPacketPayload { channel_id, jobs } = from_json(&reply.payload)?;
+            let response = reply.result.into_result().map_err(StdError::generic_err)?;
+            match parse_send_packet_sequence(&response) {
+                Some(sequence) => {
+                    PENDING_JOBS.save(deps.storage, (&channel_id, sequence), &jobs)?;
+                    Ok(Response::new().add_attribute("packet_sequence", sequence.to_string()))
+                }
+                // Old chains do not return the sequence. We don't fail the request
+                // because of that but cannot track the jobs. Their entries would never
+                // be removed if the request fails, so they are not stored.
+                None => {
+                    for job in jobs {
+                        JOBS.remove(deps.storage, (&job.sender, &job.job_id));
+                        release_funds(deps.storage, &job.funds)?;
+                    }
+                    Ok(Response::new())
+                }
+            }
+        }
+        REPLAY_ID_CALLBACK | REPLAY_ID_FAILURE_CALLBACK => {
+            let event_type = if reply.id == REPLAY_ID_CALLBACK {
+                EVENT_TYPE_CALLBACK
+            } else {
+                EVENT_TYPE_FAILURE_CALLBACK
+            };
             let mut attributes = vec![];
             match reply.result {
                 SubMsgResult::Ok(_) => {
                     attributes.push(Attribute::new(ATTR_CALLBACK_SUCCESS, "true"))
                 }
                 SubMsgResult::Err(err_msg) => {
+                    let mut stored = true;
+                    // Store failed randomness callbacks such that they can be retried
+                    if reply.id == REPLAY_ID_CALLBACK {
+                        if let Some(mut callback) = CALLBACK_IN_FLIGHT.may_load(deps.storage)? {
+                            // The callback was counted as delivered when it was sent
+                            update_dapp_stats(deps.storage, &callback.sender, |stats| {
+                                stats.delivered_callbacks =
+                                    stats.delivered_callbacks.saturating_sub(1);
+                                stats.failed_callbacks += 1;
+                            })?;
+                            callback.error.clone_from(&err_msg);
+                            let key = (&callback.sender, callback.job_id.as_str());
+                            let count = FAILED_CALLBACKS
+                                .prefix(&callback.sender)
+                                .keys_raw(deps.storage, None, None, Order::Ascending)
+                                .take(MAX_FAILED_CALLBACKS_PER_DAPP)
+                                .count();
+                            stored = count < MAX_FAILED_CALLBACKS_PER_DAPP
+                                || FAILED_CALLBACKS.has(deps.storage, key);
+                            if stored {
+                                FAILED_CALLBACKS.save(deps.storage, key, &callback)?;
+                            }
+                        }
+                    }
                     attributes.push(Attribute::new(ATTR_CALLBACK_SUCCESS, "false"));
                     attributes.push(Attribute::new(ATTR_CALLBACK_ERROR_MSG, err_msg));
+                    if !stored {
+                        attributes.push(Attribute::new(ATTR_CALLBACK_STORED, "false"));
+                    }
                 }
             };
-            let callback_event = Event::new(EVENT_TYPE_CALLBACK).add_attributes(attributes);
+            let callback_event = Event::new(event_type).add_attributes(attributes);
             Ok(Response::new().add_event(callback_event))
         }
+        REPLAY_ID_REFUND => {
+            // Only called on error. The funds stay in the proxy.
+            let error_msg = reply.result.into_result().err().unwrap_or_default();
+            let event = Event::new(EVENT_TYPE_REFUND)
+                .add_attribute(ATTR_CALLBACK_SUCCESS, "false")
+                .add_attribute(ATTR_CALLBACK_ERROR_MSG, error_msg);
+            Ok(Response::new().add_event(event))
+        }
         _ => Err(ContractError::UnknownReplyId { id: reply.id }),
     }
 }
 
+/// Reads the packet sequence from the response of an `IbcMsg::SendPacket`.
+/// wasmd responds with a `MsgIBCSendResponse`, which contains the sequence in field 1.
+#[allow(deprecated)]
+fn parse_send_packet_sequence(response: &SubMsgResponse) -> Option<u64> {
+    let data = match response.msg_responses.first() {
+        Some(msg_response) => &msg_response.value,
+        None => response.data.as_ref()?,
+    };
+    // Sequences start at 1, so 0 means the field is missing
+    Bufany::deserialize(data)
+        .ok()?
+        .uint64(1)
+        .filter(|sequence| *sequence != 0)
+}
+
 #[cfg_attr(not(feature = "library"), ::cosmwasm_std::entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<QueryResponse> {
     match msg {
         QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
-        QueryMsg::Prices {} => to_json_binary(&query_prices(deps)?),
-        QueryMsg::Price { denom } => to_json_binary(&query_price(deps, denom)?),
+        QueryMsg::Prices { address } => to_json_binary(&query_prices(deps, address)?),
+        QueryMsg::Price { denom, address } => to_json_binary(&query_price(deps, denom, address)?),
+        QueryMsg::VolumeTiers {} => to_json_binary(&query_volume_tiers(deps)?),
+        QueryMsg::CallbackGasLimit { address } => {
+            to_json_binary(&query_callback_gas_limit(deps, address)?)
+        }
+        QueryMsg::CallbackGasSurcharge {} => to_json_binary(&query_callback_gas_surcharge(deps)?),
         QueryMsg::GatewayChannel {} => to_json_binary(&query_gateway_channel(deps)?),
-        QueryMsg::Allowlist {} => to_json_binary(&query_allowlist(deps)?),
+        QueryMsg::GatewayChannels { start_after, limit } => {
+            to_json_binary(&query_gateway_channels(deps, env, start_after, limit)?)
+        }
+        QueryMsg::Allowlist { start_after, limit } => {
+            to_json_binary(&query_allowlist(deps, start_after, limit)?)
+        }
         QueryMsg::IsAllowlisted { address } => {
             to_json_binary(&query_is_allowlisted(deps, address)?)
         }
+        QueryMsg::Denylist { start_after, limit } => {
+            to_json_binary(&query_denylist(deps, start_after, limit)?)
+        }
+        QueryMsg::IsDenylisted { address } => to_json_binary(&query_is_denylisted(deps, address)?),
+        QueryMsg::PendingManager {} => to_json_binary(&query_pending_manager(deps.storage)?),
+        QueryMsg::FailureCallbacksEnabled { address } => {
+            to_json_binary(&query_failure_callbacks_enabled(deps, address)?)
+        }
+        QueryMsg::Credits { address } => to_json_binary(&query_credits(deps, address)?),
+        QueryMsg::DappStats { address } => to_json_binary(&query_dapp_stats(deps, address)?),
+        QueryMsg::AllDappStats { start_after, limit } => {
+            to_json_binary(&query_all_dapp_stats(deps, start_after, limit)?)
+        }
+        QueryMsg::Subscriptions {
+            sender,
+            start_after,
+            limit,
+        } => to_json_binary(&query_subscriptions(deps, sender, start_after, limit)?),
+        QueryMsg::FailedCallbacks {
+            sender,
+            start_after,
+            limit,
+        } => to_json_binary(&query_failed_callbacks(deps, sender, start_after, limit)?),
+        QueryMsg::Paused {} => to_json_binary(&query_paused(deps)?),
+        QueryMsg::JobIdRetention {} => to_json_binary(&query_job_id_retention(deps)?),
+        QueryMsg::Job { sender, job_id } => to_json_binary(&query_job(deps, sender, job_id)?),
+        QueryMsg::JobsBySender {
+            sender,
+            start_after,
+            limit,
+        } => to_json_binary(&query_jobs_by_sender(deps, sender, start_after, limit)?),
     }
 }
 
@@ -662,41 +2180,125 @@ fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     Ok(ConfigResponse { config })
 }
 
-fn query_prices(deps: Deps) -> StdResult<PricesResponse> {
+fn query_prices(deps: Deps, address: Option<String>) -> StdResult<PricesResponse> {
     let config = CONFIG.load(deps.storage)?;
-    Ok(PricesResponse {
-        prices: config.prices,
-    })
+    let prices = match address {
+        Some(address) => {
+            let address = deps.api.addr_validate(&address)?;
+            effective_prices(deps.storage, &config, &address)?
+        }
+        None => config.prices,
+    };
+    Ok(PricesResponse { prices })
 }
 
-fn query_price(deps: Deps, denom: String) -> StdResult<PriceResponse> {
-    let config = CONFIG.load(deps.storage)?;
-    let price = config
-        .prices
+fn query_price(deps: Deps, denom: String, address: Option<String>) -> StdResult<PriceResponse> {
+    let PricesResponse { prices } = query_prices(deps, address)?;
+    let price = prices
         .into_iter()
         .find(|price| price.denom == denom)
         .map(|coin| coin.amount);
     Ok(PriceResponse { price })
 }
 
-fn query_gateway_channel(deps: Deps) -> StdResult<GatewayChannelResponse> {
-    Ok(GatewayChannelResponse {
-        channel: GATEWAY_CHANNEL.may_load(deps.storage)?,
+fn query_callback_gas_limit(deps: Deps, address: String) -> StdResult<CallbackGasLimitResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let address = deps.api.addr_validate(&address)?;
+    let gas_limit = dapp_callback_gas_limit(deps.storage, &config, &address)?;
+    let surcharge = CALLBACK_GAS_LIMITS
+        .may_load(deps.storage, &address)?
+        .is_some_and(|limit| limit.surcharge);
+    Ok(CallbackGasLimitResponse {
+        gas_limit,
+        surcharge,
     })
 }
 
-fn query_allowlist(deps: Deps) -> StdResult<AllowlistResponse> {
-    // No pagination here yet 🤷‍♂️
-    let allowed = ALLOWLIST
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|result| {
-            let (address, _) = result.unwrap();
-            address.into()
+fn query_callback_gas_surcharge(deps: Deps) -> StdResult<CallbackGasSurchargeResponse> {
+    let surcharge = CALLBACK_GAS_SURCHARGE.may_load(deps.storage)?;
+    Ok(CallbackGasSurchargeResponse { surcharge })
+}
+
+fn query_volume_tiers(deps: Deps) -> StdResult<VolumeTiersResponse> {
+    let tiers = VOLUME_TIERS.may_load(deps.storage)?.unwrap_or_default();
+    Ok(VolumeTiersResponse { tiers })
+}
+
+fn query_gateway_channel(deps: Deps) -> StdResult<GatewayChannelResponse> {
+    let channel = GATEWAY_CHANNEL.may_load(deps.storage)?;
+    let version = match &channel {
+        Some(channel_id) => Some(GATEWAY_CHANNELS.load(deps.storage, channel_id)?.version),
+        None => None,
+    };
+    Ok(GatewayChannelResponse { channel, version })
+}
+
+fn query_gateway_channels(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<GatewayChannelsResponse> {
+    let primary = GATEWAY_CHANNEL.may_load(deps.storage)?;
+    let limit = limit.unwrap_or(50) as usize;
+    let low_bound = start_after.as_deref().map(Bound::exclusive);
+
+    let channels = GATEWAY_CHANNELS
+        .range(deps.storage, low_bound, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(channel_id, channel)| GatewayChannelStatus {
+                primary: primary.as_ref() == Some(&channel_id),
+                healthy: is_healthy(&channel, &env),
+                channel_id,
+                version: channel.version,
+                enabled: channel.enabled,
+                pending_packets: channel.pending_packets,
+                last_ack: channel.last_ack,
+                payment: channel.payment,
+            })
         })
-        .collect();
+        .collect::<StdResult<_>>()?;
+    Ok(GatewayChannelsResponse { channels })
+}
+
+fn query_allowlist(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllowlistResponse> {
+    let allowed = list_addresses(deps, ALLOWLIST, start_after, limit)?;
     Ok(AllowlistResponse { allowed })
 }
 
+fn query_denylist(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<DenylistResponse> {
+    let denied = list_addresses(deps, DENYLIST, start_after, limit)?;
+    Ok(DenylistResponse { denied })
+}
+
+/// Lists the addresses of the allowlist or denylist
+fn list_addresses(
+    deps: Deps,
+    list: Map<&Addr, u8>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<String>> {
+    let limit = limit.unwrap_or(50) as usize;
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let low_bound = start_after.as_ref().map(Bound::exclusive);
+
+    list.keys(deps.storage, low_bound, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(Addr::into_string))
+        .collect()
+}
+
 fn query_is_allowlisted(deps: Deps, addr: String) -> StdResult<IsAllowlistedResponse> {
     let addr = deps.api.addr_validate(&addr)?;
     Ok(IsAllowlistedResponse {
@@ -704,6 +2306,136 @@ fn query_is_allowlisted(deps: Deps, addr: String) -> StdResult<IsAllowlistedResp
     })
 }
 
+fn query_is_denylisted(deps: Deps, addr: String) -> StdResult<IsDenylistedResponse> {
+    let addr = deps.api.addr_validate(&addr)?;
+    Ok(IsDenylistedResponse {
+        listed: DENYLIST.has(deps.storage, &addr),
+    })
+}
+
+fn query_dapp_stats(deps: Deps, address: String) -> StdResult<DappStatsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let stats = DAPP_STATS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    Ok(DappStatsResponse { address, stats })
+}
+
+fn query_all_dapp_stats(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllDappStatsResponse> {
+    let limit = limit.unwrap_or(50) as usize;
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let low_bound = start_after.as_ref().map(Bound::exclusive);
+
+    let dapps = DAPP_STATS
+        .range(deps.storage, low_bound, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(address, stats)| DappStatsResponse { address, stats }))
+        .collect::<StdResult<_>>()?;
+    Ok(AllDappStatsResponse { dapps })
+}
+
+fn query_credits(deps: Deps, address: String) -> StdResult<CreditsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let credits = CREDITS
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<_>>()?;
+    Ok(CreditsResponse { credits })
+}
+
+fn query_subscriptions(
+    deps: Deps,
+    sender: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<SubscriptionsResponse> {
+    let sender = deps.api.addr_validate(&sender)?;
+    let limit = limit.unwrap_or(50) as usize;
+    let low_bound = start_after.as_deref().map(Bound::exclusive);
+
+    let subscriptions = SUBSCRIPTIONS
+        .prefix(&sender)
+        .range(deps.storage, low_bound, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, subscription)| subscription))
+        .collect::<StdResult<_>>()?;
+    Ok(SubscriptionsResponse { subscriptions })
+}
+
+fn query_failed_callbacks(
+    deps: Deps,
+    sender: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<FailedCallbacksResponse> {
+    let sender = deps.api.addr_validate(&sender)?;
+    let limit = limit.unwrap_or(50) as usize;
+    let low_bound = start_after.as_deref().map(Bound::exclusive);
+
+    let callbacks = FAILED_CALLBACKS
+        .prefix(&sender)
+        .range(deps.storage, low_bound, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, callback)| callback))
+        .collect::<StdResult<_>>()?;
+    Ok(FailedCallbacksResponse { callbacks })
+}
+
+fn query_paused(deps: Deps) -> StdResult<PausedResponse> {
+    let reason = PAUSED.may_load(deps.storage)?;
+    Ok(PausedResponse {
+        paused: reason.is_some(),
+        reason,
+    })
+}
+
+fn query_job_id_retention(deps: Deps) -> StdResult<JobIdRetentionResponse> {
+    let retention = JOB_ID_RETENTION.may_load(deps.storage)?;
+    Ok(JobIdRetentionResponse { retention })
+}
+
+fn query_job(deps: Deps, sender: String, job_id: String) -> StdResult<JobResponse> {
+    let sender = deps.api.addr_validate(&sender)?;
+    let job = JOBS.may_load(deps.storage, (&sender, &job_id))?;
+    Ok(JobResponse { job })
+}
+
+fn query_jobs_by_sender(
+    deps: Deps,
+    sender: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<JobsResponse> {
+    let sender = deps.api.addr_validate(&sender)?;
+    let limit = limit.unwrap_or(50) as usize;
+    let low_bound = start_after.as_deref().map(Bound::exclusive);
+
+    let jobs = JOBS
+        .prefix(&sender)
+        .range(deps.storage, low_bound, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, job)| job))
+        .collect::<StdResult<_>>()?;
+    Ok(JobsResponse { jobs })
+}
+
+fn query_failure_callbacks_enabled(
+    deps: Deps,
+    addr: String,
+) -> StdResult<FailureCallbacksEnabledResponse> {
+    let addr = deps.api.addr_validate(&addr)?;
+    Ok(FailureCallbacksEnabledResponse {
+        enabled: FAILURE_CALLBACKS.has(deps.storage, &addr),
+    })
+}
+
 #[cfg_attr(not(feature = "library"), ::cosmwasm_std::entry_point)]
 /// enforces ordering and versioing constraints
 pub fn ibc_channel_open(
@@ -730,24 +2462,45 @@ pub fn ibc_channel_connect(
     _env: Env,
     msg: IbcChannelConnectMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    let channel = match msg {
+    let (channel, version) = match msg {
         IbcChannelConnectMsg::OpenAck {
             channel,
-            counterparty_version: _,
-        } => channel,
+            counterparty_version,
+        } => (channel, counterparty_version),
         IbcChannelConnectMsg::OpenConfirm { .. } => return Err(ContractError::MustBeChainA),
     };
 
     let channel_id = channel.endpoint.channel_id;
 
-    if GATEWAY_CHANNEL.exists(deps.storage) {
+    if GATEWAY_CHANNELS.has(deps.storage, &channel_id) {
         return Err(ContractError::ChannelAlreadySet);
     }
 
-    GATEWAY_CHANNEL.save(deps.storage, &channel_id)?;
+    // The gateway answers with the version it wants to use, which we must understand as well
+    check_version(&version)?;
+
+    // The first channel becomes the primary channel. Further channels must be
+    // enabled by the manager before they are used.
+    let primary = !GATEWAY_CHANNEL.exists(deps.storage);
+    if primary {
+        GATEWAY_CHANNEL.save(deps.storage, &channel_id)?;
+    }
+    GATEWAY_CHANNELS.save(
+        deps.storage,
+        &channel_id,
+        &GatewayChannelInfo {
+            version: version.clone(),
+            enabled: primary,
+            pending_packets: 0,
+            waiting_since: None,
+            last_ack: None,
+            payment: None,
+        },
+    )?;
     Ok(IbcBasicResponse::new()
         .add_attribute(ATTR_ACTION, "ibc_channel_connect")
-        .add_attribute("channel_id", channel_id))
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("version", version))
 }
 
 #[cfg_attr(not(feature = "library"), ::cosmwasm_std::entry_point)]
@@ -764,12 +2517,27 @@ pub fn ibc_channel_close(
         // stop that anymore. We ensure this transactions succeeds to
         // allow the local channel's state to change to closed.
         //
-        // By clearing the GATEWAY_CHANNEL we allow a new channel to be established.
+        //
+        // If the primary channel is closed, the first remaining enabled channel becomes
+        // the primary channel. Without such a channel, no channel is primary until the
+        // manager sets one via `ExecuteMsg::SetPrimaryGatewayChannel`.
         IbcChannelCloseMsg::CloseConfirm { channel } => {
-            GATEWAY_CHANNEL.remove(deps.storage);
+            let channel_id = channel.endpoint.channel_id;
+            GATEWAY_CHANNELS.remove(deps.storage, &channel_id);
+            if GATEWAY_CHANNEL.may_load(deps.storage)?.as_ref() == Some(&channel_id) {
+                let next = GATEWAY_CHANNELS
+                    .range(deps.storage, None, None, Order::Ascending)
+                    .collect::<StdResult<Vec<_>>>()?
+                    .into_iter()
+                    .find(|(_, info)| info.enabled);
+                match next {
+                    Some((next_id, _)) => GATEWAY_CHANNEL.save(deps.storage, &next_id)?,
+                    None => GATEWAY_CHANNEL.remove(deps.storage),
+                }
+            }
             Ok(IbcBasicResponse::new()
                 .add_attribute(ATTR_ACTION, "ibc_channel_close")
-                .add_attribute("channel_id", channel.endpoint.channel_id))
+                .add_attribute("channel_id", channel_id))
         }
     }
 }
@@ -783,15 +2551,35 @@ pub fn ibc_packet_receive(
     // put this in a closure so we can convert all error responses into acknowledgements
     (|| {
         let IbcPacketReceiveMsg { packet, .. } = msg;
+        let channel = GATEWAY_CHANNELS
+            .may_load(deps.storage, &packet.dest.channel_id)?
+            .ok_or_else(|| ContractError::UnknownGatewayChannel {
+                channel_id: packet.dest.channel_id.clone(),
+            })?;
         let op: OutPacket = from_json(packet.data)?;
+        // Beacons are delivered on all connected channels such that requests sent before
+        // a channel was disabled still reach the dapps. Welcome packets set the payment
+        // contract of the channel, which must be known before the channel is enabled.
+        // Packets that change the global proxy state are only trusted on channels the
+        // manager enabled.
+        if !channel.enabled
+            && !matches!(
+                op,
+                OutPacket::DeliverBeacon { .. } | OutPacket::Welcome { .. }
+            )
+        {
+            return Err(ContractError::GatewayChannelDisabled);
+        }
         match op {
             OutPacket::DeliverBeacon {
                 source_id: _,
                 published,
                 randomness,
                 origin,
-            } => receive_deliver_beacon(deps, published, randomness, origin),
-            OutPacket::Welcome { payment } => receive_welcome(deps, env, payment),
+            } => receive_deliver_beacon(deps, env, published, randomness, origin),
+            OutPacket::Welcome { payment } => {
+                receive_welcome(deps, env, &packet.dest.channel_id, payment)
+            }
             OutPacket::PushBeaconPrice {
                 timestamp,
                 amount,
@@ -803,7 +2591,11 @@ pub fn ibc_packet_receive(
     .or_else(|e| {
         // we try to capture all app-level errors and convert them into
         // acknowledgement packets that contain an error code.
-        let acknowledgement = StdAck::error(format!("Error processing packet: {e}"));
+        let acknowledgement = PacketError::new(
+            e.packet_error_code(),
+            format!("Error processing packet: {e}"),
+        )
+        .to_ack();
         Ok(IbcReceiveResponse::new(acknowledgement)
             .add_event(Event::new("ibc").add_attribute("packet", "receive")))
     })
@@ -811,15 +2603,36 @@ pub fn ibc_packet_receive(
 
 fn receive_deliver_beacon(
     deps: DepsMut,
+    env: Env,
     published: Timestamp,
     randomness: HexBinary,
     origin: Binary,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let Config {
-        callback_gas_limit, ..
-    } = CONFIG.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
 
     let RequestBeaconOrigin { sender, job_id } = from_json(origin)?;
+    // The sender is an address on this chain as it was set by the proxy.
+    let callback_gas_limit =
+        dapp_callback_gas_limit(deps.storage, &config, &Addr::unchecked(&sender))?;
+
+    // Counted as delivered until the reply tells otherwise
+    update_dapp_stats(deps.storage, &Addr::unchecked(&sender), |stats| {
+        stats.delivered_callbacks += 1
+    })?;
+    JOBS.remove(deps.storage, (&Addr::unchecked(&sender), &job_id));
+
+    // Remember the callback such that it can be stored for a retry in case it fails.
+    // The sender is an address on this chain as it was set by the proxy.
+    CALLBACK_IN_FLIGHT.save(
+        deps.storage,
+        &FailedCallback {
+            sender: Addr::unchecked(&sender),
+            job_id: job_id.clone(),
+            published,
+            randomness: randomness.clone(),
+            error: String::new(),
+        },
+    )?;
 
     // Create the message for executing the callback.
     // This can fail for various reasons, like
@@ -828,36 +2641,72 @@ fn receive_deliver_beacon(
     // - out of gas
     // - any other processing error in the callback implementation
     let msg = SubMsg::reply_on_error(
-        WasmMsg::Execute {
-            contract_addr: sender,
-            msg: to_json_binary(&ReceiverExecuteMsg::NoisReceive {
-                callback: NoisCallback {
-                    job_id: job_id.clone(),
-                    published,
-                    randomness,
-                },
-            })?,
-            funds: vec![],
-        },
+        create_callback_msg(sender.clone(), job_id.clone(), published, randomness)?,
         REPLAY_ID_CALLBACK,
     )
     .with_gas_limit(callback_gas_limit);
 
     let ack = StdAck::success(to_json_binary(&OutPacketAck::DeliverBeacon {})?);
-    Ok(IbcReceiveResponse::new(ack)
+    let mut res = IbcReceiveResponse::new(ack)
         .add_attribute(ATTR_ACTION, "receive_deliver_beacon")
-        .add_attribute("job_id", job_id)
-        .add_submessage(msg))
+        .add_attribute("job_id", job_id.clone())
+        .add_submessage(msg);
+
+    // Request the next beacon of subscriptions. A failing request must not prevent the
+    // delivery of this beacon, so the subscription stalls instead.
+    if let Some(mut subscription) =
+        end_subscription_job(deps.storage, &Addr::unchecked(&sender), &job_id)?
+    {
+        match request_subscription_beacon(deps.storage, &env, config, &mut subscription) {
+            Ok(msgs) => {
+                SUBSCRIPTIONS.save(
+                    deps.storage,
+                    (&subscription.sender, &subscription.job_id_prefix),
+                    &subscription,
+                )?;
+                res = res.add_submessages(msgs);
+            }
+            Err(err) => {
+                res = res.add_event(
+                    Event::new(EVENT_TYPE_SUBSCRIPTION_STALLED)
+                        .add_attribute(ATTR_SENDER, subscription.sender.as_str())
+                        .add_attribute(ATTR_JOB_ID_PREFIX, subscription.job_id_prefix)
+                        .add_attribute(ATTR_CALLBACK_ERROR_MSG, err.to_string()),
+                );
+            }
+        }
+    }
+    Ok(res)
+}
+
+fn create_callback_msg(
+    sender: String,
+    job_id: String,
+    published: Timestamp,
+    randomness: HexBinary,
+) -> StdResult<WasmMsg> {
+    Ok(WasmMsg::Execute {
+        contract_addr: sender,
+        msg: to_json_binary(&ReceiverExecuteMsg::NoisReceive {
+            callback: NoisCallback {
+                job_id,
+                published,
+                randomness,
+            },
+        })?,
+        funds: vec![],
+    })
 }
 
 fn receive_welcome(
     deps: DepsMut,
     _env: Env,
+    channel_id: &str,
     payment: String,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let mut config = CONFIG.load(deps.storage)?;
-    config.payment = Some(payment);
-    CONFIG.save(deps.storage, &config)?;
+    let mut channel = GATEWAY_CHANNELS.load(deps.storage, channel_id)?;
+    channel.payment = Some(payment);
+    GATEWAY_CHANNELS.save(deps.storage, channel_id, &channel)?;
     let ack = StdAck::success(to_json_binary(&OutPacketAck::Welcome {})?);
     Ok(IbcReceiveResponse::new(ack).add_attribute(ATTR_ACTION, "receive_welcome"))
 }
@@ -876,27 +2725,53 @@ fn receive_push_beacon_price(
 
 #[cfg_attr(not(feature = "library"), ::cosmwasm_std::entry_point)]
 pub fn ibc_packet_ack(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     let mut attributes = Vec::<Attribute>::new();
     attributes.push(attr(ATTR_ACTION, "ibc_packet_ack"));
+    // Once acknowledged, the jobs of a request packet are not pending anymore
+    let channel_id = &msg.original_packet.src.channel_id;
+    record_packet_progress(deps.storage, &env, channel_id, true)?;
+    let jobs = take_pending_jobs(deps.storage, channel_id, msg.original_packet.sequence)?;
     let ack: StdAck = from_json(msg.acknowledgement.data)?;
+    let mut failure_reason = None;
     let is_error: bool;
     match ack {
         StdAck::Success(data) => {
             is_error = false;
             let response: InPacketAck = from_json(data)?;
             let ack_type: String = match response {
-                InPacketAck::RequestProcessed { source_id: _ } => "request_processed".to_string(),
-                InPacketAck::RequestQueued { source_id: _ } => "request_queued".to_string(),
+                InPacketAck::RequestProcessed { source_id } => {
+                    for job in &jobs {
+                        update_job_status(deps.storage, job, JobStatus::Processed {}, &source_id)?;
+                    }
+                    "request_processed".to_string()
+                }
+                InPacketAck::RequestQueued { source_id } => {
+                    for job in &jobs {
+                        update_job_status(deps.storage, job, JobStatus::Queued {}, &source_id)?;
+                    }
+                    "request_queued".to_string()
+                }
+                InPacketAck::RequestBeacons { statuses } => {
+                    for (job, status) in jobs.iter().zip(statuses) {
+                        let job_status = if status.queued {
+                            JobStatus::Queued {}
+                        } else {
+                            JobStatus::Processed {}
+                        };
+                        update_job_status(deps.storage, job, job_status, &status.source_id)?;
+                    }
+                    "request_beacons".to_string()
+                }
                 InPacketAck::PullBeaconPrice {
                     timestamp,
                     amount,
                     denom,
                 } => {
-                    update_nois_beacon_price(deps, timestamp, amount, denom)?;
+                    update_nois_beacon_price(deps.branch(), timestamp, amount, denom)?;
                     "beacon_price".to_string()
                 }
                 _ => "other".to_string(),
@@ -905,16 +2780,133 @@ pub fn ibc_packet_ack(
         }
         StdAck::Error(err) => {
             // The Request Beacon IBC packet failed, e.g. because the requested round
-            // is too old. Here we should send the dapp an error callback as the randomness
-            // will never come. Unfortunately we cannot map this packet to the job because
-            // we don't know the sequence when emitting a IbcMsg::SendPacket.
-            // https://github.com/CosmWasm/wasmd/issues/1154
+            // is too old. The randomness for its jobs will never come.
             is_error = true;
-            attributes.push(attr("error", err));
+            let PacketError { code, message } = PacketError::from_ack_error(&err);
+            failure_reason = Some(message.clone());
+            attributes.push(attr("error", message));
+            attributes.push(attr("error_code", code.as_str()));
         }
     }
     attributes.push(attr("is_error", is_error.to_string()));
-    Ok(IbcBasicResponse::new().add_attributes(attributes))
+    let mut res = IbcBasicResponse::new().add_attributes(attributes);
+    if let Some(reason) = failure_reason {
+        let (msgs, events) = process_failed_jobs(deps.storage, jobs, &reason)?;
+        res = res.add_submessages(msgs).add_events(events);
+    }
+    Ok(res)
+}
+
+/// Stores the status of an acknowledged job. Jobs that were delivered before
+/// the acknowledgement arrived are not stored again.
+fn update_job_status(
+    storage: &mut dyn Storage,
+    job: &PendingJob,
+    status: JobStatus,
+    source_id: &str,
+) -> StdResult<()> {
+    let key = (&job.sender, job.job_id.as_str());
+    if let Some(mut entry) = JOBS.may_load(storage, key)? {
+        entry.status = status;
+        entry.source_id = Some(source_id.to_string());
+        JOBS.save(storage, key, &entry)?;
+    }
+    Ok(())
+}
+
+/// Removes and returns the jobs of the request packet with the given channel and sequence.
+/// This is empty for packets that are not request packets.
+fn take_pending_jobs(
+    storage: &mut dyn Storage,
+    channel_id: &str,
+    sequence: u64,
+) -> StdResult<Vec<PendingJob>> {
+    let jobs = PENDING_JOBS
+        .may_load(storage, (channel_id, sequence))?
+        .unwrap_or_default();
+    PENDING_JOBS.remove(storage, (channel_id, sequence));
+    // Refunds of failed jobs are reserved again when added to the credit
+    for job in &jobs {
+        release_funds(storage, &job.funds)?;
+    }
+    Ok(jobs)
+}
+
+/// Refunds the senders according to the refund policy, sends failure callbacks
+/// to senders that opted into them and creates an event for every failed job.
+fn process_failed_jobs(
+    storage: &mut dyn Storage,
+    jobs: Vec<PendingJob>,
+    reason: &str,
+) -> StdResult<(Vec<SubMsg>, Vec<Event>)> {
+    let config = CONFIG.load(storage)?;
+    let refund_policy = config
+        .refund_policy
+        .clone()
+        .unwrap_or(RefundPolicy::None {});
+
+    let mut msgs = Vec::new();
+    let mut events = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        // A failed request stalls the subscription. The sender can cancel it to get
+        // the remaining funds back.
+        end_subscription_job(storage, &job.sender, &job.job_id)?;
+        JOBS.remove(storage, (&job.sender, &job.job_id));
+
+        let mut event = Event::new(EVENT_TYPE_FAILED_JOB)
+            .add_attribute(ATTR_SENDER, job.sender.as_str())
+            .add_attribute(ATTR_JOB_ID, job.job_id.as_str());
+
+        // Refund first such that the dapp has the funds when it gets the callback.
+        // A failing refund (e.g. due to insufficient balance) must not fail the whole
+        // IBC transaction, so errors are handled in the reply.
+        // Jobs paid from credit are refunded to the credit. Otherwise sponsored credit
+        // could be turned into tokens of the dapp.
+        let refund = refund_amount(&refund_policy, &job.funds);
+        if !refund.is_empty() {
+            let refund_str = refund
+                .iter()
+                .map(|coin| coin.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            event = event.add_attribute(ATTR_REFUND, refund_str);
+            update_dapp_stats(storage, &job.sender, |stats| {
+                sub_coins(&mut stats.fees, &refund)
+            })?;
+            if job.paid_from_credit {
+                add_credit(storage, &job.sender, &refund)?;
+            } else {
+                msgs.push(SubMsg::reply_on_error(
+                    BankMsg::Send {
+                        to_address: job.sender.to_string(),
+                        amount: refund,
+                    },
+                    REPLAY_ID_REFUND,
+                ));
+            }
+        }
+
+        if FAILURE_CALLBACKS.has(storage, &job.sender) {
+            let msg = SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: job.sender.to_string(),
+                    msg: to_json_binary(&FailureReceiverExecuteMsg::NoisReceiveFailure {
+                        failure: NoisFailure {
+                            job_id: job.job_id,
+                            reason: reason.to_string(),
+                        },
+                    })?,
+                    funds: vec![],
+                },
+                REPLAY_ID_FAILURE_CALLBACK,
+            )
+            .with_gas_limit(dapp_callback_gas_limit(storage, &config, &job.sender)?);
+            msgs.push(msg);
+        }
+
+        events.push(event);
+    }
+    Ok((msgs, events))
 }
 
 fn update_nois_beacon_price(
@@ -941,20 +2933,27 @@ fn update_nois_beacon_price(
 }
 
 #[cfg_attr(not(feature = "library"), ::cosmwasm_std::entry_point)]
-/// we just ignore these now. shall we store some info?
+/// The jobs of a timed out request packet failed
 pub fn ibc_packet_timeout(
-    _deps: DepsMut,
-    _env: Env,
-    _msg: IbcPacketTimeoutMsg,
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketTimeoutMsg,
 ) -> StdResult<IbcBasicResponse> {
-    Ok(IbcBasicResponse::new().add_attribute(ATTR_ACTION, "ibc_packet_timeout"))
+    let channel_id = &msg.packet.src.channel_id;
+    record_packet_progress(deps.storage, &env, channel_id, false)?;
+    let jobs = take_pending_jobs(deps.storage, channel_id, msg.packet.sequence)?;
+    let (msgs, events) = process_failed_jobs(deps.storage, jobs, "Request packet timed out")?;
+    Ok(IbcBasicResponse::new()
+        .add_attribute(ATTR_ACTION, "ibc_packet_timeout")
+        .add_submessages(msgs)
+        .add_events(events))
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
 
-    use crate::state::OperationalMode;
+    use crate::state::{IbcDenom, OperationalMode, TEST_MODE_NEXT_AFTER_INIT};
 
     use super::*;
     use cosmwasm_std::{
@@ -963,11 +2962,16 @@ mod tests {
             message_info, mock_dependencies, mock_dependencies_with_balance, mock_env,
             mock_ibc_channel_close_confirm, mock_ibc_channel_close_init,
             mock_ibc_channel_connect_ack, mock_ibc_channel_connect_confirm,
-            mock_ibc_channel_open_init, mock_ibc_packet_ack, MockApi, MockQuerier, MockStorage,
+            mock_ibc_channel_open_init, mock_ibc_packet_ack, mock_ibc_packet_recv,
+            mock_ibc_packet_timeout, MockApi, MockQuerier, MockStorage,
         },
-        CosmosMsg, IbcAcknowledgement, OwnedDeps, ReplyOn, Uint128,
+        CosmosMsg, Decimal, IbcAcknowledgement, MsgResponse, OwnedDeps, ReplyOn, Uint128,
+    };
+    use manager_transfer::{ManagerTransferError, PendingManagerResponse};
+    use nois_protocol::{
+        InPacketAck, PacketErrorCode, RequestBeaconStatus, APP_ORDER, BAD_APP_ORDER,
+        IBC_APP_VERSION, IBC_APP_VERSION_V7,
     };
-    use nois_protocol::{InPacketAck, APP_ORDER, BAD_APP_ORDER, IBC_APP_VERSION};
 
     const CREATOR: &str = "creator";
     /// A secondary manager address used when creator is not manager anymore
@@ -1018,11 +3022,15 @@ mod tests {
         })
     }
 
-    fn setup_channel(mut deps: DepsMut) {
-        let init = mock_ibc_channel_open_init("channel-12", APP_ORDER, IBC_APP_VERSION);
+    fn setup_channel(deps: DepsMut) {
+        setup_channel_with_version(deps, IBC_APP_VERSION)
+    }
+
+    fn setup_channel_with_version(mut deps: DepsMut, version: &str) {
+        let init = mock_ibc_channel_open_init("channel-12", APP_ORDER, version);
         ibc_channel_open(deps.branch(), mock_env(), init).unwrap();
 
-        let ack = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, IBC_APP_VERSION);
+        let ack = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, version);
         ibc_channel_connect(deps, mock_env(), ack).unwrap();
     }
 
@@ -1065,7 +3073,8 @@ mod tests {
         assert_eq!(res.messages.len(), 1);
         let out_msg = &res.messages[0];
         assert_eq!(out_msg.gas_limit, None);
-        assert_eq!(out_msg.reply_on, ReplyOn::Never);
+        assert_eq!(out_msg.reply_on, ReplyOn::Success);
+        assert_eq!(out_msg.id, REPLAY_ID_REQUEST_BEACON);
         assert!(matches!(
             out_msg.msg,
             CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
@@ -1114,7 +3123,8 @@ mod tests {
         assert_eq!(res.messages.len(), 1);
         let out_msg = &res.messages[0];
         assert_eq!(out_msg.gas_limit, None);
-        assert_eq!(out_msg.reply_on, ReplyOn::Never);
+        assert_eq!(out_msg.reply_on, ReplyOn::Success);
+        assert_eq!(out_msg.id, REPLAY_ID_REQUEST_BEACON);
         assert!(matches!(
             out_msg.msg,
             CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
@@ -1166,13 +3176,202 @@ mod tests {
         assert_eq!(res.messages.len(), 1);
         let out_msg = &res.messages[0];
         assert_eq!(out_msg.gas_limit, None);
-        assert_eq!(out_msg.reply_on, ReplyOn::Never);
+        assert_eq!(out_msg.reply_on, ReplyOn::Success);
+        assert_eq!(out_msg.id, REPLAY_ID_REQUEST_BEACON);
         assert!(matches!(
             out_msg.msg,
             CosmosMsg::Ibc(IbcMsg::SendPacket { .. })
         ));
     }
 
+    #[test]
+    fn get_randomness_at_round_works() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+
+        let msg = ExecuteMsg::GetRandomnessAtRound {
+            round: 4_000_000,
+            job_id: "foo".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, REPLAY_ID_REQUEST_BEACON);
+        let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg else {
+            panic!("Unexpected message type");
+        };
+        let packet: InPacket = from_json(data).unwrap();
+        assert_eq!(
+            packet,
+            InPacket::RequestBeaconAtRound {
+                round: 4_000_000,
+                origin: to_json_binary(&RequestBeaconOrigin {
+                    sender: dapp.to_string(),
+                    job_id: "foo".to_string(),
+                })
+                .unwrap(),
+            }
+        );
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "execute_get_randomness_at_round"
+        );
+
+        // Payment is required
+        let unpaid = ExecuteMsg::GetRandomnessAtRound {
+            round: 4_000_000,
+            job_id: "baz".to_string(),
+        };
+        let info = message_info(&dapp, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, unpaid).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientPayment));
+
+        // Rounds must be within the after range
+        let too_high = ExecuteMsg::GetRandomnessAtRound {
+            round: u64::MAX,
+            job_id: "bar".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info, too_high).unwrap_err();
+        match err {
+            ContractError::AfterTooHigh { max_after, after } => {
+                assert_eq!(max_after, mock_env().block.time.plus_seconds(TEN_YEARS_S));
+                assert_eq!(after, time_of_round(u32::MAX.into()));
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
+
+        // Not available on nois-v7 channels
+        let mut deps = setup(None);
+        setup_channel_with_version(deps.as_mut(), IBC_APP_VERSION_V7);
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::RoundRequestsNotSupported));
+    }
+
+    #[test]
+    fn get_randomness_batch_works() {
+        let mut deps = setup(None);
+
+        // Requires a channel to forward requests to
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+
+        let msg = ExecuteMsg::GetRandomnessBatch {
+            requests: vec![
+                BatchRandomnessRequest {
+                    job_id: "foo".to_string(),
+                    after: None,
+                },
+                BatchRandomnessRequest {
+                    job_id: "bar".to_string(),
+                    after: Some(Timestamp::from_seconds(1666343642)),
+                },
+                BatchRandomnessRequest {
+                    job_id: "baz".to_string(),
+                    after: Some(Timestamp::from_seconds(1666343642)),
+                },
+            ],
+        };
+
+        // Payment must cover all requests
+        let info = message_info(&dapp, &coins(2_999999, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientPayment));
+
+        let info = message_info(&dapp, &coins(3_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(first_attr(&res.attributes, "count").unwrap(), "3");
+        assert_eq!(res.messages.len(), 1);
+        let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg else {
+            panic!("Unexpected message type");
+        };
+        let InPacket::RequestBeacons { requests } = from_json(data).unwrap() else {
+            panic!("Unexpected packet type");
+        };
+        let origin = |job_id: &str| {
+            to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: job_id.to_string(),
+            })
+            .unwrap()
+        };
+        assert_eq!(
+            requests,
+            [
+                (TEST_MODE_NEXT_AFTER_INIT, origin("foo")),
+                (Timestamp::from_seconds(1666343642), origin("bar")),
+                (Timestamp::from_seconds(1666343642), origin("baz")),
+            ]
+        );
+
+        // Empty batch
+        let msg = ExecuteMsg::GetRandomnessBatch { requests: vec![] };
+        let info = message_info(&dapp, &coins(3_000000, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::EmptyBatch));
+
+        // Too many requests
+        let requests = (0..101)
+            .map(|i| BatchRandomnessRequest {
+                job_id: format!("job {i}"),
+                after: None,
+            })
+            .collect();
+        let msg = ExecuteMsg::GetRandomnessBatch { requests };
+        let info = message_info(&dapp, &coins(101_000000, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::BatchTooLarge { max: 100 }));
+    }
+
+    #[test]
+    fn get_randomness_batch_uses_single_packets_on_nois_v7_channel() {
+        let mut deps = setup(None);
+        setup_channel_with_version(deps.as_mut(), IBC_APP_VERSION_V7);
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+
+        let msg = ExecuteMsg::GetRandomnessBatch {
+            requests: vec![
+                BatchRandomnessRequest {
+                    job_id: "foo".to_string(),
+                    after: Some(Timestamp::from_seconds(1666343642)),
+                },
+                BatchRandomnessRequest {
+                    job_id: "bar".to_string(),
+                    after: Some(Timestamp::from_seconds(1666343643)),
+                },
+            ],
+        };
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        for (msg, (job_id, after)) in res
+            .messages
+            .iter()
+            .zip([("foo", 1666343642), ("bar", 1666343643)])
+        {
+            let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &msg.msg else {
+                panic!("Unexpected message type");
+            };
+            let packet: InPacket = from_json(data).unwrap();
+            assert_eq!(
+                packet,
+                InPacket::RequestBeacon {
+                    after: Timestamp::from_seconds(after),
+                    origin: to_json_binary(&RequestBeaconOrigin {
+                        sender: dapp.to_string(),
+                        job_id: job_id.to_string(),
+                    })
+                    .unwrap(),
+                }
+            );
+        }
+    }
+
     #[test]
     fn get_randomness_after_fails_for_invalid_inputs() {
         let mut deps = setup(None);
@@ -1204,7 +3403,7 @@ mod tests {
         // after == instantiate_time works
         let msg = ExecuteMsg::GetRandomnessAfter {
             after: instantiate_time,
-            job_id: "foo".to_string(),
+            job_id: "bar".to_string(),
         };
         let info = message_info(&dapp, &coins(22334455, "unoisx"));
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
@@ -1241,564 +3440,3595 @@ mod tests {
     }
 
     #[test]
-    fn set_config_works() {
+    fn get_randomness_after_respects_after_offsets() {
         let mut deps = setup(None);
+        setup_channel(deps.as_mut());
 
-        let creator = deps.api.addr_make(CREATOR);
+        let manager = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
 
-        // Check original config
-        let ConfigResponse { config: original } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
-        assert_eq!(original.manager, Some(creator.clone()));
-        assert_eq!(original.callback_gas_limit, 500_000);
-        assert_eq!(original.allowlist_enabled, Some(false));
+        let set_offsets = |min_after_offset: Option<u64>, max_after_offset: Option<u64>| {
+            ExecuteMsg::SetAfterOffsets {
+                min_after_offset,
+                max_after_offset,
+            }
+        };
 
-        // Update nothing
-        let msg = ExecuteMsg::SetConfig {
-            manager: None,
-            prices: None,
-            payment: None,
-            nois_beacon_price: None,
-            callback_gas_limit: None,
-            mode: None,
-            allowlist_enabled: None,
-            min_after: None,
-            max_after: None,
-        };
-        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
-        let ConfigResponse { config } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
-        assert_eq!(config, original);
+        // Only the manager can set offsets
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            set_offsets(Some(0), Some(3600)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
 
-        // Set allowlist_enabled to true
-        let msg = ExecuteMsg::SetConfig {
-            manager: None,
-            prices: None,
-            payment: None,
-            nois_beacon_price: None,
-            callback_gas_limit: None,
-            mode: None,
-            allowlist_enabled: Some(true),
-            min_after: None,
-            max_after: None,
-        };
-        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        // min offset must not exceed max offset
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            set_offsets(Some(7200), Some(3600)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAfterOffsets));
+
+        // Not in the past and at most 1 hour in the future
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            set_offsets(Some(0), Some(3600)),
+        )
+        .unwrap();
         let ConfigResponse { config } =
             from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
-        // Updated
-        assert_eq!(config.allowlist_enabled, Some(true));
-        // Rest unchanged
-        assert_eq!(config.prices, original.prices);
-        assert_eq!(config.manager, original.manager);
-        assert_eq!(config.mode, original.mode);
-        assert_eq!(config.payment, original.payment);
+        assert_eq!(config.min_after_offset, Some(0));
+        assert_eq!(config.max_after_offset, Some(3600));
 
-        // Set allowlist_enabled to false
-        let msg = ExecuteMsg::SetConfig {
-            manager: None,
-            prices: None,
-            payment: None,
-            nois_beacon_price: None,
-            callback_gas_limit: None,
-            mode: None,
-            allowlist_enabled: Some(false),
-            min_after: None,
-            max_after: None,
+        // The window moves with the block time
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_days(1);
+        let now = env.block.time;
+
+        // Within the absolute bounds but in the past
+        let msg = ExecuteMsg::GetRandomnessAfter {
+            after: now.minus_seconds(1),
+            job_id: "foo".to_string(),
         };
-        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
-        let ConfigResponse { config } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
-        // Updated
-        assert_eq!(config.allowlist_enabled, Some(false));
-        // Rest unchanged
-        assert_eq!(config.prices, original.prices);
-        assert_eq!(config.manager, original.manager);
-        assert_eq!(config.mode, original.mode);
-        assert_eq!(config.payment, original.payment);
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        match err {
+            ContractError::AfterTooLow { min_after, after } => {
+                assert_eq!(min_after, now);
+                assert_eq!(after, now.minus_seconds(1));
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
 
-        // Update callback_gas_limit
-        let msg = ExecuteMsg::SetConfig {
-            manager: None,
-            prices: None,
-            payment: None,
-            nois_beacon_price: None,
-            callback_gas_limit: Some(800_000),
-            mode: None,
-            allowlist_enabled: None,
-            min_after: None,
-            max_after: None,
+        // Too far in the future
+        let msg = ExecuteMsg::GetRandomnessAfter {
+            after: now.plus_seconds(3601),
+            job_id: "foo".to_string(),
         };
-        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
-        let ConfigResponse { config } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
-        // Updated
-        assert_eq!(config.callback_gas_limit, 800_000);
-        // Rest unchanged
-        assert_eq!(config.prices, original.prices);
-        assert_eq!(config.manager, original.manager);
-        assert_eq!(config.mode, original.mode);
-        assert_eq!(config.payment, original.payment);
-    }
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        match err {
+            ContractError::AfterTooHigh { max_after, after } => {
+                assert_eq!(max_after, now.plus_seconds(3600));
+                assert_eq!(after, now.plus_seconds(3601));
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
 
-    #[test]
-    fn set_config_only_allowed_for_manager() {
-        let mut deps = mock_dependencies();
+        // Bounds are inclusive
+        for (job_id, after) in [("a", now), ("b", now.plus_seconds(3600))] {
+            let msg = ExecuteMsg::GetRandomnessAfter {
+                after,
+                job_id: job_id.to_string(),
+            };
+            let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
 
-        let dapp = deps.api.addr_make(DAPP_ADDRESS);
-        let creator = deps.api.addr_make(CREATOR);
+        // Offsets only apply to `after` values provided by the caller
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            set_offsets(Some(60), Some(3600)),
+        )
+        .unwrap();
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "next".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let msg = ExecuteMsg::GetRandomnessBatch {
+            requests: vec![BatchRandomnessRequest {
+                job_id: "next-batch".to_string(),
+                after: None,
+            }],
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let msg = ExecuteMsg::GetRandomnessBatch {
+            requests: vec![BatchRandomnessRequest {
+                job_id: "now-batch".to_string(),
+                after: Some(now),
+            }],
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::AfterTooLow { .. }));
 
-        let msg = InstantiateMsg {
-            manager: Some(creator.to_string()),
-            prices: vec![coin(1_000000, "unoisx")],
-            test_mode: None,
-            callback_gas_limit: 500_000,
-            mode: OperationalMode::Funded {},
-            allowlist_enabled: None,
-            allowlist: None,
+        // Huge offsets do not overflow
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            set_offsets(Some(u64::MAX), Some(u64::MAX)),
+        )
+        .unwrap();
+        let msg = ExecuteMsg::GetRandomnessAfter {
+            after: now,
+            job_id: "c".to_string(),
         };
-        let info = message_info(&creator, &[]);
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::AfterTooLow { .. }));
 
-        // Edit config
-        let msg = ExecuteMsg::SetConfig {
-            manager: None,
-            prices: None,
-            payment: None,
-            nois_beacon_price: None,
-            callback_gas_limit: None,
-            mode: None,
-            allowlist_enabled: Some(false),
-            min_after: None,
-            max_after: None,
+        // Offsets can be removed
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            set_offsets(None, None),
+        )
+        .unwrap();
+        let ConfigResponse { config } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.min_after_offset, None);
+        assert_eq!(config.max_after_offset, None);
+        let msg = ExecuteMsg::GetRandomnessAfter {
+            after: now.minus_seconds(1),
+            job_id: "d".to_string(),
         };
-        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::Unauthorized));
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), env, info, msg).unwrap();
     }
 
     #[test]
-    fn withdraw_works() {
+    fn credits_work() {
         let mut deps = setup(None);
+        setup_channel(deps.as_mut());
 
-        let creator = deps.api.addr_make(CREATOR);
         let dapp = deps.api.addr_make(DAPP_ADDRESS);
-        let some = deps.api.addr_make("some-address");
+        let sponsor = deps.api.addr_make("sponsor");
+        let creator = deps.api.addr_make(CREATOR);
 
-        let msg = ExecuteMsg::Withdraw {
-            denom: "unoisx".to_string(),
-            amount: Some(Uint128::new(12)),
-            address: some.to_string(),
+        fn credits(deps: Deps, address: &Addr) -> Vec<Coin> {
+            let CreditsResponse { credits } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::Credits {
+                        address: address.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            credits
+        }
+
+        // Top up
+        let msg = ExecuteMsg::TopUp {
+            beneficiary: dapp.to_string(),
         };
         let err = execute(
             deps.as_mut(),
             mock_env(),
-            message_info(&dapp, &[]),
+            message_info(&sponsor, &[]),
             msg.clone(),
         )
         .unwrap_err();
-        assert!(matches!(err, ContractError::Unauthorized));
-        let res = execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 1);
-        assert_eq!(
-            res.messages[0].msg,
-            CosmosMsg::Bank(BankMsg::Send {
-                to_address: some.to_string(),
-                amount: coins(12, "unoisx"),
-            })
-        );
-    }
+        assert!(matches!(err, ContractError::InvalidTopUp));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&sponsor, &coins(5_000000, "ucosm")),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTopUp));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&sponsor, &coins(2_500000, "unoisx")),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(credits(deps.as_ref(), &dapp), coins(2_500000, "unoisx"));
+        assert_eq!(credits(deps.as_ref(), &sponsor), []);
 
-    #[test]
-    fn withdraw_all_works() {
-        let mut deps = setup(None);
+        // Requests without funds are paid from the credit
+        let msg = |job_id: &str| ExecuteMsg::GetNextRandomness {
+            job_id: job_id.to_string(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg("foo1"),
+        )
+        .unwrap();
+        assert_eq!(credits(deps.as_ref(), &dapp), coins(1_500000, "unoisx"));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &coins(1_000000, "unoisx")),
+            msg("foo2"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::FundsWithCredit));
+        assert_eq!(credits(deps.as_ref(), &dapp), coins(1_500000, "unoisx"));
+        let batch = ExecuteMsg::GetRandomnessBatch {
+            requests: vec![
+                BatchRandomnessRequest {
+                    job_id: "bar".to_string(),
+                    after: None,
+                },
+                BatchRandomnessRequest {
+                    job_id: "baz".to_string(),
+                    after: None,
+                },
+            ],
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), batch).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientPayment));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg("foo3"),
+        )
+        .unwrap();
+        assert_eq!(credits(deps.as_ref(), &dapp), coins(500000, "unoisx"));
 
-        let creator = deps.api.addr_make(CREATOR);
-        let dapp = deps.api.addr_make(DAPP_ADDRESS);
-        let some = deps.api.addr_make("some-address");
+        // Insufficient credit falls back to the attached funds
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg("foo4"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientPayment));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &coins(1_000000, "unoisx")),
+            msg("foo5"),
+        )
+        .unwrap();
+        assert_eq!(credits(deps.as_ref(), &dapp), coins(500000, "unoisx"));
 
-        let msg = ExecuteMsg::Withdraw {
+        // Manager withdraws unused credit
+        let withdraw = |amount: Option<u128>| ExecuteMsg::WithdrawCredits {
+            address: dapp.to_string(),
             denom: "unoisx".to_string(),
-            amount: None,
-            address: some.to_string(),
+            amount: amount.map(Uint128::new),
+            recipient: sponsor.to_string(),
         };
-
-        // Withdraw by dapp
         let err = execute(
             deps.as_mut(),
             mock_env(),
             message_info(&dapp, &[]),
-            msg.clone(),
+            withdraw(None),
         )
         .unwrap_err();
         assert!(matches!(err, ContractError::Unauthorized));
-
-        // Withdraw by manager
-        let res = execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
-        assert_eq!(res.messages.len(), 1);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            withdraw(Some(500001)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientCredit));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            withdraw(Some(200000)),
+        )
+        .unwrap();
         assert_eq!(
-            res.messages[0].msg,
-            CosmosMsg::Bank(BankMsg::Send {
-                to_address: some.to_string(),
-                amount: coins(22334455, "unoisx"),
-            })
+            res.messages,
+            [SubMsg::new(BankMsg::Send {
+                to_address: sponsor.to_string(),
+                amount: coins(200000, "unoisx"),
+            })]
         );
+        assert_eq!(credits(deps.as_ref(), &dapp), coins(300000, "unoisx"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            withdraw(None),
+        )
+        .unwrap();
+        assert_eq!(credits(deps.as_ref(), &dapp), []);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            withdraw(None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientCredit));
     }
 
     #[test]
-    fn withdraw_when_manager_is_not_set_withdraws_are_unauthorised() {
-        // Check that if manager not set, a random person cannot execute manager-like operations.
-        let mut deps = mock_dependencies();
+    fn refunds_of_credit_paid_requests_go_to_credit() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
 
-        let creator = deps.api.addr_make(CREATOR);
-        let manager = deps.api.addr_make(MANAGER_ADDRESS);
         let dapp = deps.api.addr_make(DAPP_ADDRESS);
-        let some = deps.api.addr_make("some-address");
-
-        let msg = InstantiateMsg {
-            manager: None,
-            prices: vec![coin(1_000000, "unoisx")],
-            test_mode: None,
-            callback_gas_limit: 500_000,
-            mode: OperationalMode::Funded {},
-            allowlist_enabled: None,
-            allowlist: None,
-        };
-        let info = message_info(&creator, &[]);
-        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-
-        // withdraw
-        let msg = ExecuteMsg::Withdraw {
-            denom: "unoisx".to_string(),
-            amount: Some(Uint128::new(12)),
-            address: some.to_string(),
-        };
-        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::Unauthorized));
+        let sponsor = deps.api.addr_make("sponsor");
+        let manager = deps.api.addr_make(CREATOR);
 
-        // withdraw all
-        let msg = ExecuteMsg::Withdraw {
-            denom: "unoisx".to_string(),
-            amount: None,
-            address: some.to_string(),
+        let credits = |deps: Deps| -> Vec<Coin> {
+            let CreditsResponse { credits } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::Credits {
+                        address: dapp.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            credits
         };
-        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::Unauthorized));
 
-        // Edit config
         let msg = ExecuteMsg::SetConfig {
-            manager: Some(manager.to_string()),
             prices: None,
             payment: None,
             nois_beacon_price: None,
             callback_gas_limit: None,
             mode: None,
-            allowlist_enabled: Some(false),
+            allowlist_enabled: None,
             min_after: None,
             max_after: None,
+            refund_policy: Some(RefundPolicy::Full {}),
         };
-
-        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::Unauthorized));
-    }
-
-    #[test]
-    fn update_allowlist_works() {
-        let mut deps = setup(None);
-
-        let creator = deps.api.addr_make(CREATOR);
-        let aaa = deps.api.addr_make("aaa");
-        let bbb = deps.api.addr_make("bbb");
-        let ccc = deps.api.addr_make("ccc");
-
-        let msg = ExecuteMsg::UpdateAllowlist {
-            add: vec![aaa.to_string(), ccc.to_string()],
-            remove: vec![aaa.to_string(), bbb.to_string()],
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let msg = ExecuteMsg::TopUp {
+            beneficiary: dapp.to_string(),
         };
+        let info = message_info(&sponsor, &coins(3_000000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
-
-        assert!(!ALLOWLIST.has(&deps.storage, &bbb));
-        assert!(ALLOWLIST.has(&deps.storage, &ccc));
+        // A failed request paid from credit is refunded to the credit
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "foo".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        assert_eq!(credits(deps.as_ref()), coins(2_000000, "unoisx"));
+        let packet = InPacket::RequestBeacon {
+            after: Timestamp::from_seconds(1666343642),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
+        let ack = StdAck::error("kaputt");
+        let msg = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.messages, []);
+        assert_eq!(
+            first_attr(&res.events[0].attributes, "refund").unwrap(),
+            "1000000unoisx"
+        );
+        assert_eq!(credits(deps.as_ref()), coins(3_000000, "unoisx"));
 
-        // If an address is both added and removed, err on the side or removing it,
-        // hence, here we check that "aaa" is indeed not found.
-        assert!(!ALLOWLIST.has(&deps.storage, &aaa));
+        // Cancelled subscriptions paid from credit are refunded to the credit
+        let msg = ExecuteMsg::Subscribe {
+            interval: 3600,
+            count: 2,
+            job_id_prefix: "raffle-".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(30));
+        assert_eq!(credits(deps.as_ref()), coins(1_000000, "unoisx"));
+        let msg = ExecuteMsg::CancelSubscription {
+            job_id_prefix: "raffle-".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        assert_eq!(res.messages, []);
+        assert_eq!(credits(deps.as_ref()), coins(2_000000, "unoisx"));
     }
 
     #[test]
-    fn update_allowlist_fails_for_non_manager() {
+    fn subscriptions_work() {
         let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
         let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let now = mock_env().block.time;
+
+        fn subscriptions(deps: Deps, sender: &Addr) -> Vec<Subscription> {
+            let SubscriptionsResponse { subscriptions } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::Subscriptions {
+                        sender: sender.to_string(),
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            subscriptions
+        }
 
-        let msg = ExecuteMsg::UpdateAllowlist {
-            add: vec!["aaa".to_owned(), "ccc".to_owned()],
-            remove: vec!["aaa".to_owned(), "bbb".to_owned()],
+        /// Returns the request packet of the message
+        fn request(msg: &SubMsg) -> InPacket {
+            let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &msg.msg else {
+                panic!("Unexpected message type");
+            };
+            from_json(data).unwrap()
+        }
+
+        let deliver = |deps: DepsMut, job_id: &str| {
+            let packet = OutPacket::DeliverBeacon {
+                source_id: "drand:mainnet:123".to_string(),
+                published: now,
+                randomness: HexBinary::from([0xaa; 32]),
+                origin: to_json_binary(&RequestBeaconOrigin {
+                    sender: dapp.to_string(),
+                    job_id: job_id.to_string(),
+                })
+                .unwrap(),
+            };
+            let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+            ibc_packet_receive(deps, mock_env(), msg).unwrap()
+        };
+        let origin = |job_id: &str| {
+            to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: job_id.to_string(),
+            })
+            .unwrap()
         };
 
-        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
-        assert!(matches!(err, ContractError::Unauthorized));
-    }
+        let subscribe = |count: u32, job_id_prefix: &str| ExecuteMsg::Subscribe {
+            interval: 3600,
+            count,
+            job_id_prefix: job_id_prefix.to_string(),
+        };
 
-    //
-    // Query tests
-    //
+        // Invalid subscriptions
+        let info = message_info(&dapp, &coins(3_000000, "unoisx"));
+        let msg = ExecuteMsg::Subscribe {
+            interval: 0,
+            count: 3,
+            job_id_prefix: "raffle-".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::ZeroSubscriptionInterval));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            subscribe(0, "raffle-"),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InvalidSubscriptionCount { max: 1000 }
+        ));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            subscribe(4, "raffle-"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientPayment));
+        for interval in [TEN_YEARS_S, u64::MAX] {
+            let msg = ExecuteMsg::Subscribe {
+                interval,
+                count: 3,
+                job_id_prefix: "raffle-".to_string(),
+            };
+            let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+            assert!(matches!(err, ContractError::AfterTooHigh { .. }));
+        }
 
-    #[test]
-    fn query_prices_works() {
-        let deps = setup(None);
+        // Subscribe requests the first beacon
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            subscribe(3, "raffle-"),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            request(&res.messages[0]),
+            InPacket::RequestBeacon {
+                after: now.plus_seconds(3600),
+                origin: origin("raffle-1"),
+            }
+        );
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        assert_eq!(
+            subscriptions(deps.as_ref(), &dapp),
+            [Subscription {
+                sender: dapp.clone(),
+                job_id_prefix: "raffle-".to_string(),
+                interval: 3600,
+                count: 3,
+                requested: 1,
+                next_after: now.plus_seconds(7200),
+                funds: coins(2_000000, "unoisx"),
+                paid_from_credit: false,
+                pending_job: Some("raffle-1".to_string()),
+            }]
+        );
 
-        let PricesResponse { prices } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Prices {}).unwrap()).unwrap();
-        assert_eq!(prices, coins(1000000, "unoisx"));
-    }
+        let err = execute(deps.as_mut(), mock_env(), info, subscribe(3, "raffle-")).unwrap_err();
+        assert!(matches!(err, ContractError::SubscriptionExists));
 
-    #[test]
-    fn query_price_works() {
-        let deps = setup(None);
+        // Delivery requests the next beacon
+        let res = deliver(deps.as_mut(), "raffle-1");
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.messages[0].id, REPLAY_ID_CALLBACK);
+        assert_eq!(
+            request(&res.messages[1]),
+            InPacket::RequestBeacon {
+                after: now.plus_seconds(7200),
+                origin: origin("raffle-2"),
+            }
+        );
+        send_packet_reply(deps.as_mut(), &res.messages, Some(30));
 
-        let PriceResponse { price } = from_json(
-            query(
-                deps.as_ref(),
-                mock_env(),
-                QueryMsg::Price {
-                    denom: "shitcoin".to_string(),
-                },
-            )
-            .unwrap(),
+        // Cancel refunds the unused funds and keeps the request in flight
+        let msg = ExecuteMsg::CancelSubscription {
+            job_id_prefix: "raffle-".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
         )
         .unwrap();
-        assert_eq!(price, None);
+        assert_eq!(
+            res.messages,
+            [SubMsg::new(BankMsg::Send {
+                to_address: dapp.to_string(),
+                amount: coins(1_000000, "unoisx"),
+            })]
+        );
+        let subscription = &subscriptions(deps.as_ref(), &dapp)[0];
+        assert_eq!(subscription.count, 2);
+        assert_eq!(subscription.funds, []);
+        let res = deliver(deps.as_mut(), "raffle-2");
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(subscriptions(deps.as_ref(), &dapp), []);
+        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::SubscriptionNotFound));
 
-        let PriceResponse { price } = from_json(
-            query(
-                deps.as_ref(),
-                mock_env(),
-                QueryMsg::Price {
-                    denom: "unoisx".to_string(),
-                },
-            )
-            .unwrap(),
-        )
-        .unwrap();
-        assert_eq!(price, Some(Uint128::new(1000000)));
+        // A failed request stalls the subscription
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, subscribe(2, "lottery-")).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let packet = InPacket::RequestBeacon {
+            after: now.plus_seconds(3600),
+            origin: origin("lottery-1"),
+        };
+        let msg = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        let subscription = &subscriptions(deps.as_ref(), &dapp)[0];
+        assert_eq!(subscription.requested, 1);
+        assert_eq!(subscription.pending_job, None);
+        let msg = ExecuteMsg::CancelSubscription {
+            job_id_prefix: "lottery-".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            [SubMsg::new(BankMsg::Send {
+                to_address: dapp.to_string(),
+                amount: coins(1_000000, "unoisx"),
+            })]
+        );
+        assert_eq!(subscriptions(deps.as_ref(), &dapp), []);
     }
 
     #[test]
-    fn query_allowlist_works() {
-        // some list
-        let addr_in_allowlist = vec![addr("addr2").to_string(), addr("addr1").to_string()];
-        let deps = setup(Some(InstantiateMsg {
-            manager: Some(addr(CREATOR).to_string()),
-            prices: vec![coin(1_000000, "unoisx")],
-            test_mode: Some(true),
-            callback_gas_limit: 500_000,
-            mode: OperationalMode::Funded {},
-            allowlist_enabled: Some(true),
-            allowlist: Some(addr_in_allowlist),
-        }));
+    fn subscription_stalls_when_next_request_fails() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
 
-        let AllowlistResponse { allowed } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Allowlist {}).unwrap()).unwrap();
-        assert_eq!(
-            allowed.into_iter().collect::<HashSet<_, _>>(),
-            HashSet::from([addr("addr1").to_string(), addr("addr2").to_string()])
-        );
+        let manager = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
 
-        // empty list
-        let deps = setup(Some(InstantiateMsg {
-            manager: Some(addr(CREATOR).to_string()),
-            prices: vec![coin(1_000000, "unoisx")],
-            test_mode: Some(true),
-            callback_gas_limit: 500_000,
-            mode: OperationalMode::Funded {},
-            allowlist_enabled: Some(true),
-            allowlist: Some(vec![]),
-        }));
+        let deliver = |deps: DepsMut, channel_id: &str, job_id: &str| {
+            let packet = OutPacket::DeliverBeacon {
+                source_id: "drand:mainnet:123".to_string(),
+                published: mock_env().block.time,
+                randomness: HexBinary::from([0xaa; 32]),
+                origin: to_json_binary(&RequestBeaconOrigin {
+                    sender: dapp.to_string(),
+                    job_id: job_id.to_string(),
+                })
+                .unwrap(),
+            };
+            let msg = mock_ibc_packet_recv(channel_id, &packet).unwrap();
+            ibc_packet_receive(deps, mock_env(), msg).unwrap()
+        };
+        let subscribe = |job_id_prefix: &str| ExecuteMsg::Subscribe {
+            interval: 3600,
+            count: 2,
+            job_id_prefix: job_id_prefix.to_string(),
+        };
+        let stalled_event = |job_id_prefix: &str, err: ContractError| {
+            Event::new("nois-subscription-stalled")
+                .add_attribute("sender", dapp.as_str())
+                .add_attribute("job_id_prefix", job_id_prefix)
+                .add_attribute("error_msg", err.to_string())
+        };
 
-        let AllowlistResponse { allowed } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Allowlist {}).unwrap()).unwrap();
-        assert!(allowed.is_empty());
+        // Subscription job IDs are subject to the job ID retention
+        let msg = ExecuteMsg::SetJobIdRetention {
+            retention: Some(3600),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "raffle-2".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(28));
+        deliver(deps.as_mut(), "channel-12", "raffle-2");
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, subscribe("raffle-")).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+
+        // The beacon is delivered even if the next request fails
+        let res = deliver(deps.as_mut(), "channel-12", "raffle-1");
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, REPLAY_ID_CALLBACK);
+        assert_eq!(
+            res.events,
+            [stalled_event("raffle-", ContractError::JobIdAlreadyUsed)]
+        );
+        let subscription = SUBSCRIPTIONS
+            .load(&deps.storage, (&dapp, "raffle-"))
+            .unwrap();
+        assert_eq!(subscription.requested, 1);
+        assert_eq!(subscription.pending_job, None);
+        assert_eq!(subscription.funds, coins(1_000000, "unoisx"));
+
+        // Without a usable gateway channel, the next request fails too
+        let msg = ExecuteMsg::SetJobIdRetention { retention: None };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, subscribe("lottery-")).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(30));
+        let init = mock_ibc_channel_open_init("channel-13", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), init).unwrap();
+        let ack = mock_ibc_channel_connect_ack("channel-13", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), ack).unwrap();
+        let msg = mock_ibc_channel_close_confirm("channel-12", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+        let res = deliver(deps.as_mut(), "channel-13", "lottery-1");
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, REPLAY_ID_CALLBACK);
+        assert_eq!(
+            res.events,
+            [stalled_event("lottery-", ContractError::UnsetChannel)]
+        );
+        let subscription = SUBSCRIPTIONS
+            .load(&deps.storage, (&dapp, "lottery-"))
+            .unwrap();
+        assert_eq!(subscription.requested, 1);
+        assert_eq!(subscription.pending_job, None);
+
+        // Stalled subscriptions can be cancelled
+        let msg = ExecuteMsg::CancelSubscription {
+            job_id_prefix: "lottery-".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            [SubMsg::new(BankMsg::Send {
+                to_address: dapp.to_string(),
+                amount: coins(1_000000, "unoisx"),
+            })]
+        );
     }
 
     #[test]
-    fn query_is_allowed_works_when_allowlist_enabled() {
-        let addr_in_allowlist = addr("addr1");
-        let addr_not_in_allowlist = addr("addr2");
-        let deps = setup(Some(InstantiateMsg {
-            manager: Some(addr(CREATOR).to_string()),
-            prices: vec![coin(1_000000, "unoisx")],
-            test_mode: Some(true),
-            callback_gas_limit: 500_000,
-            mode: OperationalMode::Funded {},
-            allowlist_enabled: Some(true),
-            allowlist: Some(vec![addr_in_allowlist.to_string()]),
-        }));
+    fn set_config_works() {
+        let mut deps = setup(None);
 
-        // expect the address IN allow list to return true
-        let IsAllowlistedResponse { listed } = from_json(
-            query(
-                deps.as_ref(),
-                mock_env(),
-                QueryMsg::IsAllowlisted {
-                    address: addr_in_allowlist.to_string(),
-                },
-            )
-            .unwrap(),
-        )
-        .unwrap();
+        let creator = deps.api.addr_make(CREATOR);
+
+        // Check original config
+        let ConfigResponse { config: original } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(original.manager, Some(creator.clone()));
+        assert_eq!(original.callback_gas_limit, 500_000);
+        assert_eq!(original.allowlist_enabled, Some(false));
+
+        // Update nothing
+        let msg = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: None,
+            nois_beacon_price: None,
+            callback_gas_limit: None,
+            mode: None,
+            allowlist_enabled: None,
+            min_after: None,
+            max_after: None,
+            refund_policy: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let ConfigResponse { config } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config, original);
+
+        // Set allowlist_enabled to true
+        let msg = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: None,
+            nois_beacon_price: None,
+            callback_gas_limit: None,
+            mode: None,
+            allowlist_enabled: Some(true),
+            min_after: None,
+            max_after: None,
+            refund_policy: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let ConfigResponse { config } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        // Updated
+        assert_eq!(config.allowlist_enabled, Some(true));
+        // Rest unchanged
+        assert_eq!(config.prices, original.prices);
+        assert_eq!(config.manager, original.manager);
+        assert_eq!(config.mode, original.mode);
+        assert_eq!(config.payment, original.payment);
+
+        // Set allowlist_enabled to false
+        let msg = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: None,
+            nois_beacon_price: None,
+            callback_gas_limit: None,
+            mode: None,
+            allowlist_enabled: Some(false),
+            min_after: None,
+            max_after: None,
+            refund_policy: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let ConfigResponse { config } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        // Updated
+        assert_eq!(config.allowlist_enabled, Some(false));
+        // Rest unchanged
+        assert_eq!(config.prices, original.prices);
+        assert_eq!(config.manager, original.manager);
+        assert_eq!(config.mode, original.mode);
+        assert_eq!(config.payment, original.payment);
+
+        // Update callback_gas_limit
+        let msg = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: None,
+            nois_beacon_price: None,
+            callback_gas_limit: Some(800_000),
+            mode: None,
+            allowlist_enabled: None,
+            min_after: None,
+            max_after: None,
+            refund_policy: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let ConfigResponse { config } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        // Updated
+        assert_eq!(config.callback_gas_limit, 800_000);
+        // Rest unchanged
+        assert_eq!(config.prices, original.prices);
+        assert_eq!(config.manager, original.manager);
+        assert_eq!(config.mode, original.mode);
+        assert_eq!(config.payment, original.payment);
+    }
+
+    #[test]
+    fn set_config_only_allowed_for_manager() {
+        let mut deps = mock_dependencies();
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let creator = deps.api.addr_make(CREATOR);
+
+        let msg = InstantiateMsg {
+            manager: Some(creator.to_string()),
+            prices: vec![coin(1_000000, "unoisx")],
+            test_mode: None,
+            callback_gas_limit: 500_000,
+            mode: OperationalMode::Funded {},
+            allowlist_enabled: None,
+            allowlist: None,
+        };
+        let info = message_info(&creator, &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Edit config
+        let msg = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: None,
+            nois_beacon_price: None,
+            callback_gas_limit: None,
+            mode: None,
+            allowlist_enabled: Some(false),
+            min_after: None,
+            max_after: None,
+            refund_policy: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+    }
+
+    #[test]
+    fn withdraw_works() {
+        let mut deps = setup(None);
+
+        let creator = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let some = deps.api.addr_make("some-address");
+
+        let msg = ExecuteMsg::Withdraw {
+            denom: "unoisx".to_string(),
+            amount: Some(Uint128::new(12)),
+            address: some.to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        let res = execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: some.to_string(),
+                amount: coins(12, "unoisx"),
+            })
+        );
+    }
+
+    #[test]
+    fn withdraw_all_works() {
+        let mut deps = setup(None);
+
+        let creator = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let some = deps.api.addr_make("some-address");
+
+        let msg = ExecuteMsg::Withdraw {
+            denom: "unoisx".to_string(),
+            amount: None,
+            address: some.to_string(),
+        };
+
+        // Withdraw by dapp
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // Withdraw by manager
+        let res = execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: some.to_string(),
+                amount: coins(22334455, "unoisx"),
+            })
+        );
+    }
+
+    #[test]
+    fn withdraw_keeps_funds_reserved_for_dapps() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let creator = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let partner = deps.api.addr_make("partner");
+        let some = deps.api.addr_make("some-address");
+
+        let withdraw = |amount: Option<u128>| ExecuteMsg::Withdraw {
+            denom: "unoisx".to_string(),
+            amount: amount.map(Uint128::new),
+            address: some.to_string(),
+        };
+        let withdrawn = |res: Response| match &res.messages[0].msg {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount.clone(),
+            msg => panic!("Unexpected message: {msg:?}"),
+        };
+
+        // Credit and the funds of a request in flight are reserved
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "foo".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let msg = ExecuteMsg::TopUp {
+            beneficiary: dapp.to_string(),
+        };
+        let info = message_info(&dapp, &coins(2_500000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            withdraw(Some(18834456)),
+        )
+        .unwrap_err();
+        match err {
+            ContractError::InsufficientWithdrawableBalance { withdrawable } => {
+                assert_eq!(withdrawable, coin(18834455, "unoisx"))
+            }
+            err => panic!("Unexpected error: {err:?}"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            withdraw(None),
+        )
+        .unwrap();
+        assert_eq!(withdrawn(res), coins(18834455, "unoisx"));
+
+        // Once the request is queued, its funds are not refundable anymore
+        let ack = StdAck::success(
+            to_json_binary(&InPacketAck::RequestQueued {
+                source_id: "drand:mainnet:123".to_string(),
+            })
+            .unwrap(),
+        );
+        let packet = InPacket::RequestBeacon {
+            after: Timestamp::from_seconds(1666343642),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
+        let msg = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            withdraw(None),
+        )
+        .unwrap();
+        assert_eq!(withdrawn(res), coins(19834455, "unoisx"));
+
+        // Withdrawn credit is not reserved anymore
+        let msg = ExecuteMsg::WithdrawCredits {
+            address: dapp.to_string(),
+            denom: "unoisx".to_string(),
+            amount: Some(Uint128::new(500000)),
+            recipient: dapp.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            withdraw(None),
+        )
+        .unwrap();
+        assert_eq!(withdrawn(res), coins(20334455, "unoisx"));
+
+        // Top ups accept the denoms of a price override
+        let msg = ExecuteMsg::SetPriceOverride {
+            address: partner.to_string(),
+            prices: Some(coins(100000, "ucosm")),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let msg = ExecuteMsg::TopUp {
+            beneficiary: partner.to_string(),
+        };
+        let info = message_info(&partner, &coins(300000, "ucosm"));
+        execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        let info = message_info(&partner, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTopUp));
+    }
+
+    #[test]
+    fn withdraw_when_manager_is_not_set_withdraws_are_unauthorised() {
+        // Check that if manager not set, a random person cannot execute manager-like operations.
+        let mut deps = mock_dependencies();
+
+        let creator = deps.api.addr_make(CREATOR);
+        let manager = deps.api.addr_make(MANAGER_ADDRESS);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let some = deps.api.addr_make("some-address");
+
+        let msg = InstantiateMsg {
+            manager: None,
+            prices: vec![coin(1_000000, "unoisx")],
+            test_mode: None,
+            callback_gas_limit: 500_000,
+            mode: OperationalMode::Funded {},
+            allowlist_enabled: None,
+            allowlist: None,
+        };
+        let info = message_info(&creator, &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // withdraw
+        let msg = ExecuteMsg::Withdraw {
+            denom: "unoisx".to_string(),
+            amount: Some(Uint128::new(12)),
+            address: some.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // withdraw all
+        let msg = ExecuteMsg::Withdraw {
+            denom: "unoisx".to_string(),
+            amount: None,
+            address: some.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // Propose manager
+        let msg = ExecuteMsg::ProposeManager {
+            manager: manager.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+    }
+
+    #[test]
+    fn manager_transfer_works() {
+        let mut deps = setup(None);
+
+        let creator = deps.api.addr_make(CREATOR);
+        let manager = deps.api.addr_make(MANAGER_ADDRESS);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+
+        let PendingManagerResponse { pending_manager } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending_manager, None);
+
+        // Only manager can propose
+        let msg = ExecuteMsg::ProposeManager {
+            manager: manager.to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+
+        let PendingManagerResponse { pending_manager } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending_manager, Some(manager.clone()));
+
+        // Only pending manager can accept
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ManagerTransfer(ManagerTransferError::NotPendingManager)
+        ));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap();
+
+        let ConfigResponse { config } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.manager, Some(manager.clone()));
+
+        // Previous manager cannot cancel, new manager can
+        let msg = ExecuteMsg::ProposeManager {
+            manager: dapp.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            ExecuteMsg::CancelManagerTransfer {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            ExecuteMsg::CancelManagerTransfer {},
+        )
+        .unwrap();
+        let PendingManagerResponse { pending_manager } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::PendingManager {}).unwrap())
+                .unwrap();
+        assert_eq!(pending_manager, None);
+    }
+
+    #[test]
+    fn update_allowlist_works() {
+        let mut deps = setup(None);
+
+        let creator = deps.api.addr_make(CREATOR);
+        let aaa = deps.api.addr_make("aaa");
+        let bbb = deps.api.addr_make("bbb");
+        let ccc = deps.api.addr_make("ccc");
+
+        let msg = ExecuteMsg::UpdateAllowlist {
+            add: vec![aaa.to_string(), ccc.to_string()],
+            remove: vec![aaa.to_string(), bbb.to_string()],
+        };
+
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+
+        assert!(!ALLOWLIST.has(&deps.storage, &bbb));
+        assert!(ALLOWLIST.has(&deps.storage, &ccc));
+
+        // If an address is both added and removed, err on the side or removing it,
+        // hence, here we check that "aaa" is indeed not found.
+        assert!(!ALLOWLIST.has(&deps.storage, &aaa));
+    }
+
+    #[test]
+    fn update_allowlist_fails_for_non_manager() {
+        let mut deps = setup(None);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+
+        let msg = ExecuteMsg::UpdateAllowlist {
+            add: vec!["aaa".to_owned(), "ccc".to_owned()],
+            remove: vec!["aaa".to_owned(), "bbb".to_owned()],
+        };
+
+        let err = execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+    }
+
+    #[test]
+    fn denylist_works() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let creator = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let other = deps.api.addr_make("other");
+
+        fn is_denylisted(deps: Deps, address: &Addr) -> bool {
+            let IsDenylistedResponse { listed } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::IsDenylisted {
+                        address: address.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            listed
+        }
+
+        // Only the manager can update the denylist
+        let msg = ExecuteMsg::UpdateDenylist {
+            add: vec![dapp.to_string(), other.to_string()],
+            remove: vec![],
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        assert!(is_denylisted(deps.as_ref(), &dapp));
+        assert!(is_denylisted(deps.as_ref(), &other));
+        assert!(!is_denylisted(deps.as_ref(), &creator));
+
+        let DenylistResponse { denied } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Denylist {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(denied.len(), 2);
+        let DenylistResponse { denied: page } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Denylist {
+                    start_after: Some(denied[0].clone()),
+                    limit: Some(10),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(page, denied[1..]);
+
+        // Denylisted dapps cannot get randomness while the allowlist is disabled ...
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "foo".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::SenderDenylisted));
+
+        // ... and while the allowlist is enabled, even if they are allowlisted
+        let update_allowlist = ExecuteMsg::UpdateAllowlist {
+            add: vec![dapp.to_string()],
+            remove: vec![],
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            update_allowlist,
+        )
+        .unwrap();
+        let enable_allowlist = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: None,
+            nois_beacon_price: None,
+            callback_gas_limit: None,
+            mode: None,
+            allowlist_enabled: Some(true),
+            min_after: None,
+            max_after: None,
+            refund_policy: None,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            enable_allowlist,
+        )
+        .unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::SenderDenylisted));
+
+        // Removed from the denylist
+        let msg_remove = ExecuteMsg::UpdateDenylist {
+            add: vec![],
+            remove: vec![dapp.to_string()],
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&creator, &[]),
+            msg_remove,
+        )
+        .unwrap();
+        assert!(!is_denylisted(deps.as_ref(), &dapp));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    //
+    // Query tests
+    //
+
+    #[test]
+    fn query_prices_works() {
+        let deps = setup(None);
+
+        let PricesResponse { prices } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Prices { address: None },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(prices, coins(1000000, "unoisx"));
+    }
+
+    #[test]
+    fn query_price_works() {
+        let deps = setup(None);
+
+        let PriceResponse { price } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Price {
+                    denom: "shitcoin".to_string(),
+                    address: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(price, None);
+
+        let PriceResponse { price } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Price {
+                    denom: "unoisx".to_string(),
+                    address: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(price, Some(Uint128::new(1000000)));
+    }
+
+    #[test]
+    fn price_overrides_and_volume_tiers_work() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let partner = deps.api.addr_make("partner");
+        let creator = deps.api.addr_make(CREATOR);
+
+        fn prices(deps: Deps, address: &Addr) -> Vec<Coin> {
+            let PricesResponse { prices } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::Prices {
+                        address: Some(address.to_string()),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            prices
+        }
+
+        // Only the manager can set tiers and overrides
+        let tiers = vec![
+            VolumeTier {
+                min_requests: 2,
+                discount: Decimal::percent(10),
+            },
+            VolumeTier {
+                min_requests: 3,
+                discount: Decimal::percent(50),
+            },
+        ];
+        let msg = ExecuteMsg::SetVolumeTiers {
+            tiers: tiers.clone(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let VolumeTiersResponse { tiers: queried } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::VolumeTiers {}).unwrap()).unwrap();
+        assert_eq!(queried, tiers);
+
+        let msg = ExecuteMsg::SetPriceOverride {
+            address: partner.to_string(),
+            prices: Some(coins(100000, "unoisx")),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&partner, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        assert_eq!(prices(deps.as_ref(), &partner), coins(100000, "unoisx"));
+        assert_eq!(prices(deps.as_ref(), &dapp), coins(1_000000, "unoisx"));
+
+        // The override is charged
+        let msg = |job_id: &str| ExecuteMsg::GetNextRandomness {
+            job_id: job_id.to_string(),
+        };
+        let info = message_info(&partner, &coins(100000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg("foo1")).unwrap();
+
+        // The discount depends on the number of requests
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg("foo2")).unwrap();
+        assert_eq!(prices(deps.as_ref(), &dapp), coins(1_000000, "unoisx"));
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg("foo3")).unwrap();
+        assert_eq!(prices(deps.as_ref(), &dapp), coins(900000, "unoisx"));
+        let info = message_info(&dapp, &coins(899999, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg("foo4")).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientPayment));
+        let info = message_info(&dapp, &coins(900000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg("foo5")).unwrap();
+        assert_eq!(prices(deps.as_ref(), &dapp), coins(500000, "unoisx"));
+        let PriceResponse { price } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Price {
+                    denom: "unoisx".to_string(),
+                    address: Some(dapp.to_string()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(price, Some(Uint128::new(500000)));
+
+        // Tiers do not apply to overrides. Removing the override restores the tier price.
+        assert_eq!(prices(deps.as_ref(), &partner), coins(100000, "unoisx"));
+        let msg = ExecuteMsg::SetPriceOverride {
+            address: partner.to_string(),
+            prices: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        assert_eq!(prices(deps.as_ref(), &partner), coins(1_000000, "unoisx"));
+
+        // Invalid tiers
+        let msg = ExecuteMsg::SetVolumeTiers {
+            tiers: vec![tiers[1].clone(), tiers[0].clone()],
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidVolumeTiers));
+    }
+
+    #[test]
+    fn query_allowlist_works() {
+        // some list
+        let addr_in_allowlist = vec![addr("addr2").to_string(), addr("addr1").to_string()];
+        let deps = setup(Some(InstantiateMsg {
+            manager: Some(addr(CREATOR).to_string()),
+            prices: vec![coin(1_000000, "unoisx")],
+            test_mode: Some(true),
+            callback_gas_limit: 500_000,
+            mode: OperationalMode::Funded {},
+            allowlist_enabled: Some(true),
+            allowlist: Some(addr_in_allowlist),
+        }));
+
+        let AllowlistResponse { allowed } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Allowlist {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            allowed.iter().cloned().collect::<HashSet<_, _>>(),
+            HashSet::from([addr("addr1").to_string(), addr("addr2").to_string()])
+        );
+
+        // paginated
+        let AllowlistResponse { allowed: page1 } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Allowlist {
+                    start_after: None,
+                    limit: Some(1),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(page1, allowed[..1]);
+        let AllowlistResponse { allowed: page2 } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Allowlist {
+                    start_after: Some(page1[0].clone()),
+                    limit: Some(1),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(page2, allowed[1..]);
+
+        // empty list
+        let deps = setup(Some(InstantiateMsg {
+            manager: Some(addr(CREATOR).to_string()),
+            prices: vec![coin(1_000000, "unoisx")],
+            test_mode: Some(true),
+            callback_gas_limit: 500_000,
+            mode: OperationalMode::Funded {},
+            allowlist_enabled: Some(true),
+            allowlist: Some(vec![]),
+        }));
+
+        let AllowlistResponse { allowed } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Allowlist {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(allowed.is_empty());
+    }
+
+    #[test]
+    fn query_is_allowed_works_when_allowlist_enabled() {
+        let addr_in_allowlist = addr("addr1");
+        let addr_not_in_allowlist = addr("addr2");
+        let deps = setup(Some(InstantiateMsg {
+            manager: Some(addr(CREATOR).to_string()),
+            prices: vec![coin(1_000000, "unoisx")],
+            test_mode: Some(true),
+            callback_gas_limit: 500_000,
+            mode: OperationalMode::Funded {},
+            allowlist_enabled: Some(true),
+            allowlist: Some(vec![addr_in_allowlist.to_string()]),
+        }));
+
+        // expect the address IN allow list to return true
+        let IsAllowlistedResponse { listed } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::IsAllowlisted {
+                    address: addr_in_allowlist.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(listed);
+
+        // expect the address NOT in allow list to return false
+        let IsAllowlistedResponse { listed } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::IsAllowlisted {
+                    address: addr_not_in_allowlist.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(!listed);
+    }
+
+    #[test]
+    fn query_is_allowed_works_when_allowlist_disabled() {
+        let creator = addr(CREATOR);
+        let addr_in_allowlist = addr("addr1");
+        let addr_not_in_allowlist = addr("addr2");
+        let deps = setup(Some(InstantiateMsg {
+            manager: Some(creator.to_string()),
+            prices: vec![coin(1_000000, "unoisx")],
+            test_mode: Some(true),
+            callback_gas_limit: 500_000,
+            mode: OperationalMode::Funded {},
+            allowlist_enabled: Some(false),
+            allowlist: Some(vec![addr_in_allowlist.to_string()]),
+        }));
+
+        // expect the address IN allow list to return true
+        let IsAllowlistedResponse { listed } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::IsAllowlisted {
+                    address: addr_in_allowlist.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
         assert!(listed);
 
-        // expect the address NOT in allow list to return false
-        let IsAllowlistedResponse { listed } = from_json(
-            query(
-                deps.as_ref(),
+        // Expect the address NOT in allowlist to return false even if allowlist is not enabled.
+        let IsAllowlistedResponse { listed } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::IsAllowlisted {
+                    address: addr_not_in_allowlist.to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(!listed);
+    }
+
+    //
+    // IBC tests
+    //
+
+    #[test]
+    fn ibc_channel_open_checks_version_and_order() {
+        let mut deps = setup(None);
+
+        // All good
+        let valid_handshake = mock_ibc_channel_open_init("channel-12", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), valid_handshake).unwrap();
+
+        // Wrong order
+        let wrong_order = mock_ibc_channel_open_init("channel-12", BAD_APP_ORDER, IBC_APP_VERSION);
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), wrong_order).unwrap_err();
+        assert!(matches!(res, ContractError::ChannelError(..)));
+
+        // Wrong version
+        let wrong_version = mock_ibc_channel_open_init("channel-12", APP_ORDER, "another version");
+        let res = ibc_channel_open(deps.as_mut(), mock_env(), wrong_version).unwrap_err();
+        assert!(matches!(res, ContractError::ChannelError(..)));
+    }
+
+    #[test]
+    fn ibc_channel_connect_works() {
+        // We are chain A and get the ChanOpenAck
+
+        let mut deps = setup(None);
+
+        // Channel is unset
+        let GatewayChannelResponse { channel, version } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
+                .unwrap();
+        assert_eq!(channel, None);
+        assert_eq!(version, None);
+
+        // Gateway answers with a version we do not support
+        let msg = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, "nois-v9");
+        let err = ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::ChannelError(..)));
+
+        let msg = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, IBC_APP_VERSION_V7);
+        ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+
+        // Channel is now set
+        let GatewayChannelResponse { channel, version } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
+                .unwrap();
+        assert_eq!(channel, Some("channel-12".to_string()));
+        assert_eq!(version, Some("nois-v7".to_string()));
+
+        // One more ChanOpenAck
+        let msg = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, IBC_APP_VERSION);
+        let err = ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::ChannelAlreadySet));
+
+        // ChanOpenConfirm is rejected
+        let msg = mock_ibc_channel_connect_confirm("channel-12", APP_ORDER, IBC_APP_VERSION);
+        let err = ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::MustBeChainA));
+    }
+
+    #[test]
+    fn ibc_channel_close_works() {
+        let mut deps = setup(None);
+
+        // Open
+        let valid_handshake = mock_ibc_channel_open_init("channel-12", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), valid_handshake).unwrap();
+
+        // Connect
+        let msg = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+
+        // Channel is now set
+        let GatewayChannelResponse { channel, .. } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
+                .unwrap();
+        assert_eq!(channel, Some("channel-12".to_string()));
+
+        // Closing channel fails
+        let msg = mock_ibc_channel_close_init("channel-12", APP_ORDER, IBC_APP_VERSION);
+        let err = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::ChannelMustNotBeClosed));
+
+        // Channel is still set
+        let GatewayChannelResponse { channel, .. } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
+                .unwrap();
+        assert_eq!(channel, Some("channel-12".to_string()));
+
+        // The other side closed
+        let msg = mock_ibc_channel_close_confirm("channel-12", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+
+        // Channel is unset
+        let GatewayChannelResponse { channel, .. } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
+                .unwrap();
+        assert_eq!(channel, None);
+    }
+
+    #[test]
+    fn pause_works() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+        let manager = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+
+        fn query_paused(deps: Deps) -> PausedResponse {
+            from_json(query(deps, mock_env(), QueryMsg::Paused {}).unwrap()).unwrap()
+        }
+
+        assert_eq!(
+            query_paused(deps.as_ref()),
+            PausedResponse {
+                paused: false,
+                reason: None
+            }
+        );
+
+        // Request before pausing
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "foo".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+
+        // Only the manager can pause
+        let msg = ExecuteMsg::Pause {
+            reason: "Upgrading the gateway".to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        let res = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "execute_pause"
+        );
+        assert_eq!(first_attr(&res.attributes, "paused").unwrap(), "true");
+        assert_eq!(
+            query_paused(deps.as_ref()),
+            PausedResponse {
+                paused: true,
+                reason: Some("Upgrading the gateway".to_string())
+            }
+        );
+
+        // New requests are rejected
+        let msgs = [
+            ExecuteMsg::GetNextRandomness {
+                job_id: "bar".to_string(),
+            },
+            ExecuteMsg::GetRandomnessBatch {
+                requests: vec![BatchRandomnessRequest {
+                    job_id: "bar".to_string(),
+                    after: None,
+                }],
+            },
+            ExecuteMsg::Subscribe {
+                interval: 60,
+                count: 1,
+                job_id_prefix: "bar".to_string(),
+            },
+        ];
+        for msg in msgs {
+            let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+            let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+            match err {
+                ContractError::Paused { reason } => assert_eq!(reason, "Upgrading the gateway"),
+                err => panic!("Unexpected error: {:?}", err),
+            }
+        }
+
+        // Prices are still visible
+        let PricesResponse { prices } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Prices { address: None },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(prices, coins(1_000000, "unoisx"));
+
+        // Earlier requests are still delivered
+        let packet = OutPacket::DeliverBeacon {
+            source_id: "drand:mainnet:123".to_string(),
+            published: mock_env().block.time,
+            randomness: HexBinary::from([0xaa; 32]),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
+        let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "receive_deliver_beacon"
+        );
+        assert_eq!(res.messages.len(), 1);
+
+        // Unpause
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            ExecuteMsg::Unpause {},
+        )
+        .unwrap();
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "execute_unpause"
+        );
+        assert_eq!(first_attr(&res.attributes, "paused").unwrap(), "false");
+        assert!(!query_paused(deps.as_ref()).paused);
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "bar".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    #[test]
+    fn gateway_channel_failover_works() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+        let manager = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+
+        fn query_channels(deps: Deps, env: Env) -> Vec<GatewayChannelStatus> {
+            let GatewayChannelsResponse { channels } = from_json(
+                query(
+                    deps,
+                    env,
+                    QueryMsg::GatewayChannels {
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            channels
+        }
+
+        /// Requests a beacon that is sent with the given sequence and returns the channel
+        fn request(
+            mut deps: DepsMut,
+            env: Env,
+            dapp: &Addr,
+            job_id: &str,
+            sequence: u64,
+        ) -> String {
+            let msg = ExecuteMsg::GetNextRandomness {
+                job_id: job_id.to_string(),
+            };
+            let info = message_info(dapp, &coins(1_000000, "unoisx"));
+            let res = execute(deps.branch(), env, info, msg).unwrap();
+            send_packet_reply(deps, &res.messages, Some(sequence));
+            match &res.messages[0].msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { channel_id, .. }) => channel_id.clone(),
+                m => panic!("Unexpected message: {m:?}"),
+            }
+        }
+
+        // A second channel is connected but not enabled
+        let init = mock_ibc_channel_open_init("channel-13", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), init).unwrap();
+        let ack = mock_ibc_channel_connect_ack("channel-13", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), ack).unwrap();
+        let channels = query_channels(deps.as_ref(), mock_env());
+        assert_eq!(
+            channels,
+            [
+                GatewayChannelStatus {
+                    channel_id: "channel-12".to_string(),
+                    version: IBC_APP_VERSION.to_string(),
+                    enabled: true,
+                    primary: true,
+                    healthy: true,
+                    pending_packets: 0,
+                    last_ack: None,
+                    payment: None,
+                },
+                GatewayChannelStatus {
+                    channel_id: "channel-13".to_string(),
+                    version: IBC_APP_VERSION.to_string(),
+                    enabled: false,
+                    primary: false,
+                    healthy: true,
+                    pending_packets: 0,
+                    last_ack: None,
+                    payment: None,
+                },
+            ]
+        );
+
+        // Connecting the same channel again fails
+        let ack = mock_ibc_channel_connect_ack("channel-13", APP_ORDER, IBC_APP_VERSION);
+        let err = ibc_channel_connect(deps.as_mut(), mock_env(), ack).unwrap_err();
+        assert!(matches!(err, ContractError::ChannelAlreadySet));
+
+        // A disabled channel learns its payment contract
+        let packet = OutPacket::Welcome {
+            payment: "nois1payment13".to_string(),
+        };
+        let msg = mock_ibc_packet_recv("channel-13", &packet).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "receive_welcome"
+        );
+        let channels = query_channels(deps.as_ref(), mock_env());
+        assert_eq!(channels[0].payment, None);
+        assert_eq!(channels[1].payment, Some("nois1payment13".to_string()));
+
+        // Packets changing the global proxy state from a disabled channel are rejected
+        let packet = OutPacket::PushBeaconPrice {
+            timestamp: Timestamp::from_seconds(1666343642),
+            amount: Uint128::new(1_000000),
+            denom: "unois".to_string(),
+        };
+        let msg = mock_ibc_packet_recv("channel-13", &packet).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let ack = PacketError::new(
+            PacketErrorCode::Other,
+            format!(
+                "Error processing packet: {}",
+                ContractError::GatewayChannelDisabled
+            ),
+        )
+        .to_ack();
+        assert_eq!(res.acknowledgement, Some(ack.to_binary()));
+
+        // Disabled channels cannot become primary
+        let msg = ExecuteMsg::SetPrimaryGatewayChannel {
+            channel_id: "channel-13".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::GatewayChannelDisabled));
+
+        // Only the manager can enable channels
+        let msg = ExecuteMsg::UpdateGatewayChannel {
+            channel_id: "channel-13".to_string(),
+            enabled: true,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let msg = ExecuteMsg::UpdateGatewayChannel {
+            channel_id: "channel-99".to_string(),
+            enabled: true,
+        };
+        let err = execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap_err();
+        assert!(
+            matches!(err, ContractError::UnknownGatewayChannel { channel_id } if channel_id == "channel-99")
+        );
+
+        // Requests use the primary channel
+        assert_eq!(
+            request(deps.as_mut(), mock_env(), &dapp, "1", 29),
+            "channel-12"
+        );
+        let channels = query_channels(deps.as_ref(), mock_env());
+        assert_eq!(channels[0].pending_packets, 1);
+        assert!(channels[0].healthy);
+
+        // Without progress on the primary channel, requests fail over to channel-13
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(GATEWAY_CHANNEL_STALL_TIMEOUT);
+        assert!(!query_channels(deps.as_ref(), env.clone())[0].healthy);
+        assert_eq!(
+            request(deps.as_mut(), env.clone(), &dapp, "2", 29),
+            "channel-13"
+        );
+
+        // Once the primary channel makes progress, it is used again
+        let ack = StdAck::success(
+            to_json_binary(&InPacketAck::RequestQueued {
+                source_id: "drand:mainnet:123".to_string(),
+            })
+            .unwrap(),
+        );
+        let packet = InPacket::RequestBeacon {
+            after: Timestamp::from_seconds(1666343642),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "1".to_string(),
+            })
+            .unwrap(),
+        };
+        let msg = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        let channels = query_channels(deps.as_ref(), env.clone());
+        assert_eq!(channels[0].pending_packets, 0);
+        assert_eq!(channels[0].last_ack, Some(env.block.time));
+        assert!(channels[0].healthy);
+        assert_eq!(channels[1].pending_packets, 1);
+        assert_eq!(channels[1].last_ack, None);
+        assert_eq!(
+            request(deps.as_mut(), env.clone(), &dapp, "3", 30),
+            "channel-12"
+        );
+
+        // Beacons of requests sent before a channel was disabled are still delivered
+        let set_enabled = |enabled: bool| ExecuteMsg::UpdateGatewayChannel {
+            channel_id: "channel-13".to_string(),
+            enabled,
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&manager, &[]),
+            set_enabled(false),
+        )
+        .unwrap();
+        let packet = OutPacket::DeliverBeacon {
+            source_id: "drand:mainnet:123".to_string(),
+            published: env.block.time,
+            randomness: HexBinary::from([0xaa; 32]),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "2".to_string(),
+            })
+            .unwrap(),
+        };
+        let msg = mock_ibc_packet_recv("channel-13", &packet).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(
+            first_attr(&res.attributes, "action").unwrap(),
+            "receive_deliver_beacon"
+        );
+
+        // Packets from unknown channels are rejected
+        let msg = mock_ibc_packet_recv("channel-99", &packet).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+        let ack = PacketError::new(
+            PacketErrorCode::Other,
+            format!(
+                "Error processing packet: {}",
+                ContractError::UnknownGatewayChannel {
+                    channel_id: "channel-99".to_string()
+                }
+            ),
+        )
+        .to_ack();
+        assert_eq!(res.acknowledgement, Some(ack.to_binary()));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&manager, &[]),
+            set_enabled(true),
+        )
+        .unwrap();
+
+        // The manager can change the primary channel
+        let msg = ExecuteMsg::SetPrimaryGatewayChannel {
+            channel_id: "channel-13".to_string(),
+        };
+        execute(deps.as_mut(), env.clone(), message_info(&manager, &[]), msg).unwrap();
+        assert_eq!(
+            request(deps.as_mut(), env.clone(), &dapp, "4", 30),
+            "channel-13"
+        );
+
+        // The primary channel cannot be disabled
+        let msg = ExecuteMsg::UpdateGatewayChannel {
+            channel_id: "channel-13".to_string(),
+            enabled: false,
+        };
+        let err =
+            execute(deps.as_mut(), env.clone(), message_info(&manager, &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::CannotDisablePrimaryChannel));
+
+        // Closing the primary channel promotes the other enabled channel
+        let msg = mock_ibc_channel_close_confirm("channel-13", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_close(deps.as_mut(), env.clone(), msg).unwrap();
+        let GatewayChannelResponse { channel, version } =
+            from_json(query(deps.as_ref(), env.clone(), QueryMsg::GatewayChannel {}).unwrap())
+                .unwrap();
+        assert_eq!(channel, Some("channel-12".to_string()));
+        assert_eq!(version, Some(IBC_APP_VERSION.to_string()));
+        let channels = query_channels(deps.as_ref(), env);
+        assert_eq!(channels.len(), 1);
+        assert!(channels[0].primary);
+    }
+
+    #[test]
+    fn ibc_pay_uses_payment_contract_of_request_channel() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+        let manager = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+
+        let msg = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: None,
+            nois_beacon_price: Some(Uint128::new(50_000000)),
+            callback_gas_limit: None,
+            mode: Some(OperationalMode::IbcPay {
+                unois_denom: IbcDenom {
+                    ics20_channel: "channel-1".to_string(),
+                    denom: "ibc/unois".to_string(),
+                },
+            }),
+            allowlist_enabled: None,
+            min_after: None,
+            max_after: None,
+            refund_policy: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        // Every channel has its own payment contract
+        let init = mock_ibc_channel_open_init("channel-13", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_open(deps.as_mut(), mock_env(), init).unwrap();
+        let ack = mock_ibc_channel_connect_ack("channel-13", APP_ORDER, IBC_APP_VERSION);
+        ibc_channel_connect(deps.as_mut(), mock_env(), ack).unwrap();
+        for channel_id in ["channel-12", "channel-13"] {
+            let packet = OutPacket::Welcome {
+                payment: format!("nois1payment-{channel_id}"),
+            };
+            let msg = mock_ibc_packet_recv(channel_id, &packet).unwrap();
+            ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::UpdateGatewayChannel {
+            channel_id: "channel-13".to_string(),
+            enabled: true,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+
+        // Returns the payment contract and the channel of the request
+        fn request(
+            mut deps: DepsMut,
+            env: Env,
+            dapp: &Addr,
+            job_id: &str,
+            sequence: u64,
+        ) -> (String, String) {
+            let msg = ExecuteMsg::GetNextRandomness {
+                job_id: job_id.to_string(),
+            };
+            let info = message_info(dapp, &coins(1_000000, "unoisx"));
+            let res = execute(deps.branch(), env, info, msg).unwrap();
+            send_packet_reply(deps, &res.messages, Some(sequence));
+            let CosmosMsg::Ibc(IbcMsg::Transfer { to_address, .. }) = &res.messages[0].msg else {
+                panic!("Unexpected message: {:?}", res.messages[0].msg);
+            };
+            let CosmosMsg::Ibc(IbcMsg::SendPacket { channel_id, .. }) = &res.messages[1].msg else {
+                panic!("Unexpected message: {:?}", res.messages[1].msg);
+            };
+            (to_address.clone(), channel_id.clone())
+        }
+
+        assert_eq!(
+            request(deps.as_mut(), mock_env(), &dapp, "1", 29),
+            (
+                "nois1payment-channel-12".to_string(),
+                "channel-12".to_string()
+            )
+        );
+
+        // After failover the payment goes to the contract of the backup channel
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(GATEWAY_CHANNEL_STALL_TIMEOUT);
+        assert_eq!(
+            request(deps.as_mut(), env, &dapp, "2", 29),
+            (
+                "nois1payment-channel-13".to_string(),
+                "channel-13".to_string()
+            )
+        );
+
+        // The manager can correct the payment contract of the primary channel
+        let msg = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: Some("nois1newpayment".to_string()),
+            nois_beacon_price: None,
+            callback_gas_limit: None,
+            mode: None,
+            allowlist_enabled: None,
+            min_after: None,
+            max_after: None,
+            refund_policy: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let GatewayChannelsResponse { channels } = from_json(
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GatewayChannels {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(channels[0].payment, Some("nois1newpayment".to_string()));
+        assert_eq!(
+            channels[1].payment,
+            Some("nois1payment-channel-13".to_string())
+        );
+    }
+
+    /// Simulates the reply wasmd sends for the first `SendPacket` submessage in `messages`
+    #[allow(deprecated)]
+    fn send_packet_reply(deps: DepsMut, messages: &[SubMsg], sequence: Option<u64>) -> Response {
+        let payload = messages
+            .iter()
+            .find(|msg| msg.id == REPLAY_ID_REQUEST_BEACON)
+            .expect("No request packet submessage")
+            .payload
+            .clone();
+        let msg_responses = match sequence {
+            Some(sequence) => vec![MsgResponse {
+                type_url: "/cosmwasm.wasm.v1.MsgIBCSendResponse".to_string(),
+                value: Anybuf::new().append_uint64(1, sequence).into_vec().into(),
+            }],
+            None => vec![],
+        };
+        let msg = Reply {
+            id: REPLAY_ID_REQUEST_BEACON,
+            payload,
+            gas_used: 1234,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+                msg_responses,
+            }),
+        };
+        reply(deps, mock_env(), msg).unwrap()
+    }
+
+    #[test]
+    fn job_id_retention_works() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let other = deps.api.addr_make("other");
+        let creator = deps.api.addr_make(CREATOR);
+
+        let request = |job_id: &str| ExecuteMsg::GetNextRandomness {
+            job_id: job_id.to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+
+        fn deliver(deps: DepsMut, sender: &Addr, job_id: &str) {
+            let packet = OutPacket::DeliverBeacon {
+                source_id: "drand:mainnet:123".to_string(),
+                published: Timestamp::from_seconds(1666343642),
+                randomness: HexBinary::from_hex(
+                    "3436255a9f8a1f1aaa3cc1c02bc1dd87b4e8b5f6b34ee64a3bb4d1e7a7b9b1d0",
+                )
+                .unwrap(),
+                origin: to_json_binary(&RequestBeaconOrigin {
+                    sender: sender.to_string(),
+                    job_id: job_id.to_string(),
+                })
+                .unwrap(),
+            };
+            let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+            ibc_packet_receive(deps, mock_env(), msg).unwrap();
+        }
+
+        // Duplicates are allowed by default once the first job is done
+        execute(deps.as_mut(), mock_env(), info.clone(), request("foo")).unwrap();
+        deliver(deps.as_mut(), &dapp, "foo");
+        execute(deps.as_mut(), mock_env(), info.clone(), request("foo")).unwrap();
+
+        // but not while the job is in flight
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), request("foo")).unwrap_err();
+        assert!(matches!(err, ContractError::JobIdInFlight));
+        assert_eq!(
+            JOBS.load(&deps.storage, (&dapp, "foo")).unwrap().status,
+            JobStatus::Sent {}
+        );
+
+        // Only the manager can set the retention
+        let msg = ExecuteMsg::SetJobIdRetention {
+            retention: Some(100),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let JobIdRetentionResponse { retention } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::JobIdRetention {}).unwrap())
+                .unwrap();
+        assert_eq!(retention, Some(100));
+
+        // Duplicates of the same sender are rejected
+        execute(deps.as_mut(), mock_env(), info.clone(), request("bar")).unwrap();
+        deliver(deps.as_mut(), &dapp, "bar");
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), request("bar")).unwrap_err();
+        assert!(matches!(err, ContractError::JobIdAlreadyUsed));
+        let info_other = message_info(&other, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info_other, request("bar")).unwrap();
+
+        // Also in batches
+        let batch = |job_ids: &[&str]| ExecuteMsg::GetRandomnessBatch {
+            requests: job_ids
+                .iter()
+                .map(|job_id| BatchRandomnessRequest {
+                    job_id: job_id.to_string(),
+                    after: None,
+                })
+                .collect(),
+        };
+        let info_batch = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info_batch.clone(),
+            batch(&["bar", "baz"]),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::JobIdAlreadyUsed));
+
+        // Job IDs can be reused after the retention time. Expired entries are removed.
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        assert_eq!(USED_JOB_IDS_QUEUE.len(&deps.storage).unwrap(), 2);
+        execute(deps.as_mut(), env.clone(), info.clone(), request("bar")).unwrap();
+        assert_eq!(USED_JOB_IDS_QUEUE.len(&deps.storage).unwrap(), 1);
+        assert!(!USED_JOB_IDS.has(&deps.storage, (&other, "bar")));
+        deliver(deps.as_mut(), &dapp, "bar");
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), request("bar")).unwrap_err();
+        assert!(matches!(err, ContractError::JobIdAlreadyUsed));
+
+        // Duplicates within a batch
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info_batch,
+            batch(&["baz", "baz"]),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::JobIdAlreadyUsed));
+
+        // Disabled again
+        let msg = ExecuteMsg::SetJobIdRetention { retention: None };
+        execute(deps.as_mut(), env.clone(), message_info(&creator, &[]), msg).unwrap();
+        execute(deps.as_mut(), env.clone(), info, request("bar")).unwrap();
+
+        // Duplicates within a batch are still rejected since they would be in flight together
+        let info_batch = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let err = execute(deps.as_mut(), env, info_batch, batch(&["qux", "qux"])).unwrap_err();
+        assert!(matches!(err, ContractError::JobIdInFlight));
+    }
+
+    #[test]
+    fn jobs_work() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let other = deps.api.addr_make("other");
+        const SEQUENCE: u64 = 29;
+
+        fn job(deps: Deps, sender: &Addr, job_id: &str) -> Option<Job> {
+            let JobResponse { job } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::Job {
+                        sender: sender.to_string(),
+                        job_id: job_id.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            job
+        }
+
+        fn jobs_by_sender(deps: Deps, sender: &Addr) -> Vec<Job> {
+            let JobsResponse { jobs } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::JobsBySender {
+                        sender: sender.to_string(),
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            jobs
+        }
+
+        assert_eq!(job(deps.as_ref(), &dapp, "foo"), None);
+
+        // Request
+        let after = Timestamp::from_seconds(1666343642);
+        let msg = ExecuteMsg::GetRandomnessAfter {
+            after,
+            job_id: "foo".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(SEQUENCE));
+        let mut expected = Job {
+            sender: dapp.clone(),
+            job_id: "foo".to_string(),
+            after: Some(after),
+            round: None,
+            height: mock_env().block.height,
+            status: JobStatus::Sent {},
+            source_id: None,
+        };
+        assert_eq!(job(deps.as_ref(), &dapp, "foo"), Some(expected.clone()));
+
+        // Acknowledgement
+        let packet = InPacket::RequestBeacon {
+            after,
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
+        let ack = StdAck::success(
+            to_json_binary(&InPacketAck::RequestQueued {
+                source_id: "drand:mainnet:123".to_string(),
+            })
+            .unwrap(),
+        );
+        let msg = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        expected.status = JobStatus::Queued {};
+        expected.source_id = Some("drand:mainnet:123".to_string());
+        assert_eq!(job(deps.as_ref(), &dapp, "foo"), Some(expected.clone()));
+
+        // Listed by sender
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "bar".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(SEQUENCE));
+        let jobs = jobs_by_sender(deps.as_ref(), &dapp);
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].job_id, "bar");
+        assert_eq!(jobs[0].status, JobStatus::Sent {});
+        assert_eq!(jobs[1], expected);
+        assert_eq!(jobs_by_sender(deps.as_ref(), &other), []);
+
+        // Delivered jobs are removed
+        let packet = OutPacket::DeliverBeacon {
+            source_id: "drand:mainnet:123".to_string(),
+            published: Timestamp::from_seconds(1666343643),
+            randomness: HexBinary::from_hex(
+                "3436255a9f8a1f1aaa3cc1c02bc1dd87b4e8b5f6b34ee64a3bb4d1e7a7b9b1d0",
+            )
+            .unwrap(),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
+        let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(job(deps.as_ref(), &dapp, "foo"), None);
+        assert_eq!(jobs_by_sender(deps.as_ref(), &dapp).len(), 1);
+
+        // Failed jobs are removed
+        let packet = InPacket::RequestBeacon {
+            after: jobs[0].after.unwrap(),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "bar".to_string(),
+            })
+            .unwrap(),
+        };
+        let msg = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(jobs_by_sender(deps.as_ref(), &dapp), []);
+    }
+
+    #[test]
+    fn pending_jobs_are_tracked_by_packet_sequence() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        // Sequence used by mock_ibc_packet_ack and mock_ibc_packet_timeout
+        const SEQUENCE: u64 = 29;
+
+        let packet = InPacket::RequestBeacon {
+            after: Timestamp::from_seconds(1666343642),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
+        let msg = ExecuteMsg::GetRandomnessAfter {
+            after: Timestamp::from_seconds(1666343642),
+            job_id: "foo".to_string(),
+        };
+
+        // Request and error ack
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        let res = send_packet_reply(deps.as_mut(), &res.messages, Some(SEQUENCE));
+        assert_eq!(
+            first_attr(&res.attributes, "packet_sequence").unwrap(),
+            "29"
+        );
+        assert_eq!(
+            PENDING_JOBS
+                .load(&deps.storage, ("channel-12", SEQUENCE))
+                .unwrap(),
+            [PendingJob {
+                sender: dapp.clone(),
+                job_id: "foo".to_string(),
+                funds: coins(1_000000, "unoisx"),
+                paid_from_credit: false,
+            }]
+        );
+        let ack = StdAck::error("kaputt");
+        let msg_ack = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg_ack).unwrap();
+        assert_eq!(
+            res.events,
+            [Event::new("nois-failed-job")
+                .add_attribute("sender", dapp.as_str())
+                .add_attribute("job_id", "foo")]
+        );
+        assert!(!PENDING_JOBS.has(&deps.storage, ("channel-12", SEQUENCE)));
+
+        // Request and success ack
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(SEQUENCE));
+        let ack = StdAck::success(
+            to_json_binary(&InPacketAck::RequestQueued {
+                source_id: "backend:123:456".to_string(),
+            })
+            .unwrap(),
+        );
+        let msg_ack = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg_ack).unwrap();
+        assert!(res.events.is_empty());
+        assert!(!PENDING_JOBS.has(&deps.storage, ("channel-12", SEQUENCE)));
+
+        // Batch request and timeout
+        let batch = ExecuteMsg::GetRandomnessBatch {
+            requests: vec![
+                BatchRandomnessRequest {
+                    job_id: "bar".to_string(),
+                    after: None,
+                },
+                BatchRandomnessRequest {
+                    job_id: "baz".to_string(),
+                    after: None,
+                },
+            ],
+        };
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, batch).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(SEQUENCE));
+        assert_eq!(
+            PENDING_JOBS
+                .load(&deps.storage, ("channel-12", SEQUENCE))
+                .unwrap()
+                .len(),
+            2
+        );
+        let msg_timeout = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg_timeout).unwrap();
+        assert_eq!(
+            res.events,
+            [
+                Event::new("nois-failed-job")
+                    .add_attribute("sender", dapp.as_str())
+                    .add_attribute("job_id", "bar"),
+                Event::new("nois-failed-job")
+                    .add_attribute("sender", dapp.as_str())
+                    .add_attribute("job_id", "baz"),
+            ]
+        );
+        assert!(!PENDING_JOBS.has(&deps.storage, ("channel-12", SEQUENCE)));
+
+        // Without a sequence in the response the jobs cannot be tracked but the request succeeds
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "qux".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = send_packet_reply(deps.as_mut(), &res.messages, None);
+        assert_eq!(first_attr(&res.attributes, "packet_sequence"), None);
+        assert!(PENDING_JOBS.is_empty(&deps.storage));
+        assert!(!JOBS.has(&deps.storage, (&dapp, "qux")));
+    }
+
+    #[test]
+    fn failure_callbacks_work() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let other_dapp = deps.api.addr_make("other dapp");
+
+        fn enabled(deps: Deps, address: &Addr) -> bool {
+            let FailureCallbacksEnabledResponse { enabled } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::FailureCallbacksEnabled {
+                        address: address.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            enabled
+        }
+
+        assert!(!enabled(deps.as_ref(), &dapp));
+        let msg = ExecuteMsg::SetFailureCallbacks { enabled: true };
+        execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        assert!(enabled(deps.as_ref(), &dapp));
+        assert!(!enabled(deps.as_ref(), &other_dapp));
+
+        let packet = InPacket::RequestBeacon {
+            after: Timestamp::from_seconds(1666343642),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
+        let batch = ExecuteMsg::GetRandomnessBatch {
+            requests: vec![
+                BatchRandomnessRequest {
+                    job_id: "foo".to_string(),
+                    after: None,
+                },
+                BatchRandomnessRequest {
+                    job_id: "bar".to_string(),
+                    after: None,
+                },
+            ],
+        };
+        let expected_callback = |job_id: &str, reason: &str| {
+            SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: dapp.to_string(),
+                    msg: to_json_binary(&FailureReceiverExecuteMsg::NoisReceiveFailure {
+                        failure: NoisFailure {
+                            job_id: job_id.to_string(),
+                            reason: reason.to_string(),
+                        },
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                },
+                REPLAY_ID_FAILURE_CALLBACK,
+            )
+            .with_gas_limit(500_000)
+        };
+
+        // Error ack leads to one callback per job
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, batch.clone()).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let ack = PacketError::new(PacketErrorCode::Paused, "The gateway is paused.").to_ack();
+        let msg = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            [
+                expected_callback("foo", "The gateway is paused."),
+                expected_callback("bar", "The gateway is paused."),
+            ]
+        );
+
+        // Timeout
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, batch.clone()).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let msg = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            [
+                expected_callback("foo", "Request packet timed out"),
+                expected_callback("bar", "Request packet timed out"),
+            ]
+        );
+
+        // Dapps that did not opt in get no callback
+        let info = message_info(&other_dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, batch.clone()).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let msg = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.messages, []);
+        assert_eq!(res.events.len(), 2);
+
+        // Opt out
+        let msg = ExecuteMsg::SetFailureCallbacks { enabled: false };
+        execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        assert!(!enabled(deps.as_ref(), &dapp));
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, batch).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let msg = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.messages, []);
+    }
+
+    #[test]
+    fn callback_gas_limits_work() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let partner = deps.api.addr_make("partner");
+        let creator = deps.api.addr_make(CREATOR);
+
+        fn gas_limit(deps: Deps, address: &Addr) -> CallbackGasLimitResponse {
+            from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::CallbackGasLimit {
+                        address: address.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        }
+
+        fn prices(deps: Deps, address: &Addr) -> Vec<Coin> {
+            let PricesResponse { prices } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::Prices {
+                        address: Some(address.to_string()),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            prices
+        }
+
+        fn deliver(deps: DepsMut, sender: &Addr) -> IbcReceiveResponse {
+            let packet = OutPacket::DeliverBeacon {
+                source_id: "drand:mainnet:123".to_string(),
+                published: Timestamp::from_seconds(1666343642),
+                randomness: HexBinary::from_hex(
+                    "3436255a9f8a1f1aaa3cc1c02bc1dd87b4e8b5f6b34ee64a3bb4d1e7a7b9b1d0",
+                )
+                .unwrap(),
+                origin: to_json_binary(&RequestBeaconOrigin {
+                    sender: sender.to_string(),
+                    job_id: "foo".to_string(),
+                })
+                .unwrap(),
+            };
+            let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+            ibc_packet_receive(deps, mock_env(), msg).unwrap()
+        }
+
+        // Defaults to the configured callback gas limit
+        assert_eq!(
+            gas_limit(deps.as_ref(), &dapp),
+            CallbackGasLimitResponse {
+                gas_limit: 500_000,
+                surcharge: false
+            }
+        );
+
+        // Only the manager can set a gas limit for a dapp
+        let msg = ExecuteMsg::SetCallbackGasLimit {
+            address: partner.to_string(),
+            gas_limit: Some(300_000),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&partner, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        assert_eq!(
+            gas_limit(deps.as_ref(), &partner),
+            CallbackGasLimitResponse {
+                gas_limit: 300_000,
+                surcharge: false
+            }
+        );
+        assert_eq!(prices(deps.as_ref(), &partner), coins(1_000000, "unoisx"));
+        let res = deliver(deps.as_mut(), &partner);
+        assert_eq!(res.messages[0].gas_limit, Some(300_000));
+
+        // Dapps can only request a gas limit when a surcharge is set
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            ExecuteMsg::RequestCallbackGasLimit {
+                gas_limit: Some(1_000_000),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::CallbackGasSurchargeNotSet));
+        let msg = ExecuteMsg::SetCallbackGasSurcharge {
+            surcharge: Some(Decimal::percent(50)),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        execute(deps.as_mut(), mock_env(), message_info(&creator, &[]), msg).unwrap();
+        let CallbackGasSurchargeResponse { surcharge } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::CallbackGasSurcharge {}).unwrap())
+                .unwrap();
+        assert_eq!(surcharge, Some(Decimal::percent(50)));
+
+        // Requested gas limits must be within bounds
+        for (requested, expected_err) in [
+            (
+                499_999,
+                ContractError::CallbackGasLimitTooLow { min: 500_000 },
+            ),
+            (
+                10_000_001,
+                ContractError::CallbackGasLimitTooHigh { max: 10_000_000 },
+            ),
+        ] {
+            let err = execute(
+                deps.as_mut(),
                 mock_env(),
-                QueryMsg::IsAllowlisted {
-                    address: addr_not_in_allowlist.to_string(),
+                message_info(&dapp, &[]),
+                ExecuteMsg::RequestCallbackGasLimit {
+                    gas_limit: Some(requested),
                 },
             )
+            .unwrap_err();
+            assert_eq!(err, expected_err);
+        }
+
+        // The requested gas limit is applied and charged
+        let msg = ExecuteMsg::RequestCallbackGasLimit {
+            gas_limit: Some(1_000_000),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        assert_eq!(
+            gas_limit(deps.as_ref(), &dapp),
+            CallbackGasLimitResponse {
+                gas_limit: 1_000_000,
+                surcharge: true
+            }
+        );
+        assert_eq!(prices(deps.as_ref(), &dapp), coins(1_500000, "unoisx"));
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "foo".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientPayment));
+        let info = message_info(&dapp, &coins(1_500000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = deliver(deps.as_mut(), &dapp);
+        assert_eq!(res.messages[0].gas_limit, Some(1_000_000));
+
+        // Removing the gas limit restores the defaults
+        let msg = ExecuteMsg::RequestCallbackGasLimit { gas_limit: None };
+        execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        assert_eq!(prices(deps.as_ref(), &dapp), coins(1_000000, "unoisx"));
+        let res = deliver(deps.as_mut(), &dapp);
+        assert_eq!(res.messages[0].gas_limit, Some(500_000));
+    }
+
+    #[test]
+    fn dapp_stats_work() {
+        let mut deps = setup(None);
+        setup_channel(deps.as_mut());
+
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let other = deps.api.addr_make("other");
+
+        fn dapp_stats(deps: Deps, address: &Addr) -> DappStats {
+            let DappStatsResponse { stats, .. } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::DappStats {
+                        address: address.to_string(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            stats
+        }
+
+        fn deliver(deps: DepsMut, sender: &Addr, job_id: &str) {
+            let packet = OutPacket::DeliverBeacon {
+                source_id: "drand:mainnet:123".to_string(),
+                published: Timestamp::from_seconds(1666343642),
+                randomness: HexBinary::from_hex(
+                    "3436255a9f8a1f1aaa3cc1c02bc1dd87b4e8b5f6b34ee64a3bb4d1e7a7b9b1d0",
+                )
+                .unwrap(),
+                origin: to_json_binary(&RequestBeaconOrigin {
+                    sender: sender.to_string(),
+                    job_id: job_id.to_string(),
+                })
+                .unwrap(),
+            };
+            let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+            ibc_packet_receive(deps, mock_env(), msg).unwrap();
+        }
+
+        assert_eq!(dapp_stats(deps.as_ref(), &dapp), DappStats::default());
+
+        // Requests and fees, paid with funds and from credit. Only the price is charged.
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "foo".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_500000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::TopUp {
+            beneficiary: dapp.to_string(),
+        };
+        let info = message_info(&other, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "bar".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "baz".to_string(),
+        };
+        let info = message_info(&other, &coins(1_000000, "unoisx"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            dapp_stats(deps.as_ref(), &dapp),
+            DappStats {
+                requests: 2,
+                delivered_callbacks: 0,
+                failed_callbacks: 0,
+                fees: coins(2_000000, "unoisx"),
+            }
+        );
+
+        // Delivered and failed callbacks
+        deliver(deps.as_mut(), &dapp, "foo");
+        deliver(deps.as_mut(), &dapp, "bar");
+        let msg = Reply {
+            id: REPLAY_ID_CALLBACK,
+            payload: Binary::default(),
+            gas_used: 500_000,
+            result: SubMsgResult::Err("out of gas".to_string()),
+        };
+        reply(deps.as_mut(), mock_env(), msg).unwrap();
+        let stats = dapp_stats(deps.as_ref(), &dapp);
+        assert_eq!(stats.delivered_callbacks, 1);
+        assert_eq!(stats.failed_callbacks, 1);
+
+        // A successful retry counts as delivered
+        let msg = ExecuteMsg::RetryCallback {
+            sender: dapp.to_string(),
+            job_id: "bar".to_string(),
+            gas_limit: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        let stats = dapp_stats(deps.as_ref(), &dapp);
+        assert_eq!(stats.delivered_callbacks, 2);
+        assert_eq!(stats.failed_callbacks, 0);
+
+        // Refunds are deducted from the fees
+        let msg = ExecuteMsg::SetConfig {
+            prices: None,
+            payment: None,
+            nois_beacon_price: None,
+            callback_gas_limit: None,
+            mode: None,
+            allowlist_enabled: None,
+            min_after: None,
+            max_after: None,
+            refund_policy: Some(RefundPolicy::Partial {
+                share: Decimal::percent(50),
+            }),
+        };
+        let manager = deps.api.addr_make(CREATOR);
+        execute(deps.as_mut(), mock_env(), message_info(&manager, &[]), msg).unwrap();
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "qux".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let packet = InPacket::RequestBeacon {
+            after: Timestamp::from_seconds(1666343642),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "qux".to_string(),
+            })
             .unwrap(),
-        )
-        .unwrap();
-        assert!(!listed);
-    }
-
-    #[test]
-    fn query_is_allowed_works_when_allowlist_disabled() {
-        let creator = addr(CREATOR);
-        let addr_in_allowlist = addr("addr1");
-        let addr_not_in_allowlist = addr("addr2");
-        let deps = setup(Some(InstantiateMsg {
-            manager: Some(creator.to_string()),
-            prices: vec![coin(1_000000, "unoisx")],
-            test_mode: Some(true),
-            callback_gas_limit: 500_000,
-            mode: OperationalMode::Funded {},
-            allowlist_enabled: Some(false),
-            allowlist: Some(vec![addr_in_allowlist.to_string()]),
-        }));
+        };
+        let msg = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        let stats = dapp_stats(deps.as_ref(), &dapp);
+        assert_eq!(stats.fees, coins(2_500000, "unoisx"));
+
+        let msg = ExecuteMsg::Subscribe {
+            interval: 3600,
+            count: 2,
+            job_id_prefix: "raffle-".to_string(),
+        };
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(30));
+        let stats = dapp_stats(deps.as_ref(), &dapp);
+        assert_eq!(stats.fees, coins(4_500000, "unoisx"));
+        let msg = ExecuteMsg::CancelSubscription {
+            job_id_prefix: "raffle-".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&dapp, &[]), msg).unwrap();
+        let stats = dapp_stats(deps.as_ref(), &dapp);
+        assert_eq!(stats.fees, coins(3_500000, "unoisx"));
 
-        // expect the address IN allow list to return true
-        let IsAllowlistedResponse { listed } = from_json(
+        // All dapps, paginated
+        let AllDappStatsResponse { dapps } = from_json(
             query(
                 deps.as_ref(),
                 mock_env(),
-                QueryMsg::IsAllowlisted {
-                    address: addr_in_allowlist.to_string(),
+                QueryMsg::AllDappStats {
+                    start_after: None,
+                    limit: None,
                 },
             )
             .unwrap(),
         )
         .unwrap();
-        assert!(listed);
-
-        // Expect the address NOT in allowlist to return false even if allowlist is not enabled.
-        let IsAllowlistedResponse { listed } = from_json(
+        assert_eq!(dapps.len(), 2);
+        assert!(dapps.iter().any(|entry| entry.address == dapp));
+        assert!(dapps.iter().any(|entry| entry.address == other));
+        let AllDappStatsResponse { dapps: page } = from_json(
             query(
                 deps.as_ref(),
                 mock_env(),
-                QueryMsg::IsAllowlisted {
-                    address: addr_not_in_allowlist.to_string(),
+                QueryMsg::AllDappStats {
+                    start_after: Some(dapps[0].address.to_string()),
+                    limit: Some(10),
                 },
             )
             .unwrap(),
         )
         .unwrap();
-        assert!(!listed);
+        assert_eq!(page, dapps[1..]);
     }
 
-    //
-    // IBC tests
-    //
-
     #[test]
-    fn ibc_channel_open_checks_version_and_order() {
+    #[allow(deprecated)]
+    fn retry_callback_works() {
         let mut deps = setup(None);
+        setup_channel(deps.as_mut());
 
-        // All good
-        let valid_handshake = mock_ibc_channel_open_init("channel-12", APP_ORDER, IBC_APP_VERSION);
-        ibc_channel_open(deps.as_mut(), mock_env(), valid_handshake).unwrap();
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let other = deps.api.addr_make("other");
+        let randomness =
+            HexBinary::from_hex("3436255a9f8a1f1aaa3cc1c02bc1dd87b4e8b5f6b34ee64a3bb4d1e7a7b9b1d0")
+                .unwrap();
+        let published = Timestamp::from_seconds(1666343642);
+
+        fn failed_callbacks(deps: Deps, sender: &Addr) -> Vec<FailedCallback> {
+            let FailedCallbacksResponse { callbacks } = from_json(
+                query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::FailedCallbacks {
+                        sender: sender.to_string(),
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            callbacks
+        }
 
-        // Wrong order
-        let wrong_order = mock_ibc_channel_open_init("channel-12", BAD_APP_ORDER, IBC_APP_VERSION);
-        let res = ibc_channel_open(deps.as_mut(), mock_env(), wrong_order).unwrap_err();
-        assert!(matches!(res, ContractError::ChannelError(..)));
+        // Deliver beacon and let the callback fail
+        let packet = OutPacket::DeliverBeacon {
+            source_id: "drand:mainnet:123".to_string(),
+            published,
+            randomness: randomness.clone(),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
+        let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(failed_callbacks(deps.as_ref(), &dapp), []);
+        let msg = Reply {
+            id: REPLAY_ID_CALLBACK,
+            payload: Binary::default(),
+            gas_used: 500_000,
+            result: SubMsgResult::Err("out of gas".to_string()),
+        };
+        reply(deps.as_mut(), mock_env(), msg).unwrap();
+        let expected = FailedCallback {
+            sender: dapp.clone(),
+            job_id: "foo".to_string(),
+            published,
+            randomness: randomness.clone(),
+            error: "out of gas".to_string(),
+        };
+        assert_eq!(failed_callbacks(deps.as_ref(), &dapp), [expected]);
+        assert_eq!(failed_callbacks(deps.as_ref(), &other), []);
 
-        // Wrong version
-        let wrong_version = mock_ibc_channel_open_init("channel-12", APP_ORDER, "another version");
-        let res = ibc_channel_open(deps.as_mut(), mock_env(), wrong_version).unwrap_err();
-        assert!(matches!(res, ContractError::ChannelError(..)));
+        let retry = |job_id: &str, gas_limit: Option<u64>| ExecuteMsg::RetryCallback {
+            sender: dapp.to_string(),
+            job_id: job_id.to_string(),
+            gas_limit,
+        };
+
+        // Only dapp and manager can retry
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&other, &[]),
+            retry("foo", None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+
+        // Gas limit must not be lower than the configured one
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            retry("foo", Some(499_999)),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CallbackGasLimitTooLow { min: 500_000 }
+        ));
+
+        // Unknown job
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            retry("bar", None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::FailedCallbackNotFound));
+
+        // Retry with a higher gas limit
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            retry("foo", Some(1_500_000)),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            [SubMsg::new(WasmMsg::Execute {
+                contract_addr: dapp.to_string(),
+                msg: to_json_binary(&ReceiverExecuteMsg::NoisReceive {
+                    callback: NoisCallback {
+                        job_id: "foo".to_string(),
+                        published,
+                        randomness,
+                    },
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+            .with_gas_limit(1_500_000)]
+        );
+        assert_eq!(first_attr(&res.attributes, "job_id").unwrap(), "foo");
+        assert_eq!(failed_callbacks(deps.as_ref(), &dapp), []);
+
+        // Successful callbacks are not stored
+        let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let msg = Reply {
+            id: REPLAY_ID_CALLBACK,
+            payload: Binary::default(),
+            gas_used: 1234,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+                msg_responses: vec![],
+            }),
+        };
+        reply(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(failed_callbacks(deps.as_ref(), &dapp), []);
     }
 
     #[test]
-    fn ibc_channel_connect_works() {
-        // We are chain A and get the ChanOpenAck
-
+    fn failed_callbacks_are_capped_and_can_be_pruned() {
         let mut deps = setup(None);
+        setup_channel(deps.as_mut());
 
-        // Channel is unset
-        let GatewayChannelResponse { channel } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
-                .unwrap();
-        assert_eq!(channel, None);
+        let manager = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
+        let other = deps.api.addr_make("other");
+
+        let fail_callback = |mut deps: DepsMut, job_id: &str| -> Response {
+            let packet = OutPacket::DeliverBeacon {
+                source_id: "drand:mainnet:123".to_string(),
+                published: Timestamp::from_seconds(1666343642),
+                randomness: HexBinary::from([0xaa; 32]),
+                origin: to_json_binary(&RequestBeaconOrigin {
+                    sender: dapp.to_string(),
+                    job_id: job_id.to_string(),
+                })
+                .unwrap(),
+            };
+            let msg = mock_ibc_packet_recv("channel-12", &packet).unwrap();
+            ibc_packet_receive(deps.branch(), mock_env(), msg).unwrap();
+            let msg = Reply {
+                id: REPLAY_ID_CALLBACK,
+                payload: Binary::default(),
+                gas_used: 500_000,
+                result: SubMsgResult::Err("out of gas".to_string()),
+            };
+            reply(deps, mock_env(), msg).unwrap()
+        };
+        let stored = |deps: Deps| {
+            FAILED_CALLBACKS
+                .prefix(&dapp)
+                .keys_raw(deps.storage, None, None, Order::Ascending)
+                .count()
+        };
 
-        let msg = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, IBC_APP_VERSION);
-        ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+        // Failed callbacks beyond the cap are not stored
+        for i in 0..MAX_FAILED_CALLBACKS_PER_DAPP {
+            let res = fail_callback(deps.as_mut(), &format!("job-{i:03}"));
+            assert_eq!(first_attr(&res.events[0].attributes, "stored"), None);
+        }
+        assert_eq!(stored(deps.as_ref()), MAX_FAILED_CALLBACKS_PER_DAPP);
+        let res = fail_callback(deps.as_mut(), "job-overflow");
+        assert_eq!(
+            first_attr(&res.events[0].attributes, "stored").unwrap(),
+            "false"
+        );
+        assert!(!FAILED_CALLBACKS.has(&deps.storage, (&dapp, "job-overflow")));
 
-        // Channel is now set
-        let GatewayChannelResponse { channel } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
-                .unwrap();
-        assert_eq!(channel, Some("channel-12".to_string()));
+        // Only dapp and manager can prune
+        let prune = |job_ids: Option<&[&str]>| ExecuteMsg::PruneFailedCallbacks {
+            sender: dapp.to_string(),
+            job_ids: job_ids.map(|ids| ids.iter().map(|id| id.to_string()).collect()),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&other, &[]),
+            prune(None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
 
-        // One more ChanOpenAck
-        let msg = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, IBC_APP_VERSION);
-        let err = ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap_err();
-        assert!(matches!(err, ContractError::ChannelAlreadySet));
+        // Pruning makes room for new failed callbacks
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&dapp, &[]),
+            prune(Some(&["job-000", "job-unknown"])),
+        )
+        .unwrap();
+        assert_eq!(first_attr(&res.attributes, "pruned").unwrap(), "1");
+        let res = fail_callback(deps.as_mut(), "job-overflow");
+        assert_eq!(first_attr(&res.events[0].attributes, "stored"), None);
+        assert!(FAILED_CALLBACKS.has(&deps.storage, (&dapp, "job-overflow")));
 
-        // ChanOpenConfirm is rejected
-        let msg = mock_ibc_channel_connect_confirm("channel-12", APP_ORDER, IBC_APP_VERSION);
-        let err = ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap_err();
-        assert!(matches!(err, ContractError::MustBeChainA));
+        // Prune all
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            message_info(&manager, &[]),
+            prune(None),
+        )
+        .unwrap();
+        assert_eq!(
+            first_attr(&res.attributes, "pruned").unwrap(),
+            MAX_FAILED_CALLBACKS_PER_DAPP.to_string()
+        );
+        assert_eq!(stored(deps.as_ref()), 0);
     }
 
     #[test]
-    fn ibc_channel_close_works() {
+    fn failed_jobs_are_refunded_according_to_policy() {
         let mut deps = setup(None);
+        setup_channel(deps.as_mut());
 
-        // Open
-        let valid_handshake = mock_ibc_channel_open_init("channel-12", APP_ORDER, IBC_APP_VERSION);
-        ibc_channel_open(deps.as_mut(), mock_env(), valid_handshake).unwrap();
+        let manager = deps.api.addr_make(CREATOR);
+        let dapp = deps.api.addr_make(DAPP_ADDRESS);
 
-        // Connect
-        let msg = mock_ibc_channel_connect_ack("channel-12", APP_ORDER, IBC_APP_VERSION);
-        ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap();
+        fn set_refund_policy(
+            deps: DepsMut,
+            sender: &Addr,
+            refund_policy: RefundPolicy,
+        ) -> Result<Response, ContractError> {
+            let msg = ExecuteMsg::SetConfig {
+                prices: None,
+                payment: None,
+                nois_beacon_price: None,
+                callback_gas_limit: None,
+                mode: None,
+                allowlist_enabled: None,
+                min_after: None,
+                max_after: None,
+                refund_policy: Some(refund_policy),
+            };
+            execute(deps, mock_env(), message_info(sender, &[]), msg)
+        }
 
-        // Channel is now set
-        let GatewayChannelResponse { channel } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
-                .unwrap();
-        assert_eq!(channel, Some("channel-12".to_string()));
+        // No refunds by default
+        let ConfigResponse { config } =
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.refund_policy, Some(RefundPolicy::None {}));
 
-        // Closing channel fails
-        let msg = mock_ibc_channel_close_init("channel-12", APP_ORDER, IBC_APP_VERSION);
-        let err = ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap_err();
-        assert!(matches!(err, ContractError::ChannelMustNotBeClosed));
+        // Only manager can set the policy
+        let err = set_refund_policy(deps.as_mut(), &dapp, RefundPolicy::Full {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized));
+        let err = set_refund_policy(
+            deps.as_mut(),
+            &manager,
+            RefundPolicy::Partial {
+                share: Decimal::percent(150),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRefundShare));
 
-        // Channel is still set
-        let GatewayChannelResponse { channel } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
-                .unwrap();
-        assert_eq!(channel, Some("channel-12".to_string()));
+        let packet = InPacket::RequestBeacon {
+            after: Timestamp::from_seconds(1666343642),
+            origin: to_json_binary(&RequestBeaconOrigin {
+                sender: dapp.to_string(),
+                job_id: "foo".to_string(),
+            })
+            .unwrap(),
+        };
 
-        // The other side closed
-        let msg = mock_ibc_channel_close_confirm("channel-12", APP_ORDER, IBC_APP_VERSION);
-        ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+        // Full refund on error ack
+        set_refund_policy(deps.as_mut(), &manager, RefundPolicy::Full {}).unwrap();
+        let msg = ExecuteMsg::GetNextRandomness {
+            job_id: "foo".to_string(),
+        };
+        let info = message_info(&dapp, &coins(1_200000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let ack = StdAck::error("kaputt");
+        let msg = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            [SubMsg::reply_on_error(
+                BankMsg::Send {
+                    to_address: dapp.to_string(),
+                    amount: coins(1_200000, "unoisx"),
+                },
+                REPLAY_ID_REFUND
+            )]
+        );
+        assert_eq!(
+            res.events,
+            [Event::new("nois-failed-job")
+                .add_attribute("sender", dapp.as_str())
+                .add_attribute("job_id", "foo")
+                .add_attribute("refund", "1200000unoisx")]
+        );
 
-        // Channel is unset
-        let GatewayChannelResponse { channel } =
-            from_json(query(deps.as_ref(), mock_env(), QueryMsg::GatewayChannel {}).unwrap())
-                .unwrap();
-        assert_eq!(channel, None);
+        // Partial refund on timeout
+        set_refund_policy(
+            deps.as_mut(),
+            &manager,
+            RefundPolicy::Partial {
+                share: Decimal::percent(50),
+            },
+        )
+        .unwrap();
+        let msg = ExecuteMsg::GetRandomnessBatch {
+            requests: vec![
+                BatchRandomnessRequest {
+                    job_id: "bar".to_string(),
+                    after: None,
+                },
+                BatchRandomnessRequest {
+                    job_id: "baz".to_string(),
+                    after: None,
+                },
+            ],
+        };
+        let info = message_info(&dapp, &coins(3_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let timeout = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), timeout).unwrap();
+        let refund = SubMsg::reply_on_error(
+            BankMsg::Send {
+                to_address: dapp.to_string(),
+                amount: coins(750000, "unoisx"),
+            },
+            REPLAY_ID_REFUND,
+        );
+        assert_eq!(res.messages, [refund.clone(), refund]);
+        assert_eq!(
+            first_attr(&res.events[1].attributes, "refund").unwrap(),
+            "750000unoisx"
+        );
+
+        // No refund
+        set_refund_policy(deps.as_mut(), &manager, RefundPolicy::None {}).unwrap();
+        let info = message_info(&dapp, &coins(2_000000, "unoisx"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        send_packet_reply(deps.as_mut(), &res.messages, Some(29));
+        let timeout = mock_ibc_packet_timeout("channel-12", &packet).unwrap();
+        let res = ibc_packet_timeout(deps.as_mut(), mock_env(), timeout).unwrap();
+        assert_eq!(res.messages, []);
+        assert_eq!(first_attr(&res.events[0].attributes, "refund"), None);
+
+        // A failed refund is recorded
+        let msg = Reply {
+            id: REPLAY_ID_REFUND,
+            payload: Binary::default(),
+            gas_used: 1234,
+            result: SubMsgResult::Err("insufficient funds".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.events,
+            [Event::new("nois-refund")
+                .add_attribute("success", "false")
+                .add_attribute("error_msg", "insufficient funds")]
+        );
     }
 
     #[test]
@@ -1861,6 +7091,30 @@ mod tests {
             "request_queued"
         );
 
+        // Success ack (batch)
+        let ack = StdAck::success(
+            to_json_binary(&InPacketAck::RequestBeacons {
+                statuses: vec![RequestBeaconStatus {
+                    source_id: "backend:123:456".to_string(),
+                    queued: true,
+                }],
+            })
+            .unwrap(),
+        );
+        let msg = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        let IbcBasicResponse { attributes, .. } =
+            ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(first_attr(&attributes, "is_error").unwrap(), "false");
+        assert_eq!(
+            first_attr(&attributes, "ack_type").unwrap(),
+            "request_beacons"
+        );
+
         // Error ack
         let ack = StdAck::error("kaputt");
         let msg = mock_ibc_packet_ack(
@@ -1874,6 +7128,24 @@ mod tests {
         assert_eq!(first_attr(&attributes, "action").unwrap(), "ibc_packet_ack");
         assert_eq!(first_attr(&attributes, "is_error").unwrap(), "true");
         assert_eq!(first_attr(&attributes, "error").unwrap(), "kaputt");
+        assert_eq!(first_attr(&attributes, "error_code").unwrap(), "other");
         assert_eq!(first_attr(&attributes, "ack_type"), None);
+
+        // Typed error ack
+        let ack = PacketError::new(PacketErrorCode::Paused, "The gateway is paused.").to_ack();
+        let msg = mock_ibc_packet_ack(
+            "channel-12",
+            &packet,
+            IbcAcknowledgement::encode_json(&ack).unwrap(),
+        )
+        .unwrap();
+        let IbcBasicResponse { attributes, .. } =
+            ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(first_attr(&attributes, "is_error").unwrap(), "true");
+        assert_eq!(
+            first_attr(&attributes, "error").unwrap(),
+            "The gateway is paused."
+        );
+        assert_eq!(first_attr(&attributes, "error_code").unwrap(), "paused");
     }
 }
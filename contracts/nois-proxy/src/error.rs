@@ -1,8 +1,9 @@
 use thiserror::Error;
 
-use cosmwasm_std::{StdError, Timestamp};
+use cosmwasm_std::{Coin, StdError, Timestamp};
 
-use nois_protocol::ChannelError;
+use manager_transfer::ManagerTransferError;
+use nois_protocol::{ChannelError, PacketErrorCode};
 
 #[derive(Error, Debug, PartialEq)]
 #[non_exhaustive]
@@ -13,6 +14,9 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized,
 
+    #[error("{0}")]
+    ManagerTransfer(#[from] ManagerTransferError),
+
     #[error("Job ID exceeds length limit.")]
     JobIdTooLong,
 
@@ -28,6 +32,12 @@ pub enum ContractError {
         after: Timestamp,
     },
 
+    #[error("The min_after offset must not exceed the max_after offset.")]
+    InvalidAfterOffsets,
+
+    #[error("The proxy is paused. No new requests are accepted. Reason: {reason}")]
+    Paused { reason: String },
+
     #[error("No payment option is configured in this proxy.")]
     NoPaymentOption,
 
@@ -37,6 +47,68 @@ pub enum ContractError {
     #[error("Sender address is not allowed to perform this action")]
     SenderNotAllowed,
 
+    #[error("Sender address is on the denylist")]
+    SenderDenylisted,
+
+    #[error("Job ID was already used by this sender")]
+    JobIdAlreadyUsed,
+
+    #[error("A request of this sender with the same job ID is still in flight")]
+    JobIdInFlight,
+
+    #[error("Batch must contain at least one request.")]
+    EmptyBatch,
+
+    #[error("Batch must not contain more than {max} requests.")]
+    BatchTooLarge { max: usize },
+
+    #[error("The gateway channel does not support requests for a specific round.")]
+    RoundRequestsNotSupported,
+
+    #[error("Volume tiers must be ordered by increasing min_requests and discounts must be between 0 and 1.")]
+    InvalidVolumeTiers,
+
+    #[error("Top up must contain funds in accepted denoms only.")]
+    InvalidTopUp,
+
+    #[error("Insufficient credit.")]
+    InsufficientCredit,
+
+    #[error(
+        "Withdrawal exceeds the balance not reserved for dapps. Withdrawable: {withdrawable}."
+    )]
+    InsufficientWithdrawableBalance { withdrawable: Coin },
+
+    #[error("Funds must not be sent when the price is paid from credit.")]
+    FundsWithCredit,
+
+    #[error("Subscription interval must not be zero.")]
+    ZeroSubscriptionInterval,
+
+    #[error("Subscription count must be between 1 and {max}.")]
+    InvalidSubscriptionCount { max: u32 },
+
+    #[error("A subscription with this job ID prefix already exists.")]
+    SubscriptionExists,
+
+    #[error("Subscription not found.")]
+    SubscriptionNotFound,
+
+    #[error("Refund share must be between 0 and 1.")]
+    InvalidRefundShare,
+
+    #[error("No failed callback found for this sender and job ID.")]
+    FailedCallbackNotFound,
+
+    #[error("Callback gas limit must be at least {min}.")]
+    CallbackGasLimitTooLow { min: u64 },
+
+    #[error("Callback gas limit must not exceed {max}.")]
+    CallbackGasLimitTooHigh { max: u64 },
+
+    #[error("Dapps cannot request callback gas limits because no surcharge is set.")]
+    CallbackGasSurchargeNotSet,
+
     //
     // Reply/Submessages
     //
@@ -58,9 +130,28 @@ pub enum ContractError {
     #[error("Channel was already created. Channel is immutable and cannot be overriden")]
     ChannelAlreadySet,
 
+    #[error("Gateway channel {channel_id} is not connected.")]
+    UnknownGatewayChannel { channel_id: String },
+
+    #[error("Gateway channel is not enabled.")]
+    GatewayChannelDisabled,
+
+    #[error("The primary gateway channel cannot be disabled.")]
+    CannotDisablePrimaryChannel,
+
     #[error("Channel must not be closed.")]
     ChannelMustNotBeClosed,
 
     #[error("{0}")]
     ChannelError(#[from] ChannelError),
 }
+
+impl ContractError {
+    /// The code that is sent to the gateway when this error occurs while processing a packet
+    pub fn packet_error_code(&self) -> PacketErrorCode {
+        match self {
+            ContractError::UnsupportedPacketType => PacketErrorCode::UnsupportedPacket,
+            _ => PacketErrorCode::Other,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use std::collections::BTreeMap;
 
 use crate::error::ContractError;
-use cosmwasm_std::Coin;
+use crate::state::{RefundPolicy, VolumeTier};
+use cosmwasm_std::{Coin, Decimal, Uint128};
 use nois::MAX_JOB_ID_LEN;
 
 pub fn validate_job_id(job_id: &str) -> Result<(), ContractError> {
@@ -16,10 +17,12 @@ pub fn validate_job_id(job_id: &str) -> Result<(), ContractError> {
 /// supported denoms. Payment cannot be split across multiple denoms. Extra funds
 /// are ignored.
 ///
+/// Returns the price that is charged.
+///
 /// When `prices` is an empty list the user cannot pay because there is no possible
 /// denomination in which they could do that. This can be desired in case the cantract
 /// does not want to accapt any payment (i.e. is closed).
-pub fn validate_payment(prices: &[Coin], funds: &[Coin]) -> Result<(), ContractError> {
+pub fn validate_payment(prices: &[Coin], funds: &[Coin]) -> Result<Coin, ContractError> {
     if prices.is_empty() {
         return Err(ContractError::NoPaymentOption);
     }
@@ -29,13 +32,164 @@ pub fn validate_payment(prices: &[Coin], funds: &[Coin]) -> Result<(), ContractE
         if let Some(price) = prices.get(&fund.denom) {
             // user can pay in this provided denom
             if fund.amount >= *price {
-                return Ok(());
+                return Ok(Coin {
+                    denom: fund.denom.clone(),
+                    amount: *price,
+                });
             }
         }
     }
     Err(ContractError::InsufficientPayment)
 }
 
+/// Splits the funds paid for a batch into `count` shares, one per job.
+/// Rounding remainders are added to the first share.
+pub fn split_funds(funds: &[Coin], count: u32) -> Vec<Vec<Coin>> {
+    let count = count.max(1);
+    let mut shares = vec![Vec::<Coin>::with_capacity(funds.len()); count as usize];
+    for coin in funds {
+        let share = coin.amount / Uint128::from(count);
+        let remainder = coin.amount - share * Uint128::from(count);
+        for (i, shares) in shares.iter_mut().enumerate() {
+            let amount = if i == 0 { share + remainder } else { share };
+            shares.push(Coin {
+                denom: coin.denom.clone(),
+                amount,
+            });
+        }
+    }
+    shares
+}
+
+/// Multiplies every price by `count`
+pub fn multiply_prices(prices: &[Coin], count: u32) -> Vec<Coin> {
+    prices
+        .iter()
+        .map(|price| Coin {
+            denom: price.denom.clone(),
+            amount: price.amount * Uint128::from(count),
+        })
+        .collect()
+}
+
+pub fn validate_volume_tiers(tiers: &[VolumeTier]) -> Result<(), ContractError> {
+    let ordered = tiers
+        .windows(2)
+        .all(|pair| pair[0].min_requests < pair[1].min_requests);
+    if !ordered || tiers.iter().any(|tier| tier.discount > Decimal::one()) {
+        return Err(ContractError::InvalidVolumeTiers);
+    }
+    Ok(())
+}
+
+/// Applies the discount of the highest tier reached by `requests` to the prices.
+/// Discounted amounts are rounded up.
+pub fn apply_volume_tiers(prices: Vec<Coin>, tiers: &[VolumeTier], requests: u64) -> Vec<Coin> {
+    let Some(tier) = tiers
+        .iter()
+        .rev()
+        .find(|tier| requests >= tier.min_requests)
+    else {
+        return prices;
+    };
+    let share = Decimal::one() - tier.discount;
+    prices
+        .into_iter()
+        .map(|price| Coin {
+            amount: price.amount.mul_ceil(share),
+            denom: price.denom,
+        })
+        .collect()
+}
+
+/// Increases the prices for a callback gas limit above the default by
+/// `surcharge` per additional `default_gas_limit` of gas. Amounts are rounded up.
+pub fn apply_gas_surcharge(
+    prices: Vec<Coin>,
+    surcharge: Decimal,
+    gas_limit: u64,
+    default_gas_limit: u64,
+) -> Vec<Coin> {
+    let additional_gas = gas_limit.saturating_sub(default_gas_limit);
+    if additional_gas == 0 || default_gas_limit == 0 {
+        return prices;
+    }
+    let factor =
+        Decimal::one() + surcharge * Decimal::from_ratio(additional_gas, default_gas_limit);
+    prices
+        .into_iter()
+        .map(|price| Coin {
+            amount: price.amount.mul_ceil(factor),
+            denom: price.denom,
+        })
+        .collect()
+}
+
+/// Adds the amounts of `coins` to `total`. Denoms not yet in `total` are appended.
+pub fn add_coins(total: &mut Vec<Coin>, coins: &[Coin]) {
+    for coin in coins.iter().filter(|coin| !coin.amount.is_zero()) {
+        match total
+            .iter_mut()
+            .find(|existing| existing.denom == coin.denom)
+        {
+            Some(existing) => existing.amount += coin.amount,
+            None => total.push(coin.clone()),
+        }
+    }
+}
+
+/// Subtracts the amounts of `coins` from `total`. Amounts saturate at zero and
+/// denoms with a zero amount are removed.
+pub fn sub_coins(total: &mut Vec<Coin>, coins: &[Coin]) {
+    for coin in coins {
+        if let Some(existing) = total
+            .iter_mut()
+            .find(|existing| existing.denom == coin.denom)
+        {
+            existing.amount = existing.amount.saturating_sub(coin.amount);
+        }
+    }
+    total.retain(|coin| !coin.amount.is_zero());
+}
+
+/// Takes the share of the next job out of funds that pay for `count` jobs.
+/// The share is calculated like the first share in [`split_funds`].
+pub fn take_funds_share(funds: &mut [Coin], count: u32) -> Vec<Coin> {
+    let share = split_funds(funds, count).swap_remove(0);
+    for (coin, taken) in funds.iter_mut().zip(&share) {
+        coin.amount -= taken.amount;
+    }
+    share
+}
+
+pub fn validate_refund_policy(policy: &RefundPolicy) -> Result<(), ContractError> {
+    match policy {
+        RefundPolicy::Partial { share } if *share > Decimal::one() => {
+            Err(ContractError::InvalidRefundShare)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Calculates the refund for a failed job. Zero amounts are omitted.
+pub fn refund_amount(policy: &RefundPolicy, funds: &[Coin]) -> Vec<Coin> {
+    funds
+        .iter()
+        .map(|coin| {
+            let amount = match policy {
+                RefundPolicy::Full {} => coin.amount,
+                RefundPolicy::Partial { share } => coin.amount.mul_floor(*share),
+                RefundPolicy::None {} => Uint128::zero(),
+            };
+            Coin {
+                denom: coin.denom.clone(),
+                amount,
+            }
+        })
+        .filter(|coin| !coin.amount.is_zero())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::{coin, coins};
@@ -70,7 +224,8 @@ mod tests {
         // Single payment option
         let prices = coins(100, "bucks");
         validate_payment(&prices, &[coin(100, "bucks")]).unwrap();
-        validate_payment(&prices, &[coin(150, "bucks")]).unwrap();
+        let charged = validate_payment(&prices, &[coin(150, "bucks")]).unwrap();
+        assert_eq!(charged, coin(100, "bucks"));
         validate_payment(&prices, &[coin(100, "bucks"), coin(20, "ujunox")]).unwrap();
         validate_payment(&prices, &[coin(20, "ujunox"), coin(100, "bucks")]).unwrap();
         validate_payment(
@@ -93,7 +248,8 @@ mod tests {
         validate_payment(&prices, &[coin(100, "bucks")]).unwrap();
         validate_payment(&prices, &[coin(20, "sand")]).unwrap();
         validate_payment(&prices, &[coin(150, "bucks")]).unwrap();
-        validate_payment(&prices, &[coin(50, "sand")]).unwrap();
+        let charged = validate_payment(&prices, &[coin(50, "sand")]).unwrap();
+        assert_eq!(charged, coin(20, "sand"));
         validate_payment(&prices, &[coin(100, "bucks"), coin(20, "ujunox")]).unwrap();
         validate_payment(&prices, &[coin(20, "ujunox"), coin(100, "bucks")]).unwrap();
         validate_payment(
@@ -113,4 +269,183 @@ mod tests {
         let err = validate_payment(&prices, &[coin(99, "bucks"), coin(200, "gold")]).unwrap_err();
         assert!(matches!(err, ContractError::InsufficientPayment));
     }
+
+    #[test]
+    fn split_funds_works() {
+        assert_eq!(split_funds(&[], 2), vec![vec![], vec![]]);
+        assert_eq!(
+            split_funds(&coins(10, "unoisx"), 1),
+            vec![coins(10, "unoisx")]
+        );
+        assert_eq!(
+            split_funds(&[coin(10, "unoisx"), coin(3, "uatom")], 3),
+            vec![
+                vec![coin(4, "unoisx"), coin(1, "uatom")],
+                vec![coin(3, "unoisx"), coin(1, "uatom")],
+                vec![coin(3, "unoisx"), coin(1, "uatom")],
+            ]
+        );
+    }
+
+    #[test]
+    fn multiply_prices_works() {
+        assert_eq!(multiply_prices(&[], 3), vec![]);
+        assert_eq!(
+            multiply_prices(&[coin(10, "unoisx"), coin(3, "uatom")], 3),
+            vec![coin(30, "unoisx"), coin(9, "uatom")]
+        );
+    }
+
+    #[test]
+    fn validate_volume_tiers_works() {
+        let tier = |min_requests: u64, discount: &str| VolumeTier {
+            min_requests,
+            discount: discount.parse().unwrap(),
+        };
+        validate_volume_tiers(&[]).unwrap();
+        validate_volume_tiers(&[tier(100, "0.1"), tier(1000, "1")]).unwrap();
+
+        let err = validate_volume_tiers(&[tier(100, "0.1"), tier(100, "0.2")]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidVolumeTiers));
+        let err = validate_volume_tiers(&[tier(1000, "0.2"), tier(100, "0.1")]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidVolumeTiers));
+        let err = validate_volume_tiers(&[tier(100, "1.1")]).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidVolumeTiers));
+    }
+
+    #[test]
+    fn apply_volume_tiers_works() {
+        let tiers = [
+            VolumeTier {
+                min_requests: 10,
+                discount: Decimal::percent(10),
+            },
+            VolumeTier {
+                min_requests: 100,
+                discount: Decimal::percent(50),
+            },
+        ];
+        let prices = vec![coin(1000, "unoisx"), coin(3, "uatom")];
+        assert_eq!(apply_volume_tiers(prices.clone(), &[], 1000), prices);
+        assert_eq!(apply_volume_tiers(prices.clone(), &tiers, 9), prices);
+        assert_eq!(
+            apply_volume_tiers(prices.clone(), &tiers, 10),
+            vec![coin(900, "unoisx"), coin(3, "uatom")]
+        );
+        assert_eq!(
+            apply_volume_tiers(prices, &tiers, 100),
+            vec![coin(500, "unoisx"), coin(2, "uatom")]
+        );
+    }
+
+    #[test]
+    fn apply_gas_surcharge_works() {
+        let prices = vec![coin(1000, "unoisx"), coin(3, "uatom")];
+        let surcharge = Decimal::percent(50);
+        assert_eq!(
+            apply_gas_surcharge(prices.clone(), surcharge, 500_000, 500_000),
+            prices
+        );
+        assert_eq!(
+            apply_gas_surcharge(prices.clone(), surcharge, 400_000, 500_000),
+            prices
+        );
+        assert_eq!(
+            apply_gas_surcharge(prices.clone(), surcharge, 1_000_000, 500_000),
+            vec![coin(1500, "unoisx"), coin(5, "uatom")]
+        );
+        assert_eq!(
+            apply_gas_surcharge(prices.clone(), surcharge, 600_000, 500_000),
+            vec![coin(1100, "unoisx"), coin(4, "uatom")]
+        );
+        assert_eq!(
+            apply_gas_surcharge(prices, Decimal::zero(), 2_000_000, 500_000),
+            vec![coin(1000, "unoisx"), coin(3, "uatom")]
+        );
+    }
+
+    #[test]
+    fn add_coins_works() {
+        let mut total = vec![];
+        add_coins(&mut total, &[]);
+        assert_eq!(total, []);
+        add_coins(&mut total, &[coin(10, "unoisx"), coin(0, "uatom")]);
+        assert_eq!(total, [coin(10, "unoisx")]);
+        add_coins(&mut total, &[coin(3, "uatom"), coin(5, "unoisx")]);
+        assert_eq!(total, [coin(15, "unoisx"), coin(3, "uatom")]);
+    }
+
+    #[test]
+    fn sub_coins_works() {
+        let mut total = vec![coin(15, "unoisx"), coin(3, "uatom")];
+        sub_coins(&mut total, &[]);
+        assert_eq!(total, [coin(15, "unoisx"), coin(3, "uatom")]);
+        sub_coins(&mut total, &[coin(5, "unoisx"), coin(7, "uluna")]);
+        assert_eq!(total, [coin(10, "unoisx"), coin(3, "uatom")]);
+        sub_coins(&mut total, &[coin(4, "uatom")]);
+        assert_eq!(total, [coin(10, "unoisx")]);
+        sub_coins(&mut total, &[coin(10, "unoisx")]);
+        assert_eq!(total, []);
+    }
+
+    #[test]
+    fn take_funds_share_works() {
+        let mut funds = vec![coin(10, "unoisx"), coin(3, "uatom")];
+        assert_eq!(
+            take_funds_share(&mut funds, 3),
+            vec![coin(4, "unoisx"), coin(1, "uatom")]
+        );
+        assert_eq!(funds, vec![coin(6, "unoisx"), coin(2, "uatom")]);
+        assert_eq!(
+            take_funds_share(&mut funds, 2),
+            vec![coin(3, "unoisx"), coin(1, "uatom")]
+        );
+        assert_eq!(
+            take_funds_share(&mut funds, 1),
+            vec![coin(3, "unoisx"), coin(1, "uatom")]
+        );
+        assert_eq!(funds, vec![coin(0, "unoisx"), coin(0, "uatom")]);
+    }
+
+    #[test]
+    fn refund_amount_works() {
+        let funds = [coin(10, "unoisx"), coin(3, "uatom")];
+        assert_eq!(refund_amount(&RefundPolicy::Full {}, &funds), funds);
+        assert_eq!(refund_amount(&RefundPolicy::None {}, &funds), []);
+        assert_eq!(
+            refund_amount(
+                &RefundPolicy::Partial {
+                    share: Decimal::percent(50)
+                },
+                &funds
+            ),
+            [coin(5, "unoisx"), coin(1, "uatom")]
+        );
+        // Zero amounts are omitted
+        assert_eq!(
+            refund_amount(
+                &RefundPolicy::Partial {
+                    share: Decimal::percent(20)
+                },
+                &funds
+            ),
+            [coin(2, "unoisx")]
+        );
+        assert_eq!(refund_amount(&RefundPolicy::Full {}, &[]), []);
+    }
+
+    #[test]
+    fn validate_refund_policy_works() {
+        validate_refund_policy(&RefundPolicy::Full {}).unwrap();
+        validate_refund_policy(&RefundPolicy::None {}).unwrap();
+        validate_refund_policy(&RefundPolicy::Partial {
+            share: Decimal::one(),
+        })
+        .unwrap();
+        let err = validate_refund_policy(&RefundPolicy::Partial {
+            share: Decimal::percent(101),
+        })
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRefundShare));
+    }
 }
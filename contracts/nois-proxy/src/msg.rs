@@ -1,8 +1,11 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Coin, Timestamp, Uint128};
+use cosmwasm_std::{Addr, Coin, Decimal, Timestamp, Uint128};
+use manager_transfer::PendingManagerResponse;
 use nois::ProxyExecuteMsg;
 
-use crate::state::{Config, OperationalMode};
+use crate::state::{
+    Config, DappStats, FailedCallback, Job, OperationalMode, RefundPolicy, Subscription, VolumeTier,
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -38,17 +41,149 @@ pub enum ExecuteMsg {
         after: Timestamp,
         job_id: String,
     },
+    /// Requests the beacon of a specific drand round. This allows committing publicly to
+    /// a round before it is published, e.g. for lotteries.
+    ///
+    /// The round must be in the future when the request arrives at the gateway.
+    /// Otherwise the request fails.
+    GetRandomnessAtRound {
+        round: u64,
+        job_id: String,
+    },
+    /// Requests multiple independent beacons using a single IBC packet.
+    /// Every request leads to a separate callback.
+    ///
+    /// The funds sent must cover the price for all requests.
+    GetRandomnessBatch {
+        requests: Vec<BatchRandomnessRequest>,
+    },
+    /// Subscribes to `count` beacons, one every `interval` seconds starting `interval`
+    /// seconds from now. The job IDs are `job_id_prefix` followed by a 1-based counter.
+    ///
+    /// The funds sent must cover the price for all beacons. Beacons are requested one by
+    /// one and the next beacon is requested when the previous one is delivered.
+    Subscribe {
+        interval: u64,
+        count: u32,
+        job_id_prefix: String,
+    },
+    /// Cancels a subscription of the sender and refunds the prepaid funds that were not
+    /// used by a request yet.
+    CancelSubscription {
+        job_id_prefix: String,
+    },
+    /// Adds the sent funds to the credit of the beneficiary. Anyone can do that.
+    ///
+    /// Requests of the beneficiary are paid from its credit before attached funds are
+    /// considered. Requests paid from credit must not have funds attached.
+    /// Only denoms of the current prices are accepted.
+    TopUp {
+        beneficiary: String,
+    },
+    /// Withdraws unused credit of a dapp to the given address.
+    ///
+    /// Only the manager address can do that.
+    WithdrawCredits {
+        address: String,
+        denom: String,
+        /// The amount of credit to withdraw.
+        /// If None, withdraw all credit of the given denom.
+        amount: Option<Uint128>,
+        recipient: String,
+    },
+    /// Sets prices for a specific dapp that replace the prices of the config.
+    /// Volume tiers do not apply to dapps with a price override.
+    /// Use `None` to remove the override.
+    ///
+    /// Only the manager address can do that.
+    SetPriceOverride {
+        address: String,
+        prices: Option<Vec<Coin>>,
+    },
+    /// Sets the volume tiers, i.e. discounts based on the number of beacons a dapp requested.
+    /// Tiers must be ordered by increasing `min_requests`.
+    ///
+    /// Only the manager address can do that.
+    SetVolumeTiers {
+        tiers: Vec<VolumeTier>,
+    },
+    /// Sets or removes the callback gas limit of a dapp. Gas limits set by the
+    /// manager do not increase the prices of the dapp.
+    ///
+    /// Only the manager address can do that.
+    SetCallbackGasLimit {
+        address: String,
+        gas_limit: Option<u64>,
+    },
+    /// Sets the relative price increase for dapps that requested a higher callback gas limit.
+    /// Unsetting it prevents new requests for callback gas limits.
+    ///
+    /// Only the manager address can do that.
+    SetCallbackGasSurcharge {
+        surcharge: Option<Decimal>,
+    },
+    /// Requests a callback gas limit for the sender above the configured callback gas limit.
+    /// This increases the sender's prices by the callback gas surcharge.
+    /// `None` removes the sender's callback gas limit.
+    RequestCallbackGasLimit {
+        gas_limit: Option<u64>,
+    },
+    /// Sets how long used job IDs are remembered in seconds. While set, requests that
+    /// reuse a job ID of the same sender within this time are rejected.
+    /// `None` allows duplicate job IDs.
+    ///
+    /// Only the manager address can do that.
+    SetJobIdRetention {
+        retention: Option<u64>,
+    },
+    /// Sets the bounds for `after` in seconds relative to the block time of the request.
+    /// They only apply to `after` values and rounds provided by the dapp.
+    /// They apply in addition to `min_after` and `max_after` of the config.
+    /// `None` removes the respective bound.
+    ///
+    /// Only the manager address can do that.
+    SetAfterOffsets {
+        min_after_offset: Option<u64>,
+        max_after_offset: Option<u64>,
+    },
+    /// Enables or disables a connected gateway channel. The first channel is enabled when
+    /// connected. Further channels must be enabled before they are used. Disabled channels
+    /// get no new requests but still deliver the beacons of earlier requests.
+    ///
+    /// Only the manager address can do that.
+    UpdateGatewayChannel {
+        channel_id: String,
+        enabled: bool,
+    },
+    /// Sets the primary gateway channel, which must be enabled. Requests are sent through
+    /// the primary channel unless it is stalled. Then another enabled channel is used.
+    ///
+    /// Only the manager address can do that.
+    SetPrimaryGatewayChannel {
+        channel_id: String,
+    },
+    /// Pauses the proxy. While paused, new requests are rejected but beacons of
+    /// earlier requests are still delivered.
+    ///
+    /// Only the manager address can do that.
+    Pause {
+        reason: String,
+    },
+    /// Unpauses the proxy.
+    ///
+    /// Only the manager address can do that.
+    Unpause {},
     /// Update config entries.
     ///
     /// Only the manager address can do that.
     SetConfig {
-        manager: Option<String>,
         /// The prices of a randomness. List is to be interpreted as oneof,
         /// i.e. payment must be paid in one of those denominations.
-        /// If this list is empty, the user cannot pay. This can be used to put the
+        /// If this list is empty, the user cannot pay. Use pausing to put the
         /// contract out of service.
         prices: Option<Vec<Coin>>,
-        /// Address of the payment contract (on the other chain)
+        /// Address of the payment contract (on the other chain) of the primary gateway channel.
+        /// The gateway sends it when a channel is connected, so this is only needed to correct it.
         payment: Option<String>,
         /// The amount of tokens the proxy sends for each randomness request to the Nois chain
         nois_beacon_price: Option<Uint128>,
@@ -64,6 +199,8 @@ pub enum ExecuteMsg {
         /// The maximum value for `after` when requesting a beacon.
         /// This aims to counter accidental misusage. Not all values in the allowed range are reasonable.
         max_after: Option<Timestamp>,
+        /// How much of the paid funds a dapp gets back when its request fails
+        refund_policy: Option<RefundPolicy>,
     },
     /// Withdraw the given amount to the withdrawal address.
     ///
@@ -71,7 +208,7 @@ pub enum ExecuteMsg {
     Withdraw {
         denom: String,
         /// The amount of tokens to withdraw.
-        /// If None, withdraw all available balance of the given denom.
+        /// If None, withdraw all balance of the given denom that is not reserved for dapps.
         amount: Option<Uint128>,
         address: String,
     },
@@ -82,6 +219,53 @@ pub enum ExecuteMsg {
         add: Vec<String>,
         remove: Vec<String>,
     },
+    /// Add or remove entries from the list of addresses that are not allowed to get randomness.
+    /// The denylist applies no matter if the allowlist is enabled or not.
+    ///
+    /// Only the manager address can do that.
+    UpdateDenylist {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Proposes a new manager. The new manager has to accept the role using `AcceptManager`.
+    ///
+    /// Only the manager address can do that.
+    ProposeManager {
+        manager: String,
+    },
+    /// Accepts the manager role. Must be sent by the pending manager.
+    AcceptManager {},
+    /// Cancels a pending manager transfer.
+    ///
+    /// Only the manager address can do that.
+    CancelManagerTransfer {},
+    /// Opts the sender in or out of failure callbacks. When enabled, the proxy
+    /// sends a `FailureReceiverExecuteMsg::NoisReceiveFailure` to the sender for
+    /// every request that will never get randomness.
+    SetFailureCallbacks {
+        enabled: bool,
+    },
+    /// Executes a failed `NoisReceive` callback again.
+    ///
+    /// Only the dapp that requested the randomness or the manager can do that.
+    RetryCallback {
+        sender: String,
+        job_id: String,
+        /// The gas limit for the callback. Must not be lower than the callback gas
+        /// limit of the dapp. Defaults to the callback gas limit of the dapp.
+        gas_limit: Option<u64>,
+    },
+    /// Removes failed `NoisReceive` callbacks of a dapp without executing them.
+    /// Only a limited number of failed callbacks is stored per dapp, so this makes
+    /// room for new ones.
+    ///
+    /// Only the dapp that requested the randomness or the manager can do that.
+    PruneFailedCallbacks {
+        sender: String,
+        /// The job IDs of the callbacks to remove. If None, all failed callbacks
+        /// of the dapp are removed.
+        job_ids: Option<Vec<String>>,
+    },
 }
 
 // Unused from implementation to bring up compiler errors when the
@@ -99,6 +283,14 @@ impl From<ProxyExecuteMsg> for ExecuteMsg {
     }
 }
 
+#[cw_serde]
+pub struct BatchRandomnessRequest {
+    pub job_id: String,
+    /// Beacon publish time must be > `after`. When unset, the next randomness
+    /// is requested like in `GetNextRandomness`.
+    pub after: Option<Timestamp>,
+}
+
 #[cw_serde]
 pub enum SudoMsg {
     /// Withdraw the given amount to the withdrawal address
@@ -106,7 +298,7 @@ pub enum SudoMsg {
     Withdraw {
         denom: String,
         /// The amount of tokens to withdraw.
-        /// If None, withdraw all available balance of the given denom.
+        /// If None, withdraw all balance of the given denom that is not reserved for dapps.
         amount: Option<Uint128>,
         address: String,
     },
@@ -115,7 +307,7 @@ pub enum SudoMsg {
     WithdrawToCommunityPool {
         denom: String,
         /// The amount of tokens to withdraw.
-        /// If None, withdraw all available balance of the given denom.
+        /// If None, withdraw all balance of the given denom that is not reserved for dapps.
         amount: Option<Uint128>,
     },
     /// Set the config
@@ -124,10 +316,11 @@ pub enum SudoMsg {
         manager: Option<String>,
         /// The prices of a randomness. List is to be interpreted as oneof,
         /// i.e. payment must be paid in one of those denominations.
-        /// If this list is empty, the user cannot pay. This can be used to put the
+        /// If this list is empty, the user cannot pay. Use pausing to put the
         /// contract out of service.
         prices: Option<Vec<Coin>>,
-        /// Address of the payment contract (on the other chain)
+        /// Address of the payment contract (on the other chain) of the primary gateway channel.
+        /// The gateway sends it when a channel is connected, so this is only needed to correct it.
         payment: Option<String>,
         /// The amount of tokens the proxy sends for each randomness request to the Nois chain
         nois_beacon_price: Option<Uint128>,
@@ -143,7 +336,24 @@ pub enum SudoMsg {
         /// The maximum value for `after` when requesting a beacon.
         /// This aims to counter accidental misusage. Not all values in the allowed range are reasonable.
         max_after: Option<Timestamp>,
+        /// How much of the paid funds a dapp gets back when its request fails
+        refund_policy: Option<RefundPolicy>,
+    },
+    /// Sets the bounds for `after` in seconds relative to the block time of the request.
+    /// They only apply to `after` values and rounds provided by the dapp.
+    /// `None` removes the respective bound.
+    #[cfg(feature = "governance_owned")]
+    SetAfterOffsets {
+        min_after_offset: Option<u64>,
+        max_after_offset: Option<u64>,
     },
+    /// Pauses the proxy. While paused, new requests are rejected but beacons of
+    /// earlier requests are still delivered.
+    #[cfg(feature = "governance_owned")]
+    Pause { reason: String },
+    /// Unpauses the proxy
+    #[cfg(feature = "governance_owned")]
+    Unpause {},
 }
 
 #[cw_serde]
@@ -152,24 +362,106 @@ pub enum QueryMsg {
     /// Get the config state
     #[returns(ConfigResponse)]
     Config {},
-    /// Get the prices.
+    /// Get the prices. If an address is set, the effective prices of this dapp
+    /// are returned, i.e. including price overrides and volume discounts.
     #[returns(PricesResponse)]
-    Prices {},
+    Prices { address: Option<String> },
     /// Get the price in one particular denom. Returns None when denom is not accepted.
+    /// If an address is set, the effective price of this dapp is returned.
     #[returns(PriceResponse)]
-    Price { denom: String },
-    /// Queries the local channel ID that is used to request beacons.
+    Price {
+        denom: String,
+        address: Option<String>,
+    },
+    /// Get the volume tiers
+    #[returns(VolumeTiersResponse)]
+    VolumeTiers {},
+    /// Gets the callback gas limit that applies to the given dapp
+    #[returns(CallbackGasLimitResponse)]
+    CallbackGasLimit { address: String },
+    /// Gets the callback gas surcharge
+    #[returns(CallbackGasSurchargeResponse)]
+    CallbackGasSurcharge {},
+    /// Queries the local ID of the primary channel that is used to request beacons.
     /// This channel is unset when the proxy is instantiated and will be set when
-    /// the channel is created. Once created, the value does not change anymore.
+    /// the first channel is created. It changes when the manager selects another
+    /// primary channel or the primary channel is closed.
     #[returns(GatewayChannelResponse)]
     GatewayChannel {},
-    /// Returns the list of allowed dapp addresses
+    /// Lists all connected gateway channels and their health, ordered by channel ID
+    #[returns(GatewayChannelsResponse)]
+    GatewayChannels {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns the list of allowed dapp addresses, ordered by address
     #[returns(AllowlistResponse)]
-    Allowlist {},
+    Allowlist {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
     /// Queries whether the given address is part of the proxy's allowlist.
     /// Whether or not the allowlist is enabled must be queried via the config.
     #[returns(IsAllowlistedResponse)]
     IsAllowlisted { address: String },
+    /// Returns the list of denied dapp addresses, ordered by address
+    #[returns(DenylistResponse)]
+    Denylist {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Queries whether the given address is part of the proxy's denylist
+    #[returns(IsDenylistedResponse)]
+    IsDenylisted { address: String },
+    /// Gets the manager that was proposed but did not accept yet
+    #[returns(PendingManagerResponse)]
+    PendingManager {},
+    /// Queries whether the given address receives failure callbacks
+    #[returns(FailureCallbacksEnabledResponse)]
+    FailureCallbacksEnabled { address: String },
+    /// Gets the prepaid credit of the given dapp
+    #[returns(CreditsResponse)]
+    Credits { address: String },
+    /// Gets the usage statistics of the given dapp
+    #[returns(DappStatsResponse)]
+    DappStats { address: String },
+    /// Lists the usage statistics of all dapps, ordered by address
+    #[returns(AllDappStatsResponse)]
+    AllDappStats {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the subscriptions of the given dapp, ordered by job ID prefix
+    #[returns(SubscriptionsResponse)]
+    Subscriptions {
+        sender: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the failed callbacks of the given dapp, ordered by job ID
+    #[returns(FailedCallbacksResponse)]
+    FailedCallbacks {
+        sender: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Gets whether the proxy is paused and why
+    #[returns(PausedResponse)]
+    Paused {},
+    /// Gets how long used job IDs are remembered. None if duplicate job IDs are allowed.
+    #[returns(JobIdRetentionResponse)]
+    JobIdRetention {},
+    /// Gets a job that was requested and not yet delivered
+    #[returns(JobResponse)]
+    Job { sender: String, job_id: String },
+    /// Lists the jobs of the given dapp that were requested and not yet delivered,
+    /// ordered by job ID
+    #[returns(JobsResponse)]
+    JobsBySender {
+        sender: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 #[cw_serde]
@@ -192,6 +484,31 @@ pub struct PriceResponse {
 #[cw_serde]
 pub struct GatewayChannelResponse {
     pub channel: Option<String>,
+    /// The IBC app version negotiated for the channel. None if no channel is set.
+    pub version: Option<String>,
+}
+
+#[cw_serde]
+pub struct GatewayChannelStatus {
+    pub channel_id: String,
+    /// The IBC app version negotiated for the channel
+    pub version: String,
+    pub enabled: bool,
+    pub primary: bool,
+    /// False if the channel has pending packets and did not make progress for a while.
+    /// Requests are not sent through unhealthy channels if there are healthy alternatives.
+    pub healthy: bool,
+    /// The number of request packets that were neither acknowledged nor timed out yet
+    pub pending_packets: u64,
+    /// The time of the last acknowledgement
+    pub last_ack: Option<Timestamp>,
+    /// Address of the payment contract of this channel (on the other chain)
+    pub payment: Option<String>,
+}
+
+#[cw_serde]
+pub struct GatewayChannelsResponse {
+    pub channels: Vec<GatewayChannelStatus>,
 }
 
 #[cw_serde]
@@ -200,6 +517,84 @@ pub struct AllowlistResponse {
     pub allowed: Vec<String>,
 }
 
+#[cw_serde]
+pub struct DenylistResponse {
+    /// List of addresses
+    pub denied: Vec<String>,
+}
+
+#[cw_serde]
+pub struct VolumeTiersResponse {
+    pub tiers: Vec<VolumeTier>,
+}
+
+#[cw_serde]
+pub struct CallbackGasLimitResponse {
+    pub gas_limit: u64,
+    /// True if the dapp requested the gas limit itself and pays the surcharge
+    pub surcharge: bool,
+}
+
+#[cw_serde]
+pub struct CallbackGasSurchargeResponse {
+    pub surcharge: Option<Decimal>,
+}
+
+#[cw_serde]
+pub struct DappStatsResponse {
+    pub address: Addr,
+    pub stats: DappStats,
+}
+
+#[cw_serde]
+pub struct AllDappStatsResponse {
+    pub dapps: Vec<DappStatsResponse>,
+}
+
+#[cw_serde]
+pub struct CreditsResponse {
+    pub credits: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct SubscriptionsResponse {
+    pub subscriptions: Vec<Subscription>,
+}
+
+#[cw_serde]
+pub struct FailedCallbacksResponse {
+    pub callbacks: Vec<FailedCallback>,
+}
+
+#[cw_serde]
+pub struct PausedResponse {
+    pub paused: bool,
+    /// The reason given when pausing. None if not paused.
+    pub reason: Option<String>,
+}
+
+#[cw_serde]
+pub struct JobIdRetentionResponse {
+    /// The retention in seconds
+    pub retention: Option<u64>,
+}
+
+#[cw_serde]
+pub struct JobResponse {
+    /// None if the job does not exist or was already delivered
+    pub job: Option<Job>,
+}
+
+#[cw_serde]
+pub struct JobsResponse {
+    pub jobs: Vec<Job>,
+}
+
+#[cw_serde]
+pub struct FailureCallbacksEnabledResponse {
+    pub enabled: bool,
+}
+
 #[cw_serde]
 pub struct IsAllowlistedResponse {
     /// Returns true if and only if the address is part of the proxy's allowlist.
@@ -207,6 +602,12 @@ pub struct IsAllowlistedResponse {
     pub listed: bool,
 }
 
+#[cw_serde]
+pub struct IsDenylistedResponse {
+    /// Returns true if and only if the address is part of the proxy's denylist.
+    pub listed: bool,
+}
+
 /// This struct contains information about the origin of the beacon request. It helps the
 /// proxy to route the beacon response to the final destination.
 /// The IBC communication between proxy and gateway does not need this information. It is
@@ -218,3 +619,18 @@ pub struct RequestBeaconOrigin {
     pub sender: String,
     pub job_id: String,
 }
+
+/// The message sent to dapps that opted into failure callbacks.
+/// Dapps add the `NoisReceiveFailure` variant to their execute message to handle it.
+#[cw_serde]
+pub enum FailureReceiverExecuteMsg {
+    NoisReceiveFailure { failure: NoisFailure },
+}
+
+#[cw_serde]
+pub struct NoisFailure {
+    /// The ID chosen by the dapp when requesting the randomness
+    pub job_id: String,
+    /// Why the request failed. The randomness for this job will never arrive.
+    pub reason: String,
+}
@@ -1,6 +1,6 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Coin, Timestamp, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, Coin, Decimal, HexBinary, Timestamp, Uint128};
+use cw_storage_plus::{Deque, Item, Map};
 
 /// The denom information required to send a MsgTransfer.
 /// Ideally we could just query the ICS-20 channel ID and did not have to store it,
@@ -28,6 +28,19 @@ pub enum OperationalMode {
     },
 }
 
+/// Defines how much of the paid funds a dapp gets back when its request fails,
+/// i.e. gets an error acknowledgement or times out.
+#[cw_serde]
+pub enum RefundPolicy {
+    /// Refund all funds paid for the job
+    Full {},
+    /// Refund the given share (between 0 and 1) of the funds paid for the job.
+    /// Amounts are rounded down.
+    Partial { share: Decimal },
+    /// Keep the funds
+    None {},
+}
+
 #[cw_serde]
 pub struct Config {
     /// The prices of a randomness. List is to be interpreted as oneof,
@@ -40,7 +53,9 @@ pub struct Config {
     pub test_mode: bool,
     /// The amount of gas that the callback to the dapp can consume
     pub callback_gas_limit: u64,
-    /// Address of the payment contract (on the other chain)
+    /// Address of the payment contract (on the other chain) from before multiple
+    /// gateway channels were supported. Payment contracts are stored per channel in
+    /// `GATEWAY_CHANNELS` now and this is moved there on migration.
     pub payment: Option<String>,
     /// The amount of tokens the proxy sends for each randomness request to the Nois chain
     pub nois_beacon_price: Uint128,
@@ -62,10 +77,28 @@ pub struct Config {
     /// This is an Option for compatibility with older versions of the contract that did not have the field.
     /// From instances running version 0.13.5 onwards, the value is always set to Some(..).
     pub max_after: Option<Timestamp>,
+    /// The minimal value for `after` relative to the block time of the request, in seconds.
+    /// E.g. 0 rejects values in the past. Applies in addition to `min_after` to `after`
+    /// values and rounds provided by the dapp but not to next beacons and subscriptions.
+    /// None means there is no relative lower bound.
+    pub min_after_offset: Option<u64>,
+    /// The maximum value for `after` relative to the block time of the request, in seconds.
+    /// E.g. 86400 rejects values more than a day in the future. Applies in addition to `max_after`
+    /// to `after` values and rounds provided by the dapp but not to next beacons and subscriptions.
+    /// None means there is no relative upper bound.
+    pub max_after_offset: Option<u64>,
+    /// How much of the paid funds a dapp gets back when its request fails.
+    /// This is an Option for compatibility with older versions of the contract that did not have the field.
+    /// None means no refunds.
+    pub refund_policy: Option<RefundPolicy>,
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+/// The reason why the proxy is paused. While set, new requests are rejected.
+/// Beacons of earlier requests are still delivered to the dapps.
+pub const PAUSED: Item<String> = Item::new("paused");
+
 /// List of addresses allowed to get randomness if allowlist enabled. To decide
 /// if an address is allowed, we consider only whether the address is present as
 /// a key. The u8 value itself is a dummy value.
@@ -74,9 +107,228 @@ pub const ALLOWLIST: Map<&Addr, u8> = Map::new("allowlist");
 /// Dummy value. Don't rely on the value but just check existence.
 pub const ALLOWLIST_MARKER: u8 = 1;
 
-/// Channel to the nois-gateway contract on the Nois chain
+/// List of addresses that are not allowed to get randomness, no matter if the
+/// allowlist is enabled or not. Like the allowlist, only the presence of the key
+/// matters and the value is [`ALLOWLIST_MARKER`].
+pub const DENYLIST: Map<&Addr, u8> = Map::new("denylist");
+
+/// Dapps that opted into failure callbacks. Only the presence of the key matters.
+pub const FAILURE_CALLBACKS: Map<&Addr, bool> = Map::new("failure_callbacks");
+
+/// A `NoisReceive` callback that failed, e.g. because it ran out of gas.
+/// The dapp can retry it using `ExecuteMsg::RetryCallback`.
+#[cw_serde]
+pub struct FailedCallback {
+    pub sender: Addr,
+    pub job_id: String,
+    pub published: Timestamp,
+    pub randomness: HexBinary,
+    /// The error message of the failed execution
+    pub error: String,
+}
+
+/// Failed callbacks by sender and job ID. The number of entries per sender is capped,
+/// see `ExecuteMsg::PruneFailedCallbacks`.
+pub const FAILED_CALLBACKS: Map<(&Addr, &str), FailedCallback> = Map::new("failed_callbacks");
+
+/// The callback sent by the current `DeliverBeacon` packet. The reply moves it to
+/// `FAILED_CALLBACKS` if the callback fails. Otherwise it is overwritten by the next delivery.
+pub const CALLBACK_IN_FLIGHT: Item<FailedCallback> = Item::new("callback_in_flight");
+
+/// A discount for dapps that requested at least `min_requests` beacons
+#[cw_serde]
+pub struct VolumeTier {
+    pub min_requests: u64,
+    /// The share of the price that is discounted (between 0 and 1)
+    pub discount: Decimal,
+}
+
+/// Volume tiers ordered by `min_requests`. They apply to dapps without a price override.
+pub const VOLUME_TIERS: Item<Vec<VolumeTier>> = Item::new("volume_tiers");
+
+/// Prices that replace `Config.prices` for specific dapps
+pub const PRICE_OVERRIDES: Map<&Addr, Vec<Coin>> = Map::new("price_overrides");
+
+/// A callback gas limit for a specific dapp
+#[cw_serde]
+pub struct DappCallbackGasLimit {
+    pub gas_limit: u64,
+    /// True if the dapp requested the gas limit itself. Then its prices
+    /// include the callback gas surcharge.
+    pub surcharge: bool,
+}
+
+/// Callback gas limits that replace `Config.callback_gas_limit` for specific dapps
+pub const CALLBACK_GAS_LIMITS: Map<&Addr, DappCallbackGasLimit> = Map::new("callback_gas_limits");
+
+/// The relative price increase for dapps that requested a higher callback gas limit
+/// per additional `Config.callback_gas_limit` of gas. E.g. with a surcharge of 0.5,
+/// doubling the gas limit increases the price by 50%.
+///
+/// Dapps can only request a callback gas limit when this is set.
+pub const CALLBACK_GAS_SURCHARGE: Item<Decimal> = Item::new("callback_gas_surcharge");
+
+/// Usage statistics of a dapp
+#[cw_serde]
+#[derive(Default)]
+pub struct DappStats {
+    /// The number of beacons requested
+    pub requests: u64,
+    /// The number of randomness callbacks that succeeded, including successful retries
+    pub delivered_callbacks: u64,
+    /// The number of randomness callbacks that failed and were not retried successfully
+    pub failed_callbacks: u64,
+    /// The prices charged for requests, including payments from prepaid credit.
+    /// Refunds of failed jobs and cancelled subscriptions are deducted.
+    pub fees: Vec<Coin>,
+}
+
+/// Usage statistics by dapp address
+pub const DAPP_STATS: Map<&Addr, DappStats> = Map::new("dapp_stats");
+
+/// Prepaid credit by dapp address and denom. Requests of the dapp are paid from
+/// the credit before attached funds are considered.
+pub const CREDITS: Map<(&Addr, &str), Uint128> = Map::new("credits");
+
+/// Funds the proxy holds on behalf of dapps by denom, i.e. prepaid credit, unused
+/// subscription funds and the funds of request packets in flight, which can be refunded.
+/// The manager cannot withdraw them.
+pub const RESERVED_FUNDS: Map<&str, Uint128> = Map::new("reserved_funds");
+
+/// The primary channel to the nois-gateway contract on the Nois chain.
+/// Requests are sent through this channel as long as it is healthy.
 pub const GATEWAY_CHANNEL: Item<String> = Item::new("gateway_channel");
 
+/// The IBC app version negotiated for the primary gateway channel before multiple
+/// channels were supported. Not set for channels established before the version was stored,
+/// which are `nois-v7` channels. It is moved to `GATEWAY_CHANNELS` on migration.
+pub const GATEWAY_CHANNEL_VERSION: Item<String> = Item::new("gateway_channel_version");
+
+/// A connected channel to the nois-gateway contract
+#[cw_serde]
+pub struct GatewayChannelInfo {
+    /// The IBC app version negotiated for the channel
+    pub version: String,
+    /// Only enabled channels are used for requests and accept packets from the gateway
+    pub enabled: bool,
+    /// The number of request packets that were neither acknowledged nor timed out yet
+    pub pending_packets: u64,
+    /// Set while there are pending packets. This is the time of the last acknowledgement
+    /// or timeout or the time the first pending packet was sent, whatever is later.
+    pub waiting_since: Option<Timestamp>,
+    /// The time of the last acknowledgement
+    pub last_ack: Option<Timestamp>,
+    /// Address of the payment contract of this channel (on the other chain).
+    /// Set by the `Welcome` packet of the gateway. Requests sent through this channel
+    /// are paid to this contract in IBC pay mode.
+    pub payment: Option<String>,
+}
+
+/// All connected gateway channels by channel ID
+pub const GATEWAY_CHANNELS: Map<&str, GatewayChannelInfo> = Map::new("gateway_channels");
+
+/// A randomness request that was sent to the gateway and not yet acknowledged
+#[cw_serde]
+pub struct PendingJob {
+    /// The address of the dapp that requested the randomness
+    pub sender: Addr,
+    pub job_id: String,
+    /// The funds the sender paid for this job
+    pub funds: Vec<Coin>,
+    /// True if the funds were paid from the sender's credit. Refunds are then
+    /// added back to the credit instead of being sent to the sender.
+    pub paid_from_credit: bool,
+}
+
+/// The jobs of request packets in flight, keyed by channel ID and packet sequence.
+/// A packet contains multiple jobs in case of batch requests.
+/// Entries are removed when the packet is acknowledged or times out.
+pub const PENDING_JOBS: Map<(&str, u64), Vec<PendingJob>> = Map::new("pending_jobs");
+
+/// The status of a job as reported by the gateway
+#[cw_serde]
+pub enum JobStatus {
+    /// The request was sent but not yet acknowledged
+    Sent {},
+    /// The beacon does not yet exist. The job is processed once it is published.
+    Queued {},
+    /// The beacon exists and the randomness is on its way
+    Processed {},
+}
+
+/// A job that was requested and not yet delivered
+#[cw_serde]
+pub struct Job {
+    pub sender: Addr,
+    pub job_id: String,
+    /// The `after` value of the request. Unset for requests of a specific round.
+    pub after: Option<Timestamp>,
+    /// The drand round of requests of a specific round
+    pub round: Option<u64>,
+    /// The block height of the request
+    pub height: u64,
+    pub status: JobStatus,
+    /// A RNG specific randomness source identifier, e.g. `drand:<network id>:<round>`.
+    /// Set once the request is acknowledged.
+    pub source_id: Option<String>,
+}
+
+/// How long used job IDs are remembered in seconds. While set, the proxy rejects
+/// requests that reuse a job ID of the same sender within this time.
+pub const JOB_ID_RETENTION: Item<u64> = Item::new("job_id_retention");
+
+/// The time a job ID was last used, by sender and job ID
+pub const USED_JOB_IDS: Map<(&Addr, &str), Timestamp> = Map::new("used_job_ids");
+
+#[cw_serde]
+pub struct UsedJobId {
+    pub sender: Addr,
+    pub job_id: String,
+    pub used: Timestamp,
+}
+
+/// The entries of `USED_JOB_IDS` in the order they were used. This allows
+/// removing expired entries from the front.
+pub const USED_JOB_IDS_QUEUE: Deque<UsedJobId> = Deque::new("used_job_ids_queue");
+
+/// Jobs by sender and job ID. Entries are removed when the randomness is delivered
+/// or the request failed. Jobs of request packets with an unknown sequence are not
+/// tracked.
+pub const JOBS: Map<(&Addr, &str), Job> = Map::new("jobs");
+
+/// A series of beacon requests with a fixed interval between the `after` values.
+///
+/// Only one beacon of a subscription is requested at a time. The next one is requested
+/// when the previous one is delivered and paid from the prepaid funds.
+#[cw_serde]
+pub struct Subscription {
+    pub sender: Addr,
+    /// Job IDs are this prefix followed by a 1-based counter
+    pub job_id_prefix: String,
+    /// Interval between two beacons in seconds
+    pub interval: u64,
+    /// The total number of beacons. Lowered to `requested` when the subscription is cancelled.
+    pub count: u32,
+    /// The number of beacons requested so far
+    pub requested: u32,
+    /// The `after` value of the next request
+    pub next_after: Timestamp,
+    /// The prepaid funds that are not yet used by a request
+    pub funds: Vec<Coin>,
+    /// True if the subscription was paid from the sender's credit. Refunds are then
+    /// added back to the credit instead of being sent to the sender.
+    pub paid_from_credit: bool,
+    /// The job ID of the request in flight. A subscription without a request in flight
+    /// and `requested < count` is stalled because a request failed.
+    pub pending_job: Option<String>,
+}
+
+/// Subscriptions by sender and job ID prefix
+pub const SUBSCRIPTIONS: Map<(&Addr, &str), Subscription> = Map::new("subscriptions");
+
+/// The job ID prefix of subscription requests in flight, by sender and job ID
+pub const SUBSCRIPTION_JOBS: Map<(&Addr, &str), String> = Map::new("subscription_jobs");
+
 /// We use this value to get publish times that are independent of the current clock
 /// in test mode. We want the following rounds to be the result. To get there we use
 /// a starting time of 1677687597000000000 - 1 nanoseconds and then increment by 30 seconds.
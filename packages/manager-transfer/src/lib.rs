@@ -0,0 +1,128 @@
+//! A two-step manager transfer shared by the Nois contracts.
+//!
+//! The current manager proposes a new manager, which then has to accept the role
+//! from its own address. Until then the current manager stays in charge and can
+//! cancel the transfer. This way a typo in the new address cannot lock the admin
+//! functionality of a contract.
+//!
+//! The contracts store the manager in their own config and are responsible for
+//! checking that only the current manager proposes or cancels a transfer.
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, StdError, StdResult, Storage};
+use cw_storage_plus::Item;
+use thiserror::Error;
+
+const PENDING_MANAGER: Item<Addr> = Item::new("pending_manager");
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ManagerTransferError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("No manager transfer pending.")]
+    NoPendingManager,
+
+    #[error("Sender is not the pending manager.")]
+    NotPendingManager,
+}
+
+#[cw_serde]
+pub struct PendingManagerResponse {
+    /// The address that was proposed as the new manager and did not accept yet
+    pub pending_manager: Option<Addr>,
+}
+
+/// Stores the proposed manager. An existing proposal is replaced.
+pub fn propose_manager(storage: &mut dyn Storage, manager: &Addr) -> StdResult<()> {
+    PENDING_MANAGER.save(storage, manager)
+}
+
+/// Completes the transfer if `sender` is the pending manager and returns the new manager.
+pub fn accept_manager(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+) -> Result<Addr, ManagerTransferError> {
+    let pending = PENDING_MANAGER
+        .may_load(storage)?
+        .ok_or(ManagerTransferError::NoPendingManager)?;
+    if pending != sender {
+        return Err(ManagerTransferError::NotPendingManager);
+    }
+    PENDING_MANAGER.remove(storage);
+    Ok(pending)
+}
+
+/// Removes the pending manager and returns it.
+pub fn cancel_manager_transfer(storage: &mut dyn Storage) -> Result<Addr, ManagerTransferError> {
+    let pending = PENDING_MANAGER
+        .may_load(storage)?
+        .ok_or(ManagerTransferError::NoPendingManager)?;
+    PENDING_MANAGER.remove(storage);
+    Ok(pending)
+}
+
+pub fn query_pending_manager(storage: &dyn Storage) -> StdResult<PendingManagerResponse> {
+    Ok(PendingManagerResponse {
+        pending_manager: PENDING_MANAGER.may_load(storage)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn propose_and_accept_works() {
+        let mut storage = MockStorage::new();
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+
+        assert_eq!(
+            query_pending_manager(&storage).unwrap().pending_manager,
+            None
+        );
+        let err = accept_manager(&mut storage, &alice).unwrap_err();
+        assert_eq!(err, ManagerTransferError::NoPendingManager);
+
+        propose_manager(&mut storage, &alice).unwrap();
+        assert_eq!(
+            query_pending_manager(&storage).unwrap().pending_manager,
+            Some(alice.clone())
+        );
+
+        // A new proposal replaces the old one
+        propose_manager(&mut storage, &bob).unwrap();
+        let err = accept_manager(&mut storage, &alice).unwrap_err();
+        assert_eq!(err, ManagerTransferError::NotPendingManager);
+
+        let new_manager = accept_manager(&mut storage, &bob).unwrap();
+        assert_eq!(new_manager, bob);
+        assert_eq!(
+            query_pending_manager(&storage).unwrap().pending_manager,
+            None
+        );
+        let err = accept_manager(&mut storage, &bob).unwrap_err();
+        assert_eq!(err, ManagerTransferError::NoPendingManager);
+    }
+
+    #[test]
+    fn cancel_manager_transfer_works() {
+        let mut storage = MockStorage::new();
+        let alice = Addr::unchecked("alice");
+
+        let err = cancel_manager_transfer(&mut storage).unwrap_err();
+        assert_eq!(err, ManagerTransferError::NoPendingManager);
+
+        propose_manager(&mut storage, &alice).unwrap();
+        let cancelled = cancel_manager_transfer(&mut storage).unwrap();
+        assert_eq!(cancelled, alice);
+        assert_eq!(
+            query_pending_manager(&storage).unwrap().pending_manager,
+            None
+        );
+        let err = accept_manager(&mut storage, &alice).unwrap_err();
+        assert_eq!(err, ManagerTransferError::NoPendingManager);
+    }
+}
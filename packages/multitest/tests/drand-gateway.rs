@@ -130,6 +130,7 @@ fn integration_test() {
             payment_code_id: code_id_nois_payment,
             payment_initial_funds: payment_initial(),
             sink: sink.clone(),
+            paused: None,
         }
     );
 
@@ -138,7 +139,6 @@ fn integration_test() {
         bossman.clone(),
         addr_nois_drand.to_owned(),
         &nois_drand::msg::ExecuteMsg::SetConfig {
-            manager: None,
             gateway: Some(addr_nois_gateway.to_string()),
             min_round: None,
             incentive_point_price: None,
@@ -167,11 +167,11 @@ fn integration_test() {
         manager.clone(),
         addr_nois_gateway.to_owned(),
         &nois_gateway::msg::ExecuteMsg::SetConfig {
-            manager: None,
             price: None,
             drand_addr: Some(addr_nois_drand.to_string()),
             trusted_sources: Some(vec![addr_nois_drand.to_string()]),
             payment_initial_funds: None,
+            sink: None,
         },
         &[],
     )
@@ -190,6 +190,7 @@ fn integration_test() {
             payment_code_id: code_id_nois_payment,
             payment_initial_funds: payment_initial(),
             sink: sink.clone(),
+            paused: None,
         }
     );
 
@@ -242,6 +243,9 @@ fn integration_test() {
                 allowlist_enabled: Some(false),
                 min_after: Some(instantiation_time),
                 max_after: Some(instantiation_time.plus_seconds(10 * 365 * 24 * 3600)),
+                min_after_offset: None,
+                max_after_offset: None,
+                refund_policy: Some(nois_proxy::state::RefundPolicy::None {}),
             },
         }
     );
@@ -83,16 +83,17 @@ fn integration_test() {
             payment_code_id: PAYMENT,
             payment_initial_funds: payment_initial(),
             sink: sink.clone(),
+            paused: None,
         }
     );
 
     // Set drand
     let msg = nois_gateway::msg::ExecuteMsg::SetConfig {
-        manager: None,
         price: None,
         drand_addr: Some(drand.to_string()),
         trusted_sources: Some(vec![drand.to_string()]),
         payment_initial_funds: None,
+        sink: None,
     };
     let _resp = app
         .execute_contract(manager.clone(), addr_nois_gateway.clone(), &msg, &[])
@@ -113,6 +114,7 @@ fn integration_test() {
             payment_code_id: PAYMENT,
             payment_initial_funds: payment_initial(),
             sink: sink.clone(),
+            paused: None,
         }
     );
 
@@ -164,6 +166,9 @@ fn integration_test() {
                 allowlist_enabled: Some(false),
                 min_after: Some(instantiation_time),
                 max_after: Some(instantiation_time.plus_seconds(10 * 365 * 24 * 3600)),
+                min_after_offset: None,
+                max_after_offset: None,
+                refund_policy: Some(nois_proxy::state::RefundPolicy::None {}),
             },
         }
     );
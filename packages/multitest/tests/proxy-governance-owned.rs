@@ -85,16 +85,17 @@ fn integration_test() {
             payment_code_id: PAYMENT,
             payment_initial_funds: payment_initial(),
             sink: sink.clone(),
+            paused: None,
         }
     );
 
     // Set drand
     let msg = nois_gateway::msg::ExecuteMsg::SetConfig {
-        manager: None,
         price: None,
         drand_addr: Some(drand.to_string()),
         trusted_sources: Some(vec![drand.to_string()]),
         payment_initial_funds: None,
+        sink: None,
     };
     let _resp = app
         .execute_contract(manager.clone(), addr_nois_gateway.clone(), &msg, &[])
@@ -115,6 +116,7 @@ fn integration_test() {
             payment_code_id: PAYMENT,
             payment_initial_funds: payment_initial(),
             sink: sink.clone(),
+            paused: None,
         }
     );
 
@@ -170,6 +172,9 @@ fn integration_test() {
                 allowlist_enabled: Some(false),
                 min_after: Some(instantiation_time),
                 max_after: Some(instantiation_time.plus_seconds(10 * 365 * 24 * 3600)),
+                min_after_offset: None,
+                max_after_offset: None,
+                refund_policy: Some(nois_proxy_governance_owned::state::RefundPolicy::None {}),
             },
         }
     );
@@ -188,6 +193,7 @@ fn integration_test() {
                 allowlist_enabled: None,
                 min_after: None,
                 max_after: None,
+                refund_policy: None,
             },
         )
         .unwrap();
@@ -196,7 +202,7 @@ fn integration_test() {
     let resp: nois_proxy_governance_owned::msg::ConfigResponse = app
         .wrap()
         .query_wasm_smart(
-            addr_nois_proxy,
+            addr_nois_proxy.clone(),
             &nois_proxy_governance_owned::msg::QueryMsg::Config {},
         )
         .unwrap();
@@ -215,9 +221,50 @@ fn integration_test() {
                 allowlist_enabled: Some(false),
                 min_after: Some(instantiation_time),
                 max_after: Some(instantiation_time.plus_seconds(10 * 365 * 24 * 3600)),
+                min_after_offset: None,
+                max_after_offset: None,
+                refund_policy: Some(nois_proxy_governance_owned::state::RefundPolicy::None {}),
+            },
+        }
+    );
+
+    // Pause and unpause via sudo
+    let _resp = app
+        .wasm_sudo(
+            addr_nois_proxy.clone(),
+            &nois_proxy_governance_owned::msg::SudoMsg::Pause {
+                reason: "Maintenance".to_string(),
             },
+        )
+        .unwrap();
+    let resp: nois_proxy_governance_owned::msg::PausedResponse = app
+        .wrap()
+        .query_wasm_smart(
+            addr_nois_proxy.clone(),
+            &nois_proxy_governance_owned::msg::QueryMsg::Paused {},
+        )
+        .unwrap();
+    assert_eq!(
+        resp,
+        nois_proxy_governance_owned::msg::PausedResponse {
+            paused: true,
+            reason: Some("Maintenance".to_string()),
         }
     );
+    let _resp = app
+        .wasm_sudo(
+            addr_nois_proxy.clone(),
+            &nois_proxy_governance_owned::msg::SudoMsg::Unpause {},
+        )
+        .unwrap();
+    let resp: nois_proxy_governance_owned::msg::PausedResponse = app
+        .wrap()
+        .query_wasm_smart(
+            addr_nois_proxy,
+            &nois_proxy_governance_owned::msg::QueryMsg::Paused {},
+        )
+        .unwrap();
+    assert!(!resp.paused);
 
     // Add verified round
     let msg = nois_gateway::msg::ExecuteMsg::AddVerifiedRound {
@@ -1,4 +1,4 @@
-pub use crate::{APP_ORDER, IBC_APP_VERSION};
+pub use crate::{APP_ORDER, IBC_APP_VERSION, IBC_APP_VERSION_V7, SUPPORTED_VERSIONS};
 use cosmwasm_std::IbcOrder;
 
 use thiserror::Error;
@@ -8,8 +8,8 @@ pub enum ChannelError {
     #[error("Only supports unordered channels")]
     InvalidChannelOrder,
 
-    #[error("Counterparty version must be '{0}'")]
-    InvalidChannelVersion(&'static str),
+    #[error("Counterparty version must be one of {0:?}")]
+    InvalidChannelVersion(&'static [&'static str]),
 }
 
 pub fn check_order(order: &IbcOrder) -> Result<(), ChannelError> {
@@ -20,10 +20,80 @@ pub fn check_order(order: &IbcOrder) -> Result<(), ChannelError> {
     }
 }
 
+/// Checks that the version is one of the supported versions
 pub fn check_version(version: &str) -> Result<(), ChannelError> {
-    if version != IBC_APP_VERSION {
-        Err(ChannelError::InvalidChannelVersion(IBC_APP_VERSION))
+    if !SUPPORTED_VERSIONS.contains(&version) {
+        Err(ChannelError::InvalidChannelVersion(SUPPORTED_VERSIONS))
     } else {
         Ok(())
     }
 }
+
+/// Picks the highest supported version that the counterparty can speak.
+///
+/// A counterparty proposing a version newer than any supported one gets the
+/// highest supported version, which it can then accept or reject in its handshake.
+pub fn negotiate_version(counterparty_version: &str) -> Result<&'static str, ChannelError> {
+    if let Some(supported) = SUPPORTED_VERSIONS
+        .iter()
+        .find(|supported| **supported == counterparty_version)
+    {
+        return Ok(supported);
+    }
+    match (
+        version_number(counterparty_version),
+        version_number(IBC_APP_VERSION),
+    ) {
+        (Some(proposed), Some(highest)) if proposed > highest => Ok(IBC_APP_VERSION),
+        _ => Err(ChannelError::InvalidChannelVersion(SUPPORTED_VERSIONS)),
+    }
+}
+
+/// Returns true if the channel version is `nois-v8` or later. Those channels support
+/// batch requests (`InPacket::RequestBeacons`) and requests for a specific round
+/// (`InPacket::RequestBeaconAtRound`).
+///
+/// Channels created before the version was stored are `nois-v7` channels.
+pub fn is_v8_or_later(version: Option<&str>) -> bool {
+    version_number(version.unwrap_or(IBC_APP_VERSION_V7)).is_some_and(|number| number >= 8)
+}
+
+fn version_number(version: &str) -> Option<u32> {
+    version.strip_prefix("nois-v")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_version_works() {
+        check_version("nois-v8").unwrap();
+        check_version("nois-v7").unwrap();
+        let expected_err = ChannelError::InvalidChannelVersion(SUPPORTED_VERSIONS);
+        assert_eq!(check_version("nois-v9").unwrap_err(), expected_err);
+        assert_eq!(check_version("nois-v6").unwrap_err(), expected_err);
+    }
+
+    #[test]
+    fn negotiate_version_works() {
+        assert_eq!(negotiate_version("nois-v8").unwrap(), "nois-v8");
+        assert_eq!(negotiate_version("nois-v7").unwrap(), "nois-v7");
+        // Newer counterparty falls back to the highest version we speak
+        assert_eq!(negotiate_version("nois-v9").unwrap(), "nois-v8");
+
+        let expected_err = ChannelError::InvalidChannelVersion(SUPPORTED_VERSIONS);
+        assert_eq!(negotiate_version("nois-v6").unwrap_err(), expected_err);
+        assert_eq!(negotiate_version("nois-vX").unwrap_err(), expected_err);
+        assert_eq!(negotiate_version("ics20-1").unwrap_err(), expected_err);
+        assert_eq!(negotiate_version("").unwrap_err(), expected_err);
+    }
+
+    #[test]
+    fn is_v8_or_later_works() {
+        assert!(is_v8_or_later(Some("nois-v8")));
+        assert!(is_v8_or_later(Some("nois-v9")));
+        assert!(!is_v8_or_later(Some("nois-v7")));
+        assert!(!is_v8_or_later(None));
+    }
+}
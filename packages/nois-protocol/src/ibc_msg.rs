@@ -1,5 +1,5 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Binary, HexBinary, Timestamp, Uint128};
+use cosmwasm_std::{from_json, to_json_string, Binary, HexBinary, StdAck, Timestamp, Uint128};
 
 /// This is the message we send over the IBC channel from nois-proxy to nois-gateway.
 #[cw_serde]
@@ -11,6 +11,26 @@ pub enum InPacket {
         /// The origin data set by the proxy in a proxy specific format.
         origin: Binary,
     },
+    /// Requests multiple independent beacons in one packet. This saves packets and relayer fees
+    /// compared to sending one `RequestBeacon` per job.
+    ///
+    /// The gateway processes the requests atomically, i.e. either all requests are accepted or
+    /// the whole packet fails. The number of requests must be between 1 and
+    /// `MAX_BEACON_REQUESTS_PER_PACKET`.
+    RequestBeacons {
+        /// Pairs of `after` and `origin`, equivalent to the fields of `RequestBeacon`.
+        requests: Vec<(Timestamp, Binary)>,
+    },
+    /// Requests the beacon of a specific drand round. This allows dapps to commit
+    /// publicly to a round before it is published.
+    ///
+    /// The round must be in the future at the time the gateway processes the request.
+    /// The acknowledgement is `InPacketAck::RequestProcessed` or `InPacketAck::RequestQueued`.
+    RequestBeaconAtRound {
+        round: u64,
+        /// The origin data set by the proxy in a proxy specific format.
+        origin: Binary,
+    },
     /// Requests the current price per beacon. This can change over time and potentially
     /// change per channel ID.
     /// The proxy can pull the beacon price but should also expect price updates to get pushed.
@@ -30,6 +50,9 @@ pub enum InPacketAck {
         /// A RNG specific randomness source identifier, e.g. `drand:<network id>:<round>`
         source_id: String,
     },
+    /// The response of the RequestBeacons packet. Contains one status per request in the
+    /// same order as the requests.
+    RequestBeacons { statuses: Vec<RequestBeaconStatus> },
     /// The response of the PullBeaconPrice packet.
     PullBeaconPrice {
         /// The time of this price info. Since packages are not ordered, we use this to only save
@@ -42,6 +65,96 @@ pub enum InPacketAck {
     },
 }
 
+/// The result of a single request in a `RequestBeacons` packet
+#[cw_serde]
+pub struct RequestBeaconStatus {
+    /// A RNG specific randomness source identifier, e.g. `drand:<network id>:<round>`
+    pub source_id: String,
+    /// True if the beacon does not yet exist and the request is queued for later.
+    /// False if the request was processed immediately.
+    pub queued: bool,
+}
+
+/// A machine readable reason for an error acknowledgement
+#[cw_serde]
+#[non_exhaustive]
+pub enum PacketErrorCode {
+    /// The origin data of a beacon request exceeds the length limit
+    OriginTooLong,
+    /// The customer is not allowed to send requests at the moment
+    CustomerSuspended,
+    /// The customer's payment contract cannot pay for the request
+    InsufficientPaymentBalance,
+    /// The packet data cannot be parsed
+    InvalidPacket,
+    /// The packet type is not supported by the receiver
+    UnsupportedPacket,
+    /// The number of beacon requests in the packet is zero or exceeds the limit
+    InvalidBatchSize,
+    /// The requested round is not in the future or too far in the future
+    InvalidRound,
+    /// The receiver does not accept new requests at the moment
+    Paused,
+    /// A request limit of the customer was exceeded
+    LimitExceeded,
+    /// Any other error. See the message for details.
+    Other,
+}
+
+impl PacketErrorCode {
+    /// The code as used in the JSON encoding, e.g. for event attributes
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PacketErrorCode::OriginTooLong => "origin_too_long",
+            PacketErrorCode::CustomerSuspended => "customer_suspended",
+            PacketErrorCode::InsufficientPaymentBalance => "insufficient_payment_balance",
+            PacketErrorCode::InvalidPacket => "invalid_packet",
+            PacketErrorCode::UnsupportedPacket => "unsupported_packet",
+            PacketErrorCode::InvalidBatchSize => "invalid_batch_size",
+            PacketErrorCode::InvalidRound => "invalid_round",
+            PacketErrorCode::Paused => "paused",
+            PacketErrorCode::LimitExceeded => "limit_exceeded",
+            PacketErrorCode::Other => "other",
+        }
+    }
+}
+
+/// The error that is encoded in error acknowledgements.
+///
+/// Use [`PacketError::to_ack`] and [`PacketError::from_ack_error`] to convert from and to the
+/// error string of a [`StdAck`].
+#[cw_serde]
+pub struct PacketError {
+    pub code: PacketErrorCode,
+    /// A human readable error message. This is not meant to be parsed.
+    pub message: String,
+}
+
+impl PacketError {
+    pub fn new(code: PacketErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Creates an error acknowledgement containing the JSON encoded error
+    pub fn to_ack(&self) -> StdAck {
+        match to_json_string(self) {
+            Ok(json) => StdAck::error(json),
+            Err(_) => StdAck::error(self.message.clone()),
+        }
+    }
+
+    /// Decodes the error string of an error acknowledgement.
+    ///
+    /// Counterparties that do not encode the error as JSON send a plain text error.
+    /// This is mapped to [`PacketErrorCode::Other`] with the text as message.
+    pub fn from_ack_error(error: &str) -> Self {
+        from_json(error.as_bytes()).unwrap_or_else(|_| Self::new(PacketErrorCode::Other, error))
+    }
+}
+
 /// This is the message we send over the IBC channel from nois-gateway to nois-proxy.
 #[cw_serde]
 #[non_exhaustive]
@@ -90,3 +203,53 @@ pub enum OutPacketAck {
     /// The ack the proxy must send when receiving a `OutPacket::PushBeaconPrice`.
     PushBeaconPrice {},
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_error_to_ack_works() {
+        let ack = PacketError::new(PacketErrorCode::Paused, "We are closed").to_ack();
+        assert_eq!(
+            ack,
+            StdAck::error(r#"{"code":"paused","message":"We are closed"}"#)
+        );
+    }
+
+    #[test]
+    fn packet_error_from_ack_error_works() {
+        let error =
+            PacketError::from_ack_error(r#"{"code":"origin_too_long","message":"Too long"}"#);
+        assert_eq!(
+            error,
+            PacketError::new(PacketErrorCode::OriginTooLong, "Too long")
+        );
+
+        // Round trip
+        let original = PacketError::new(PacketErrorCode::LimitExceeded, "Slow down");
+        let StdAck::Error(error) = original.to_ack() else {
+            panic!("Expected error ack");
+        };
+        assert_eq!(PacketError::from_ack_error(&error), original);
+
+        // Codes are encoded like `as_str`
+        for code in [
+            PacketErrorCode::CustomerSuspended,
+            PacketErrorCode::InsufficientPaymentBalance,
+            PacketErrorCode::InvalidBatchSize,
+        ] {
+            assert_eq!(
+                to_json_string(&code).unwrap(),
+                format!(r#""{}""#, code.as_str())
+            );
+        }
+
+        // Plain text
+        let error = PacketError::from_ack_error("Error processing packet: kaputt");
+        assert_eq!(
+            error,
+            PacketError::new(PacketErrorCode::Other, "Error processing packet: kaputt")
+        );
+    }
+}
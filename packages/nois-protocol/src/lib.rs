@@ -3,10 +3,18 @@ mod ibc_msg;
 
 use cosmwasm_std::IbcOrder;
 
-pub use checks::{check_order, check_version, ChannelError};
-pub use ibc_msg::{InPacket, InPacketAck, OutPacket, OutPacketAck};
+pub use checks::{check_order, check_version, is_v8_or_later, negotiate_version, ChannelError};
+pub use ibc_msg::{
+    InPacket, InPacketAck, OutPacket, OutPacketAck, PacketError, PacketErrorCode,
+    RequestBeaconStatus,
+};
 
-pub const IBC_APP_VERSION: &str = "nois-v7";
+pub const IBC_APP_VERSION_V7: &str = "nois-v7";
+pub const IBC_APP_VERSION_V8: &str = "nois-v8";
+/// The highest version of the protocol, which is proposed when opening channels
+pub const IBC_APP_VERSION: &str = IBC_APP_VERSION_V8;
+/// All versions that can be negotiated, highest first
+pub const SUPPORTED_VERSIONS: &[&str] = &[IBC_APP_VERSION_V8, IBC_APP_VERSION_V7];
 pub const APP_ORDER: IbcOrder = IbcOrder::Unordered;
 // we use this for tests to ensure it is rejected
 pub const BAD_APP_ORDER: IbcOrder = IbcOrder::Ordered;
@@ -21,5 +29,8 @@ pub const DELIVER_BEACON_PACKET_LIFETIME: u64 = 100 * 24 * 3600; // seconds
 pub const BEACON_PRICE_PACKET_LIFETIME: u64 = 100 * 24 * 3600; // seconds
 pub const WELCOME_PACKET_LIFETIME: u64 = 100 * 24 * 3600; // seconds
 
+/// The max number of requests in a single `InPacket::RequestBeacons`
+pub const MAX_BEACON_REQUESTS_PER_PACKET: usize = 100;
+
 /// Token transfers time out earlier than other messages to avoid locking funds for too long
 pub const TRANSFER_PACKET_LIFETIME: u64 = 24 * 3600; // seconds
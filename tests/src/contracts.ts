@@ -63,11 +63,11 @@ export interface GatewayExecuteMsg {
     readonly is_verifying_tx: boolean;
   };
   readonly set_config?: {
-    readonly manager?: null | string;
     readonly price?: null | Coin;
     readonly drand_addr?: null | string;
     readonly payment_initial_funds?: null | Coin;
     readonly trusted_sources?: null | string[];
+    readonly sink?: null | string;
   };
 }
 